@@ -0,0 +1,9 @@
+-- Group failover attempts of a single client request.
+ALTER TABLE request_logs
+  ADD COLUMN IF NOT EXISTS parent_request_id uuid;
+
+CREATE INDEX IF NOT EXISTS idx_request_logs_parent_request_id
+  ON request_logs(parent_request_id);
+
+COMMENT ON COLUMN request_logs.parent_request_id IS
+  'Request id shared by every upstream attempt made for the same client request.';
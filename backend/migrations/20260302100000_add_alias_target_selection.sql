@@ -0,0 +1,24 @@
+-- Per-alias target selection strategy, plus weight and priority per target.
+DO $$
+BEGIN
+  CREATE TYPE lb_strategy AS ENUM (
+    'round_robin',
+    'weighted_random',
+    'priority_with_fallback',
+    'least_latency'
+  );
+EXCEPTION
+  WHEN duplicate_object THEN NULL;
+END $$;
+
+ALTER TABLE aliases
+  ADD COLUMN IF NOT EXISTS strategy lb_strategy NOT NULL DEFAULT 'round_robin';
+
+ALTER TABLE alias_targets
+  ADD COLUMN IF NOT EXISTS weight integer NOT NULL DEFAULT 1 CHECK (weight > 0),
+  ADD COLUMN IF NOT EXISTS priority integer NOT NULL DEFAULT 0;
+
+COMMENT ON TYPE lb_strategy IS 'Target selection strategies for alias routing.';
+COMMENT ON COLUMN aliases.strategy IS 'Strategy used to order alias targets for each request.';
+COMMENT ON COLUMN alias_targets.weight IS 'Relative weight for weighted selection.';
+COMMENT ON COLUMN alias_targets.priority IS 'Priority for priority_with_fallback; lower values are tried first.';
@@ -0,0 +1,5 @@
+-- Temporary cooldown for provider keys that hit upstream rate limits or quotas.
+ALTER TABLE provider_keys
+  ADD COLUMN IF NOT EXISTS cooldown_until timestamptz;
+
+COMMENT ON COLUMN provider_keys.cooldown_until IS 'Key is skipped by routing until this time after a 429 or quota error.';
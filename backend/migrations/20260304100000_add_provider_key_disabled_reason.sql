@@ -0,0 +1,23 @@
+-- Track why and when a provider key was disabled so automatic disables can be re-validated.
+DO $$
+BEGIN
+  CREATE TYPE key_disabled_reason AS ENUM (
+    'manual',
+    'unauthorized'
+  );
+EXCEPTION
+  WHEN duplicate_object THEN NULL;
+END $$;
+
+ALTER TABLE provider_keys
+  ADD COLUMN IF NOT EXISTS disabled_reason key_disabled_reason,
+  ADD COLUMN IF NOT EXISTS disabled_at timestamptz;
+
+-- Keys disabled before this migration were disabled by hand or by a 401; treat them as manual.
+UPDATE provider_keys
+SET disabled_reason = 'manual', disabled_at = now()
+WHERE enabled = false AND disabled_reason IS NULL;
+
+COMMENT ON TYPE key_disabled_reason IS 'Why a provider key was disabled.';
+COMMENT ON COLUMN provider_keys.disabled_reason IS 'manual for admin disables, unauthorized for keys disabled after an upstream 401.';
+COMMENT ON COLUMN provider_keys.disabled_at IS 'When the key was last disabled.';
@@ -0,0 +1,20 @@
+-- Conversation state for Responses API requests emulated over chat completions upstreams.
+CREATE TABLE IF NOT EXISTS stored_responses (
+  id text PRIMARY KEY,
+  gateway_key_id uuid NOT NULL,
+  model text NOT NULL,
+  input jsonb NOT NULL,
+  output jsonb NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_stored_responses_created_at
+  ON stored_responses(created_at);
+
+COMMENT ON TABLE stored_responses IS 'Responses generated by the gateway itself, kept so later requests can continue them via previous_response_id.';
+COMMENT ON COLUMN stored_responses.id IS 'Response id returned to the client.';
+COMMENT ON COLUMN stored_responses.gateway_key_id IS 'Gateway key that created the response; only it may continue the conversation.';
+COMMENT ON COLUMN stored_responses.model IS 'Model (alias) requested by the client.';
+COMMENT ON COLUMN stored_responses.input IS 'Full input items of the request, including those inherited from earlier responses.';
+COMMENT ON COLUMN stored_responses.output IS 'Output items of the response.';
+COMMENT ON COLUMN stored_responses.created_at IS 'Row creation timestamp.';
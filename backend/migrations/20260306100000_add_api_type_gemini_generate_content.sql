@@ -0,0 +1,2 @@
+-- Add gemini_generate_content to api_type enum
+ALTER TYPE api_type ADD VALUE IF NOT EXISTS 'gemini_generate_content';
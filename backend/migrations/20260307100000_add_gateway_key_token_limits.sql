@@ -0,0 +1,7 @@
+-- Token-based rate limits on gateway keys, next to the request-based ones.
+ALTER TABLE gateway_keys
+  ADD COLUMN IF NOT EXISTS rate_limit_tpm integer,
+  ADD COLUMN IF NOT EXISTS rate_limit_tpd integer;
+
+COMMENT ON COLUMN gateway_keys.rate_limit_tpm IS 'Maximum prompt plus completion tokens per minute; NULL means unlimited.';
+COMMENT ON COLUMN gateway_keys.rate_limit_tpd IS 'Maximum prompt plus completion tokens per day; NULL means unlimited.';
@@ -0,0 +1,5 @@
+-- Cap on requests a gateway key may have in flight at once, streaming bodies included.
+ALTER TABLE gateway_keys
+  ADD COLUMN IF NOT EXISTS max_concurrent_requests integer;
+
+COMMENT ON COLUMN gateway_keys.max_concurrent_requests IS 'Maximum requests in flight at once, held until the response body ends; NULL means unlimited.';
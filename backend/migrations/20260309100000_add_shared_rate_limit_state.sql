@@ -0,0 +1,36 @@
+-- Rate limit and login protection state shared by gateway replicas when RATE_LIMIT_BACKEND=postgres.
+-- Bucket and in-flight state is short-lived, so those tables skip the WAL.
+CREATE UNLOGGED TABLE IF NOT EXISTS rate_limit_buckets (
+  gateway_key_id uuid NOT NULL REFERENCES gateway_keys(id) ON DELETE CASCADE,
+  bucket text NOT NULL,
+  tokens double precision NOT NULL,
+  refilled_at timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY (gateway_key_id, bucket)
+);
+
+CREATE UNLOGGED TABLE IF NOT EXISTS rate_limit_in_flight (
+  id uuid PRIMARY KEY,
+  gateway_key_id uuid NOT NULL REFERENCES gateway_keys(id) ON DELETE CASCADE,
+  acquired_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_rate_limit_in_flight_gateway_key
+  ON rate_limit_in_flight (gateway_key_id);
+
+CREATE TABLE IF NOT EXISTS login_failures (
+  ip text NOT NULL,
+  failed_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_failures_ip ON login_failures (ip, failed_at);
+
+CREATE TABLE IF NOT EXISTS login_bans (
+  ip text PRIMARY KEY,
+  banned_at timestamptz NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE rate_limit_buckets IS 'Token buckets per gateway key: rps, rpm, tpm and tpd.';
+COMMENT ON COLUMN rate_limit_buckets.tokens IS 'Tokens left as of refilled_at; negative while a key owes tokens after reconciliation.';
+COMMENT ON TABLE rate_limit_in_flight IS 'One row per request in flight, for max_concurrent_requests.';
+COMMENT ON TABLE login_failures IS 'Failed logins per client address within the failure window.';
+COMMENT ON TABLE login_bans IS 'Client addresses banned after too many failed logins.';
@@ -0,0 +1,32 @@
+-- Spend budgets per gateway key, priced from the token usage recorded in request_logs.
+DO $$
+BEGIN
+  CREATE TYPE budget_period AS ENUM (
+    'daily',
+    'weekly',
+    'monthly'
+  );
+EXCEPTION
+  WHEN duplicate_object THEN NULL;
+END $$;
+
+CREATE TABLE IF NOT EXISTS gateway_key_budgets (
+  gateway_key_id uuid PRIMARY KEY REFERENCES gateway_keys(id) ON DELETE CASCADE,
+  hard_limit double precision,
+  soft_limit double precision,
+  period budget_period NOT NULL DEFAULT 'monthly',
+  model_prices jsonb NOT NULL DEFAULT '{}'::jsonb,
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- Spend is summed over a key's logs since the start of the period.
+CREATE INDEX IF NOT EXISTS idx_request_logs_gateway_key_created_at
+  ON request_logs(gateway_key_id, created_at);
+
+COMMENT ON TYPE budget_period IS 'How often a gateway key budget resets, in UTC.';
+COMMENT ON TABLE gateway_key_budgets IS 'Spend budget of a gateway key.';
+COMMENT ON COLUMN gateway_key_budgets.hard_limit IS 'Spend per period above which requests are rejected; NULL means none.';
+COMMENT ON COLUMN gateway_key_budgets.soft_limit IS 'Spend per period above which responses carry a warning; NULL means none.';
+COMMENT ON COLUMN gateway_key_budgets.period IS 'Budget period; spend resets at its start.';
+COMMENT ON COLUMN gateway_key_budgets.model_prices IS 'Prices per million prompt and completion tokens, keyed by model or alias name.';
+COMMENT ON COLUMN gateway_key_budgets.updated_at IS 'When the budget was last changed.';
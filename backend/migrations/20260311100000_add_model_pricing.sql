@@ -0,0 +1,25 @@
+-- Token prices per upstream model, used to compute the cost of each request.
+CREATE TABLE IF NOT EXISTS model_prices (
+  id uuid PRIMARY KEY DEFAULT uuidv7(),
+  provider_id uuid NOT NULL REFERENCES providers(id) ON DELETE CASCADE,
+  model_id text NOT NULL,
+  input_per_million double precision NOT NULL CHECK (input_per_million >= 0),
+  output_per_million double precision NOT NULL CHECK (output_per_million >= 0),
+  cached_input_per_million double precision CHECK (cached_input_per_million >= 0),
+  created_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now(),
+  UNIQUE (provider_id, model_id)
+);
+
+ALTER TABLE request_logs
+ADD COLUMN IF NOT EXISTS cached_tokens integer,
+ADD COLUMN IF NOT EXISTS cost double precision;
+
+COMMENT ON TABLE model_prices IS 'Token prices of an upstream model at a provider.';
+COMMENT ON COLUMN model_prices.model_id IS 'Upstream model id the price applies to.';
+COMMENT ON COLUMN model_prices.input_per_million IS 'Price per million uncached prompt tokens.';
+COMMENT ON COLUMN model_prices.output_per_million IS 'Price per million completion tokens.';
+COMMENT ON COLUMN model_prices.cached_input_per_million IS 'Price per million prompt tokens read from the provider cache; NULL means the input price.';
+COMMENT ON COLUMN model_prices.updated_at IS 'When the price was last changed.';
+COMMENT ON COLUMN request_logs.cached_tokens IS 'Prompt tokens served from the provider prompt cache.';
+COMMENT ON COLUMN request_logs.cost IS 'Cost of the request from model_prices at the time it was logged; NULL when unpriced.';
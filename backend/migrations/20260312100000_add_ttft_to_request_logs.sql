@@ -0,0 +1,5 @@
+-- Time to first token of streamed responses.
+ALTER TABLE request_logs
+ADD COLUMN IF NOT EXISTS ttft_ms integer;
+
+COMMENT ON COLUMN request_logs.ttft_ms IS 'Milliseconds from request start to the first streamed chunk; NULL for buffered responses.';
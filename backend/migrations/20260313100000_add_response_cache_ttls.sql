@@ -0,0 +1,9 @@
+-- Response cache TTL overrides per alias and per gateway key.
+ALTER TABLE aliases
+  ADD COLUMN IF NOT EXISTS cache_ttl_secs integer CHECK (cache_ttl_secs >= 0);
+
+ALTER TABLE gateway_keys
+  ADD COLUMN IF NOT EXISTS cache_ttl_secs integer CHECK (cache_ttl_secs >= 0);
+
+COMMENT ON COLUMN aliases.cache_ttl_secs IS 'Oldest cached response served for this alias, in seconds; NULL uses the global TTL.';
+COMMENT ON COLUMN gateway_keys.cache_ttl_secs IS 'Oldest cached response served to this key, in seconds; overrides the alias TTL; NULL inherits.';
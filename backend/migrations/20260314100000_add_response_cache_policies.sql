@@ -0,0 +1,39 @@
+-- Response cache policy per alias and gateway key, and the decision behind each cache event.
+DO $$
+BEGIN
+  CREATE TYPE cache_policy AS ENUM (
+    'disabled',
+    'per_key',
+    'shared'
+  );
+EXCEPTION
+  WHEN duplicate_object THEN NULL;
+END $$;
+
+ALTER TABLE aliases
+  ADD COLUMN IF NOT EXISTS cache_policy cache_policy,
+  ADD COLUMN IF NOT EXISTS cache_non_deterministic boolean;
+
+ALTER TABLE gateway_keys
+  ADD COLUMN IF NOT EXISTS cache_policy cache_policy,
+  ADD COLUMN IF NOT EXISTS cache_non_deterministic boolean;
+
+-- Misses and bypasses are logged too, so not every event has a layer.
+ALTER TABLE cache_log
+  ALTER COLUMN cache_layer DROP NOT NULL,
+  ADD COLUMN IF NOT EXISTS decision text NOT NULL DEFAULT 'hit'
+    CHECK (decision IN ('hit', 'miss', 'bypass')),
+  ADD COLUMN IF NOT EXISTS cache_policy cache_policy,
+  ADD COLUMN IF NOT EXISTS reason text;
+
+CREATE INDEX IF NOT EXISTS idx_cache_log_decision_created_at
+  ON cache_log(decision, created_at);
+
+COMMENT ON TYPE cache_policy IS 'Who may be served a cached response: nobody, the same gateway key, or every key sharing the policy.';
+COMMENT ON COLUMN aliases.cache_policy IS 'Response cache policy for this alias; NULL inherits. The most restrictive of alias and key applies.';
+COMMENT ON COLUMN aliases.cache_non_deterministic IS 'Cache requests sampled with temperature > 0; NULL means no.';
+COMMENT ON COLUMN gateway_keys.cache_policy IS 'Response cache policy for this key; NULL inherits. The most restrictive of alias and key applies.';
+COMMENT ON COLUMN gateway_keys.cache_non_deterministic IS 'Cache requests sampled with temperature > 0; overrides the alias; NULL inherits.';
+COMMENT ON COLUMN cache_log.decision IS 'Outcome of the cache middleware: hit, miss or bypass.';
+COMMENT ON COLUMN cache_log.cache_policy IS 'Policy in effect for the request.';
+COMMENT ON COLUMN cache_log.reason IS 'Why the lookup was bypassed, for bypass decisions.';
@@ -0,0 +1,9 @@
+-- Stream mode of logged requests, so cached responses replay in the right format.
+ALTER TABLE request_logs
+ADD COLUMN IF NOT EXISTS stream boolean NOT NULL DEFAULT false;
+
+UPDATE request_logs
+SET stream = true
+WHERE response_content_type LIKE 'text/event-stream%';
+
+COMMENT ON COLUMN request_logs.stream IS 'Whether the client asked for a streamed response; the stream flag is left out of request_body_hash.';
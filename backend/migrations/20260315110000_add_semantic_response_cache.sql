@@ -0,0 +1,28 @@
+-- Semantic response cache: prompt embeddings mapped to exact cache keys.
+CREATE TABLE IF NOT EXISTS response_cache_embeddings (
+  request_body_hash text PRIMARY KEY,
+  context_hash text NOT NULL,
+  embedding real[] NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_response_cache_embeddings_context_hash_created_at
+  ON response_cache_embeddings(context_hash, created_at DESC);
+
+ALTER TABLE aliases
+  ADD COLUMN IF NOT EXISTS semantic_cache_threshold real
+    CHECK (semantic_cache_threshold > 0 AND semantic_cache_threshold <= 1);
+
+ALTER TABLE cache_log
+  DROP CONSTRAINT IF EXISTS cache_log_cache_layer_check,
+  ADD CONSTRAINT cache_log_cache_layer_check
+    CHECK (cache_layer IN ('moka', 'database', 'semantic')),
+  ADD COLUMN IF NOT EXISTS similarity real;
+
+COMMENT ON TABLE response_cache_embeddings IS 'Embeddings of the last user message of cached requests, for the semantic response cache.';
+COMMENT ON COLUMN response_cache_embeddings.request_body_hash IS 'Exact response cache key the embedding resolves to.';
+COMMENT ON COLUMN response_cache_embeddings.context_hash IS 'Hash of the request without its last user message, in the same cache scope; only requests with equal context are compared.';
+COMMENT ON COLUMN response_cache_embeddings.embedding IS 'Embedding of the last user message.';
+COMMENT ON COLUMN aliases.semantic_cache_threshold IS 'Cosine similarity from which a semantically close cached response is served; NULL disables the semantic cache.';
+COMMENT ON COLUMN cache_log.cache_layer IS 'Cache layer that served a hit: moka, database or semantic.';
+COMMENT ON COLUMN cache_log.similarity IS 'Cosine similarity of semantic hits.';
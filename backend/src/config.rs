@@ -7,6 +7,9 @@ use serde::Deserialize;
 pub struct AppConfig {
     pub server: ServerConfig,
     pub database: DatabaseConfig,
+    pub responses: ResponsesConfig,
+    pub rate_limit: RateLimitConfig,
+    pub response_cache: ResponseCacheConfig,
     pub jwt_secret: String,
 }
 
@@ -15,6 +18,11 @@ pub struct ServerConfig {
     pub host: String,
     pub port: u16,
     pub graceful_shutdown_timeout_secs: u64,
+    /// Time between a shutdown signal and refusing new connections, during
+    /// which `/readyz` already reports the instance as unready.
+    pub shutdown_drain_secs: u64,
+    /// Serve `/metrics` on this port instead of the main one.
+    pub metrics_port: Option<u16>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,6 +31,48 @@ pub struct DatabaseConfig {
     pub max_connections: u32,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ResponsesConfig {
+    /// Keep emulated Responses API conversations so `previous_response_id`
+    /// works on chat completions upstreams.
+    pub store_enabled: bool,
+    /// Stored conversations older than this are purged.
+    pub store_retention_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RateLimitConfig {
+    pub backend: RateLimitBackend,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResponseCacheConfig {
+    /// Oldest cached response served when neither the alias nor the gateway
+    /// key sets a TTL.
+    pub ttl_secs: u64,
+    /// Entries kept in the in-memory layer.
+    pub max_entries: u64,
+    /// Responses with larger bodies are never served from the cache.
+    pub max_body_bytes: usize,
+    /// Delay between events when replaying a cached stream.
+    pub stream_replay_interval_ms: u64,
+    /// Serve cached buffered responses to streaming requests and the other
+    /// way round, for chat completions and Anthropic messages.
+    pub stream_synthesis: bool,
+    /// Embeddings alias used by the semantic cache; unset disables it.
+    pub semantic_embeddings_alias: Option<String>,
+}
+
+/// Where rate limit and login protection state is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitBackend {
+    /// Per process; each replica enforces limits on its own.
+    Memory,
+    /// Shared by all replicas through the database.
+    Postgres,
+}
+
 pub fn load_config() -> anyhow::Result<AppConfig> {
     let server_host = env::var("SERVER_HOST").unwrap_or("0.0.0.0".to_string());
     let server_port = env::var("SERVER_PORT")
@@ -38,6 +88,24 @@ pub fn load_config() -> anyhow::Result<AppConfig> {
         })
         .transpose()?
         .unwrap_or(30);
+    let shutdown_drain_secs = env::var("SERVER_SHUTDOWN_DRAIN_SECS")
+        .ok()
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .map_err(|err| anyhow!("SERVER_SHUTDOWN_DRAIN_SECS must be a u64: {err}"))
+        })
+        .transpose()?
+        .unwrap_or(5);
+
+    let metrics_port = env::var("METRICS_PORT")
+        .ok()
+        .map(|value| {
+            value
+                .parse::<u16>()
+                .map_err(|err| anyhow!("METRICS_PORT must be a u16: {err}"))
+        })
+        .transpose()?;
 
     let database_url = env::var("DATABASE_URL")
         .unwrap_or_else(|_| "postgres://postgres@192.168.255.201:5432/af".to_string());
@@ -51,6 +119,89 @@ pub fn load_config() -> anyhow::Result<AppConfig> {
         .transpose()?
         .unwrap_or(10);
 
+    let responses_store_enabled = env::var("RESPONSES_STORE_ENABLED")
+        .ok()
+        .map(|value| {
+            value
+                .parse::<bool>()
+                .map_err(|err| anyhow!("RESPONSES_STORE_ENABLED must be a bool: {err}"))
+        })
+        .transpose()?
+        .unwrap_or(false);
+    let responses_store_retention_secs = env::var("RESPONSES_STORE_RETENTION_SECS")
+        .ok()
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .map_err(|err| anyhow!("RESPONSES_STORE_RETENTION_SECS must be a u64: {err}"))
+        })
+        .transpose()?
+        .unwrap_or(30 * 24 * 60 * 60);
+
+    let rate_limit_backend = env::var("RATE_LIMIT_BACKEND")
+        .ok()
+        .map(|value| match value.as_str() {
+            "memory" => Ok(RateLimitBackend::Memory),
+            "postgres" => Ok(RateLimitBackend::Postgres),
+            _ => Err(anyhow!(
+                "RATE_LIMIT_BACKEND must be memory or postgres, got {value}"
+            )),
+        })
+        .transpose()?
+        .unwrap_or(RateLimitBackend::Memory);
+
+    let response_cache_ttl_secs = env::var("RESPONSE_CACHE_TTL_SECS")
+        .ok()
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .map_err(|err| anyhow!("RESPONSE_CACHE_TTL_SECS must be a u64: {err}"))
+        })
+        .transpose()?
+        .unwrap_or(60 * 60);
+    let response_cache_max_entries = env::var("RESPONSE_CACHE_MAX_ENTRIES")
+        .ok()
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .map_err(|err| anyhow!("RESPONSE_CACHE_MAX_ENTRIES must be a u64: {err}"))
+        })
+        .transpose()?
+        .unwrap_or(1000);
+    let response_cache_max_body_bytes = env::var("RESPONSE_CACHE_MAX_BODY_BYTES")
+        .ok()
+        .map(|value| {
+            value
+                .parse::<usize>()
+                .map_err(|err| anyhow!("RESPONSE_CACHE_MAX_BODY_BYTES must be a usize: {err}"))
+        })
+        .transpose()?
+        .unwrap_or(1024 * 1024);
+    let response_cache_stream_replay_interval_ms =
+        env::var("RESPONSE_CACHE_STREAM_REPLAY_INTERVAL_MS")
+            .ok()
+            .map(|value| {
+                value.parse::<u64>().map_err(|err| {
+                    anyhow!("RESPONSE_CACHE_STREAM_REPLAY_INTERVAL_MS must be a u64: {err}")
+                })
+            })
+            .transpose()?
+            .unwrap_or(10);
+    let response_cache_stream_synthesis = env::var("RESPONSE_CACHE_STREAM_SYNTHESIS")
+        .ok()
+        .map(|value| {
+            value
+                .parse::<bool>()
+                .map_err(|err| anyhow!("RESPONSE_CACHE_STREAM_SYNTHESIS must be a bool: {err}"))
+        })
+        .transpose()?
+        .unwrap_or(false);
+
+    let response_cache_semantic_embeddings_alias =
+        env::var("RESPONSE_CACHE_SEMANTIC_EMBEDDINGS_ALIAS")
+            .ok()
+            .filter(|value| !value.trim().is_empty());
+
     let jwt_secret = env::var("JWT_SECRET")
         .unwrap_or("F0oA/t+6Ia2rs/oWEvCjOUYk67kWKhOISNDzrDP6WHM=".to_string());
 
@@ -59,11 +210,28 @@ pub fn load_config() -> anyhow::Result<AppConfig> {
             host: server_host,
             port: server_port,
             graceful_shutdown_timeout_secs,
+            shutdown_drain_secs,
+            metrics_port,
         },
         database: DatabaseConfig {
             url: database_url,
             max_connections: database_max_connections,
         },
+        responses: ResponsesConfig {
+            store_enabled: responses_store_enabled,
+            store_retention_secs: responses_store_retention_secs,
+        },
+        rate_limit: RateLimitConfig {
+            backend: rate_limit_backend,
+        },
+        response_cache: ResponseCacheConfig {
+            ttl_secs: response_cache_ttl_secs,
+            max_entries: response_cache_max_entries,
+            max_body_bytes: response_cache_max_body_bytes,
+            stream_replay_interval_ms: response_cache_stream_replay_interval_ms,
+            stream_synthesis: response_cache_stream_synthesis,
+            semantic_embeddings_alias: response_cache_semantic_embeddings_alias,
+        },
         jwt_secret,
     })
 }
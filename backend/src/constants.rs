@@ -1,2 +1,33 @@
 pub const MAX_REQUEST_BODY_BYTES: usize = 100 * 1024 * 1024;
 pub const UPSTREAM_USER_AGENT: &str = "RooCode/3.28.16";
+pub const UPSTREAM_CONNECT_TIMEOUT_SECS: u64 = 10;
+pub const UPSTREAM_READ_TIMEOUT_SECS: u64 = 300;
+pub const MAX_UPSTREAM_ATTEMPTS: usize = 8;
+pub const CIRCUIT_BREAKER_FAILURE_THRESHOLD: u32 = 5;
+pub const CIRCUIT_BREAKER_ERROR_RATE: f64 = 0.5;
+pub const CIRCUIT_BREAKER_MIN_REQUESTS: u32 = 20;
+pub const CIRCUIT_BREAKER_WINDOW_SECS: u64 = 60;
+pub const CIRCUIT_BREAKER_COOLDOWN_SECS: u64 = 30;
+pub const KEY_COOLDOWN_DEFAULT_SECS: u64 = 30;
+pub const KEY_QUOTA_COOLDOWN_SECS: u64 = 60 * 60;
+pub const KEY_COOLDOWN_MAX_SECS: u64 = 24 * 60 * 60;
+pub const KEY_REVALIDATION_INTERVAL_SECS: u64 = 10 * 60;
+pub const KEY_REVALIDATION_TIMEOUT_SECS: u64 = 30;
+pub const STORED_RESPONSES_PURGE_INTERVAL_SECS: u64 = 60 * 60;
+pub const ANTHROPIC_DEFAULT_MAX_TOKENS: u32 = 4096;
+pub const GEMINI_MODELS_PATH: &str = "/v1beta/models/";
+pub const ESTIMATED_CHARS_PER_TOKEN: usize = 4;
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+pub const IN_FLIGHT_LEASE_SECS: u64 = 60 * 60;
+pub const LOGIN_FAILURE_WINDOW_SECS: u64 = 60;
+pub const LOGIN_MAX_FAILURES: usize = 5;
+pub const BUDGET_WARNING_HEADER: &str = "x-af-budget-warning";
+pub const BUDGET_SPEND_REFRESH_SECS: u64 = 30;
+pub const MAX_STATS_BUCKETS: i64 = 10_000;
+pub const DEFAULT_STATS_TOP_N: i64 = 10;
+pub const MAX_STATS_TOP_N: i64 = 100;
+pub const READINESS_CHECK_TIMEOUT_SECS: u64 = 2;
+pub const CACHE_STATUS_HEADER: &str = "x-af-cache";
+pub const CACHE_LAYER_HEADER: &str = "x-af-cache-layer";
+pub const CACHE_TTL_HEADER: &str = "x-af-cache-ttl";
+pub const SEMANTIC_CACHE_MAX_CANDIDATES: i64 = 200;
@@ -4,7 +4,7 @@ use sqlx::PgPool;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-use super::types::ApiType;
+use super::types::{ApiType, LbStrategy};
 use crate::error::AppResult;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +15,8 @@ pub struct AliasTarget {
     pub model_id: String,
     pub extra_fields: Value,
     pub usage_count: i64,
+    pub weight: i32,
+    pub priority: i32,
     pub enabled: bool,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
@@ -33,7 +35,11 @@ pub struct AliasTargetDetail {
     pub usage_count: i64,
     pub provider_endpoint_id: Option<Uuid>,
     pub endpoint_url: Option<String>,
+    pub endpoint_api_type: Option<ApiType>,
     pub model_id: String,
+    pub weight: i32,
+    pub priority: i32,
+    pub strategy: LbStrategy,
     pub enabled: bool,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
@@ -43,8 +49,9 @@ pub async fn fetch_all_alias_target_details(
     pool: &PgPool,
     alias_id: Uuid,
 ) -> AppResult<Vec<AliasTargetDetail>> {
-    let rows = sqlx::query!(
-        "SELECT
+    let details = sqlx::query_as!(
+        AliasTargetDetail,
+        r#"SELECT
             at.id,
             a.id AS alias_id,
             a.name AS alias_name,
@@ -54,7 +61,13 @@ pub async fn fetch_all_alias_target_details(
             p.name AS provider_name,
             p.usage_count AS provider_usage_count,
             at.usage_count,
+            NULL::uuid AS provider_endpoint_id,
+            NULL::text AS endpoint_url,
+            NULL::api_type AS "endpoint_api_type: ApiType",
             at.model_id,
+            at.weight,
+            at.priority,
+            a.strategy AS "strategy: LbStrategy",
             at.enabled,
             at.created_at
          FROM aliases a
@@ -63,32 +76,12 @@ pub async fn fetch_all_alias_target_details(
          JOIN providers p
            ON p.id = at.provider_id
          WHERE a.id = $1
-         ORDER BY at.usage_count ASC, p.id",
+         ORDER BY at.usage_count ASC, p.id"#,
         alias_id
     )
     .fetch_all(pool)
     .await?;
 
-    let mut details = Vec::with_capacity(rows.len());
-    for row in rows {
-        details.push(AliasTargetDetail {
-            id: row.id,
-            alias_id: row.alias_id,
-            alias_name: row.alias_name,
-            alias_target_id: row.alias_target_id,
-            extra_fields: row.extra_fields,
-            provider_id: row.provider_id,
-            provider_name: row.provider_name,
-            provider_usage_count: row.provider_usage_count,
-            usage_count: row.usage_count,
-            provider_endpoint_id: None,
-            endpoint_url: None,
-            model_id: row.model_id,
-            enabled: row.enabled,
-            created_at: row.created_at,
-        });
-    }
-
     Ok(details)
 }
 
@@ -97,35 +90,33 @@ pub struct CreateAliasTargetParams {
     pub provider_id: Uuid,
     pub model_id: String,
     pub extra_fields: Option<Value>,
+    pub weight: Option<i32>,
+    pub priority: Option<i32>,
 }
 
 pub async fn create_alias_target(
     pool: &PgPool,
     params: CreateAliasTargetParams,
 ) -> AppResult<AliasTarget> {
-    let extra_fields = params.extra_fields.unwrap_or(Value::Object(serde_json::Map::new()));
-    let row = sqlx::query!(
-        "INSERT INTO alias_targets (alias_id, provider_id, model_id, extra_fields)
-         VALUES ($1, $2, $3, $4)
-         RETURNING id, alias_id, provider_id, model_id, extra_fields, usage_count, enabled, created_at",
+    let extra_fields = params
+        .extra_fields
+        .unwrap_or(Value::Object(serde_json::Map::new()));
+    let target = sqlx::query_as!(
+        AliasTarget,
+        "INSERT INTO alias_targets (alias_id, provider_id, model_id, extra_fields, weight, priority)
+         VALUES ($1, $2, $3, $4, COALESCE($5, 1), COALESCE($6, 0))
+         RETURNING id, alias_id, provider_id, model_id, extra_fields, usage_count, weight, priority, enabled, created_at",
         params.alias_id,
         params.provider_id,
         params.model_id,
-        extra_fields
+        extra_fields,
+        params.weight,
+        params.priority
     )
     .fetch_one(pool)
     .await?;
 
-    Ok(AliasTarget {
-        id: row.id,
-        alias_id: row.alias_id,
-        provider_id: row.provider_id,
-        model_id: row.model_id,
-        extra_fields: row.extra_fields,
-        usage_count: row.usage_count,
-        enabled: row.enabled,
-        created_at: row.created_at,
-    })
+    Ok(target)
 }
 
 pub struct UpdateAliasTargetParams {
@@ -133,6 +124,8 @@ pub struct UpdateAliasTargetParams {
     pub model_id: Option<String>,
     pub enabled: Option<bool>,
     pub extra_fields: Option<Value>,
+    pub weight: Option<i32>,
+    pub priority: Option<i32>,
 }
 
 pub async fn update_alias_target(
@@ -140,38 +133,30 @@ pub async fn update_alias_target(
     id: Uuid,
     params: UpdateAliasTargetParams,
 ) -> AppResult<Option<AliasTarget>> {
-    let row = sqlx::query!(
+    let target = sqlx::query_as!(
+        AliasTarget,
         "UPDATE alias_targets
          SET
             provider_id = COALESCE($1, provider_id),
             model_id = COALESCE($2, model_id),
             enabled = COALESCE($3, enabled),
-            extra_fields = COALESCE($4, extra_fields)
-         WHERE id = $5
-         RETURNING id, alias_id, provider_id, model_id, extra_fields, usage_count, enabled, created_at",
+            extra_fields = COALESCE($4, extra_fields),
+            weight = COALESCE($5, weight),
+            priority = COALESCE($6, priority)
+         WHERE id = $7
+         RETURNING id, alias_id, provider_id, model_id, extra_fields, usage_count, weight, priority, enabled, created_at",
         params.provider_id,
         params.model_id,
         params.enabled,
         params.extra_fields,
+        params.weight,
+        params.priority,
         id
     )
     .fetch_optional(pool)
     .await?;
 
-    let Some(row) = row else {
-        return Ok(None);
-    };
-
-    Ok(Some(AliasTarget {
-        id: row.id,
-        alias_id: row.alias_id,
-        provider_id: row.provider_id,
-        model_id: row.model_id,
-        extra_fields: row.extra_fields,
-        usage_count: row.usage_count,
-        enabled: row.enabled,
-        created_at: row.created_at,
-    }))
+    Ok(target)
 }
 
 pub async fn delete_alias_target(pool: &PgPool, id: Uuid) -> AppResult<bool> {
@@ -181,13 +166,16 @@ pub async fn delete_alias_target(pool: &PgPool, id: Uuid) -> AppResult<bool> {
     Ok(result.rows_affected() > 0)
 }
 
+/// Resolves the enabled targets of an alias, each with one endpoint whose type
+/// is in `api_types`. Endpoint types earlier in the slice are preferred.
 pub async fn fetch_alias_target_details(
     pool: &PgPool,
     alias_name: &str,
-    api_type: ApiType,
+    api_types: &[ApiType],
 ) -> AppResult<Vec<AliasTargetDetail>> {
-    let rows = sqlx::query!(
-        "SELECT DISTINCT ON (at.usage_count, at.id)
+    let details = sqlx::query_as!(
+        AliasTargetDetail,
+        r#"SELECT DISTINCT ON (at.id)
             at.id,
             a.id AS alias_id,
             a.name AS alias_name,
@@ -197,9 +185,13 @@ pub async fn fetch_alias_target_details(
             p.name AS provider_name,
             p.usage_count AS provider_usage_count,
             at.usage_count,
-            pe.id AS provider_endpoint_id,
-            pe.url AS endpoint_url,
+            pe.id AS "provider_endpoint_id?",
+            pe.url AS "endpoint_url?",
+            pe.api_type AS "endpoint_api_type?: ApiType",
             at.model_id,
+            at.weight,
+            at.priority,
+            a.strategy AS "strategy: LbStrategy",
             at.enabled,
             at.created_at
          FROM aliases a
@@ -208,44 +200,14 @@ pub async fn fetch_alias_target_details(
          JOIN providers p
            ON p.id = at.provider_id AND p.enabled = true
          JOIN provider_endpoints pe
-           ON pe.provider_id = p.id AND pe.api_type = $2 AND pe.enabled = true
+           ON pe.provider_id = p.id AND pe.api_type = ANY($2) AND pe.enabled = true
          WHERE a.name = $1 AND a.enabled = true
-         ORDER BY at.usage_count ASC, at.id",
+         ORDER BY at.id, array_position($2, pe.api_type), pe.created_at"#,
         alias_name,
-        api_type as _
+        api_types as _
     )
     .fetch_all(pool)
     .await?;
 
-    let mut details = Vec::with_capacity(rows.len());
-    for row in rows {
-        details.push(AliasTargetDetail {
-            id: row.id,
-            alias_id: row.alias_id,
-            alias_name: row.alias_name,
-            alias_target_id: row.alias_target_id,
-            extra_fields: row.extra_fields,
-            provider_id: row.provider_id,
-            provider_name: row.provider_name,
-            provider_usage_count: row.provider_usage_count,
-            usage_count: row.usage_count,
-            provider_endpoint_id: Some(row.provider_endpoint_id),
-            endpoint_url: Some(row.endpoint_url),
-            model_id: row.model_id,
-            enabled: row.enabled,
-            created_at: row.created_at,
-        });
-    }
-
     Ok(details)
 }
-
-pub async fn increment_usage_count(pool: &PgPool, id: Uuid) -> AppResult<()> {
-    sqlx::query!(
-        "UPDATE alias_targets SET usage_count = usage_count + 1 WHERE id = $1",
-        id
-    )
-    .execute(pool)
-    .await?;
-    Ok(())
-}
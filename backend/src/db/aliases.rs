@@ -1,8 +1,9 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::PgPool;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use super::types::{CachePolicy, CacheSettings, LbStrategy};
 use crate::error::AppResult;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,83 +11,139 @@ pub struct Alias {
     pub id: Uuid,
     pub name: String,
     pub enabled: bool,
+    pub strategy: LbStrategy,
+    #[serde(flatten)]
+    pub cache: CacheSettings,
+    /// Cosine similarity from which the semantic cache serves a response;
+    /// `None` disables it.
+    pub semantic_cache_threshold: Option<f32>,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
 }
 
+#[derive(Debug)]
+struct AliasRow {
+    id: Uuid,
+    name: String,
+    enabled: bool,
+    strategy: LbStrategy,
+    cache_ttl_secs: Option<i32>,
+    cache_policy: Option<CachePolicy>,
+    cache_non_deterministic: Option<bool>,
+    semantic_cache_threshold: Option<f32>,
+    created_at: OffsetDateTime,
+}
+
+impl From<AliasRow> for Alias {
+    fn from(row: AliasRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            enabled: row.enabled,
+            strategy: row.strategy,
+            cache: CacheSettings {
+                cache_ttl_secs: row.cache_ttl_secs,
+                cache_policy: row.cache_policy,
+                cache_non_deterministic: row.cache_non_deterministic,
+            },
+            semantic_cache_threshold: row.semantic_cache_threshold,
+            created_at: row.created_at,
+        }
+    }
+}
+
 pub async fn list_aliases(pool: &PgPool, page: i64, page_size: i64) -> AppResult<Vec<Alias>> {
     let offset = (page - 1) * page_size;
-    let rows = sqlx::query!(
-        "SELECT id, name, enabled, created_at
+    let rows = sqlx::query_as!(
+        AliasRow,
+        r#"SELECT id, name, enabled, strategy AS "strategy: LbStrategy", cache_ttl_secs,
+                cache_policy AS "cache_policy: CachePolicy", cache_non_deterministic,
+                semantic_cache_threshold, created_at
          FROM aliases
          ORDER BY created_at DESC
-         LIMIT $1 OFFSET $2",
+         LIMIT $1 OFFSET $2"#,
         page_size,
         offset
     )
     .fetch_all(pool)
     .await?;
 
-    let mut aliases = Vec::with_capacity(rows.len());
-    for row in rows {
-        aliases.push(Alias {
-            id: row.id,
-            name: row.name,
-            enabled: row.enabled,
-            created_at: row.created_at,
-        });
-    }
-
-    Ok(aliases)
+    Ok(rows.into_iter().map(Into::into).collect())
 }
 
 pub async fn get_alias(pool: &PgPool, id: Uuid) -> AppResult<Option<Alias>> {
-    let row = sqlx::query!(
-        "SELECT id, name, enabled, created_at
+    let row = sqlx::query_as!(
+        AliasRow,
+        r#"SELECT id, name, enabled, strategy AS "strategy: LbStrategy", cache_ttl_secs,
+                cache_policy AS "cache_policy: CachePolicy", cache_non_deterministic,
+                semantic_cache_threshold, created_at
          FROM aliases
-         WHERE id = $1",
+         WHERE id = $1"#,
         id
     )
     .fetch_optional(pool)
     .await?;
 
-    let Some(row) = row else {
-        return Ok(None);
-    };
-
-    Ok(Some(Alias {
-        id: row.id,
-        name: row.name,
-        enabled: row.enabled,
-        created_at: row.created_at,
-    }))
+    Ok(row.map(Into::into))
 }
 
 pub struct CreateAliasParams {
     pub name: String,
+    pub strategy: Option<LbStrategy>,
+    pub cache: CacheSettings,
+    pub semantic_cache_threshold: Option<f32>,
 }
 
 pub async fn create_alias(pool: &PgPool, params: CreateAliasParams) -> AppResult<Alias> {
-    let row = sqlx::query!(
-        "INSERT INTO aliases (name)
-         VALUES ($1)
-         RETURNING id, name, enabled, created_at",
-        params.name
+    let row = sqlx::query_as!(
+        AliasRow,
+        r#"INSERT INTO aliases (name, strategy, cache_ttl_secs, cache_policy,
+                              cache_non_deterministic, semantic_cache_threshold)
+         VALUES ($1, COALESCE($2, 'round_robin'::lb_strategy), $3, $4, $5, $6)
+         RETURNING id, name, enabled, strategy AS "strategy: LbStrategy", cache_ttl_secs,
+                cache_policy AS "cache_policy: CachePolicy", cache_non_deterministic,
+                semantic_cache_threshold, created_at"#,
+        params.name,
+        params.strategy as _,
+        params.cache.cache_ttl_secs,
+        params.cache.cache_policy as _,
+        params.cache.cache_non_deterministic,
+        params.semantic_cache_threshold
     )
     .fetch_one(pool)
     .await?;
 
-    Ok(Alias {
-        id: row.id,
-        name: row.name,
-        enabled: row.enabled,
-        created_at: row.created_at,
-    })
+    Ok(row.into())
 }
 
 pub struct UpdateAliasParams {
     pub name: Option<String>,
     pub enabled: Option<bool>,
+    pub strategy: Option<LbStrategy>,
+    pub cache: CacheSettingsUpdate,
+}
+
+/// Cache settings to change; `Some(None)` clears a setting and `None` leaves
+/// it unchanged.
+#[derive(Debug, Default, Deserialize)]
+pub struct CacheSettingsUpdate {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub cache_ttl_secs: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub cache_policy: Option<Option<CachePolicy>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub cache_non_deterministic: Option<Option<bool>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub semantic_cache_threshold: Option<Option<f32>>,
+}
+
+/// Tells an explicit `null` apart from a missing field.
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 pub async fn update_alias(
@@ -94,29 +151,69 @@ pub async fn update_alias(
     id: Uuid,
     params: UpdateAliasParams,
 ) -> AppResult<Option<Alias>> {
-    let row = sqlx::query!(
-        "UPDATE aliases
+    let row = sqlx::query_as!(
+        AliasRow,
+        r#"UPDATE aliases
          SET name = COALESCE($1, name),
-             enabled = COALESCE($2, enabled)
-         WHERE id = $3
-         RETURNING id, name, enabled, created_at",
+             enabled = COALESCE($2, enabled),
+             strategy = COALESCE($3, strategy),
+             cache_ttl_secs = CASE WHEN $4 THEN $5 ELSE cache_ttl_secs END,
+             cache_policy = CASE WHEN $6 THEN $7 ELSE cache_policy END,
+             cache_non_deterministic =
+                 CASE WHEN $8 THEN $9 ELSE cache_non_deterministic END,
+             semantic_cache_threshold =
+                 CASE WHEN $10 THEN $11 ELSE semantic_cache_threshold END
+         WHERE id = $12
+         RETURNING id, name, enabled, strategy AS "strategy: LbStrategy", cache_ttl_secs,
+                cache_policy AS "cache_policy: CachePolicy", cache_non_deterministic,
+                semantic_cache_threshold, created_at"#,
         params.name,
         params.enabled,
+        params.strategy as _,
+        params.cache.cache_ttl_secs.is_some(),
+        params.cache.cache_ttl_secs.flatten(),
+        params.cache.cache_policy.is_some(),
+        params.cache.cache_policy.flatten() as _,
+        params.cache.cache_non_deterministic.is_some(),
+        params.cache.cache_non_deterministic.flatten(),
+        params.cache.semantic_cache_threshold.is_some(),
+        params.cache.semantic_cache_threshold.flatten(),
         id
     )
     .fetch_optional(pool)
     .await?;
 
-    let Some(row) = row else {
-        return Ok(None);
-    };
+    Ok(row.map(Into::into))
+}
+
+/// Cache settings of the enabled alias with this name.
+pub async fn fetch_cache_settings(pool: &PgPool, name: &str) -> AppResult<CacheSettings> {
+    let settings = sqlx::query_as!(
+        CacheSettings,
+        r#"SELECT cache_ttl_secs, cache_policy AS "cache_policy: CachePolicy",
+                cache_non_deterministic
+         FROM aliases
+         WHERE name = $1 AND enabled = true"#,
+        name
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(settings.unwrap_or_default())
+}
+
+/// Semantic cache threshold of the enabled alias with this name.
+pub async fn fetch_semantic_cache_threshold(pool: &PgPool, name: &str) -> AppResult<Option<f32>> {
+    let threshold = sqlx::query_scalar!(
+        "SELECT semantic_cache_threshold
+         FROM aliases
+         WHERE name = $1 AND enabled = true",
+        name
+    )
+    .fetch_optional(pool)
+    .await?;
 
-    Ok(Some(Alias {
-        id: row.id,
-        name: row.name,
-        enabled: row.enabled,
-        created_at: row.created_at,
-    }))
+    Ok(threshold.flatten())
 }
 
 pub async fn delete_alias(pool: &PgPool, id: Uuid) -> AppResult<bool> {
@@ -0,0 +1,98 @@
+use serde_json::Value;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{db::types::BudgetPeriod, error::AppResult};
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct BudgetRow {
+    pub hard_limit: Option<f64>,
+    pub soft_limit: Option<f64>,
+    pub period: BudgetPeriod,
+    pub model_prices: Value,
+}
+
+/// Tokens a key used on one model since some point in time.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ModelUsage {
+    pub model: Option<String>,
+    pub alias: Option<String>,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    /// Sum of the costs logged from the pricing catalog.
+    pub cost: f64,
+}
+
+pub async fn fetch_budget(pool: &PgPool, gateway_key_id: Uuid) -> AppResult<Option<BudgetRow>> {
+    let budget = sqlx::query_as::<_, BudgetRow>(
+        "SELECT hard_limit, soft_limit, period, model_prices
+         FROM gateway_key_budgets
+         WHERE gateway_key_id = $1",
+    )
+    .bind(gateway_key_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(budget)
+}
+
+pub async fn upsert_budget(
+    pool: &PgPool,
+    gateway_key_id: Uuid,
+    budget: &BudgetRow,
+) -> AppResult<BudgetRow> {
+    let budget = sqlx::query_as::<_, BudgetRow>(
+        "INSERT INTO gateway_key_budgets
+             (gateway_key_id, hard_limit, soft_limit, period, model_prices, updated_at)
+         VALUES ($1, $2, $3, $4, $5, now())
+         ON CONFLICT (gateway_key_id) DO UPDATE
+         SET hard_limit = EXCLUDED.hard_limit,
+             soft_limit = EXCLUDED.soft_limit,
+             period = EXCLUDED.period,
+             model_prices = EXCLUDED.model_prices,
+             updated_at = now()
+         RETURNING hard_limit, soft_limit, period, model_prices",
+    )
+    .bind(gateway_key_id)
+    .bind(budget.hard_limit)
+    .bind(budget.soft_limit)
+    .bind(budget.period)
+    .bind(&budget.model_prices)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(budget)
+}
+
+pub async fn delete_budget(pool: &PgPool, gateway_key_id: Uuid) -> AppResult<bool> {
+    let result = sqlx::query("DELETE FROM gateway_key_budgets WHERE gateway_key_id = $1")
+        .bind(gateway_key_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn usage_since(
+    pool: &PgPool,
+    gateway_key_id: Uuid,
+    since: OffsetDateTime,
+) -> AppResult<Vec<ModelUsage>> {
+    let usage = sqlx::query_as::<_, ModelUsage>(
+        "SELECT model,
+                alias,
+                COALESCE(SUM(prompt_tokens), 0)::bigint AS prompt_tokens,
+                COALESCE(SUM(completion_tokens), 0)::bigint AS completion_tokens,
+                COALESCE(SUM(cost), 0)::double precision AS cost
+         FROM request_logs
+         WHERE gateway_key_id = $1 AND created_at >= $2
+         GROUP BY model, alias",
+    )
+    .bind(gateway_key_id)
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(usage)
+}
@@ -0,0 +1,65 @@
+use sqlx::PgPool;
+
+use crate::error::AppResult;
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct CacheEmbedding {
+    pub request_body_hash: String,
+    pub embedding: Vec<f32>,
+}
+
+pub async fn upsert_embedding(
+    pool: &PgPool,
+    request_body_hash: &str,
+    context_hash: &str,
+    embedding: &[f32],
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO response_cache_embeddings (request_body_hash, context_hash, embedding)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (request_body_hash) DO UPDATE
+         SET context_hash = EXCLUDED.context_hash,
+             embedding = EXCLUDED.embedding,
+             created_at = now()",
+    )
+    .bind(request_body_hash)
+    .bind(context_hash)
+    .bind(embedding)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Newest embeddings with this context whose request has a successful
+/// response logged after `not_before`.
+pub async fn list_candidates(
+    pool: &PgPool,
+    context_hash: &str,
+    not_before: time::OffsetDateTime,
+    limit: i64,
+) -> AppResult<Vec<CacheEmbedding>> {
+    let candidates = sqlx::query_as::<_, CacheEmbedding>(
+        "SELECT e.request_body_hash, e.embedding
+         FROM response_cache_embeddings e
+         WHERE e.context_hash = $1
+           AND e.created_at >= $2
+           AND EXISTS (
+             SELECT 1
+             FROM request_logs rl
+             WHERE rl.request_body_hash = e.request_body_hash
+               AND rl.status_code BETWEEN 200 AND 299
+               AND rl.response_body IS NOT NULL
+               AND rl.created_at >= $2
+           )
+         ORDER BY e.created_at DESC
+         LIMIT $3",
+    )
+    .bind(context_hash)
+    .bind(not_before)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(candidates)
+}
@@ -0,0 +1,159 @@
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use super::types::ApiType;
+use crate::error::AppResult;
+
+/// Selects cache entries; unset fields match everything.
+#[derive(Debug, Default)]
+pub struct CacheEntryFilter {
+    pub request_body_hash: Option<String>,
+    pub alias: Option<String>,
+    pub gateway_key_id: Option<Uuid>,
+    /// Only entries cached before this time.
+    pub created_before: Option<time::OffsetDateTime>,
+}
+
+/// A cached response: the newest servable request log with its hash.
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct CacheEntry {
+    pub request_body_hash: String,
+    pub api_type: ApiType,
+    pub alias: Option<String>,
+    pub model: Option<String>,
+    pub gateway_key_id: Option<Uuid>,
+    pub stream: bool,
+    pub size_bytes: i32,
+    pub hit_count: i64,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_hit_at: Option<time::OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+}
+
+fn append_filters(builder: &mut QueryBuilder<'_, Postgres>, filter: &CacheEntryFilter) {
+    builder.push(
+        " WHERE request_body_hash IS NOT NULL
+          AND status_code BETWEEN 200 AND 299
+          AND response_body IS NOT NULL",
+    );
+    if let Some(hash) = &filter.request_body_hash {
+        builder.push(" AND request_body_hash = ");
+        builder.push_bind(hash.clone());
+    }
+    if let Some(alias) = &filter.alias {
+        builder.push(" AND alias = ");
+        builder.push_bind(alias.clone());
+    }
+    if let Some(gateway_key_id) = filter.gateway_key_id {
+        builder.push(" AND gateway_key_id = ");
+        builder.push_bind(gateway_key_id);
+    }
+    if let Some(created_before) = filter.created_before {
+        builder.push(" AND created_at < ");
+        builder.push_bind(created_before);
+    }
+}
+
+pub async fn list_entries(
+    pool: &PgPool,
+    filter: &CacheEntryFilter,
+    limit: i64,
+    offset: i64,
+) -> AppResult<Vec<CacheEntry>> {
+    let mut builder = QueryBuilder::new(
+        "SELECT
+            e.request_body_hash,
+            e.api_type,
+            e.alias,
+            e.model,
+            e.gateway_key_id,
+            e.stream,
+            e.size_bytes,
+            COALESCE(h.hit_count, 0) AS hit_count,
+            h.last_hit_at,
+            e.created_at
+        FROM (
+            SELECT DISTINCT ON (request_body_hash)
+                request_body_hash,
+                api_type,
+                alias,
+                model,
+                gateway_key_id,
+                stream,
+                octet_length(response_body) AS size_bytes,
+                created_at
+            FROM request_logs",
+    );
+    append_filters(&mut builder, filter);
+    builder.push(
+        " ORDER BY request_body_hash, created_at DESC
+        ) e
+        LEFT JOIN LATERAL (
+            SELECT count(*) AS hit_count, max(cl.created_at) AS last_hit_at
+            FROM cache_log cl
+            JOIN request_logs src ON src.id = cl.source_request_log_id
+            WHERE cl.decision = 'hit'
+              AND src.request_body_hash = e.request_body_hash
+        ) h ON true
+        ORDER BY e.created_at DESC
+        LIMIT ",
+    );
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(offset);
+
+    let entries = builder
+        .build_query_as::<CacheEntry>()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(entries)
+}
+
+pub async fn count_entries(pool: &PgPool, filter: &CacheEntryFilter) -> AppResult<i64> {
+    let mut builder =
+        QueryBuilder::new("SELECT count(DISTINCT request_body_hash) FROM request_logs");
+    append_filters(&mut builder, filter);
+
+    let count = builder.build_query_scalar::<i64>().fetch_one(pool).await?;
+
+    Ok(count)
+}
+
+/// Takes matching request logs out of the response cache by clearing their
+/// hash; the logs themselves are kept. Returns the purged hashes.
+pub async fn purge_entries(pool: &PgPool, filter: &CacheEntryFilter) -> AppResult<Vec<String>> {
+    let mut tx = pool.begin().await?;
+
+    let mut builder = QueryBuilder::new(
+        "WITH purged AS (
+            SELECT id, request_body_hash
+            FROM request_logs",
+    );
+    append_filters(&mut builder, filter);
+    builder.push(
+        " FOR UPDATE
+        )
+        UPDATE request_logs rl
+        SET request_body_hash = NULL
+        FROM purged
+        WHERE rl.id = purged.id
+        RETURNING purged.request_body_hash",
+    );
+    let mut hashes = builder
+        .build_query_scalar::<String>()
+        .fetch_all(&mut *tx)
+        .await?;
+    hashes.sort_unstable();
+    hashes.dedup();
+
+    sqlx::query("DELETE FROM response_cache_embeddings WHERE request_body_hash = ANY($1)")
+        .bind(&hashes)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(hashes)
+}
@@ -1,13 +1,22 @@
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use super::types::CachePolicy;
 use crate::error::AppResult;
 
 pub struct CacheLogContext {
     pub request_id: Uuid,
     pub source_request_log_id: Option<i64>,
     pub gateway_key_id: Option<Uuid>,
-    pub cache_layer: &'static str,
+    /// Layer that served a hit; `None` for misses and bypasses.
+    pub cache_layer: Option<&'static str>,
+    /// `hit`, `miss` or `bypass`.
+    pub decision: &'static str,
+    pub cache_policy: Option<CachePolicy>,
+    /// Why the lookup was bypassed.
+    pub reason: Option<&'static str>,
+    /// Cosine similarity of semantic hits.
+    pub similarity: Option<f32>,
     pub latency_ms: Option<i32>,
     pub client_ip: Option<String>,
     pub user_agent: Option<String>,
@@ -27,9 +36,13 @@ pub async fn record_cache_event(pool: &PgPool, context: &CacheLogContext) -> App
             cache_layer,
             latency_ms,
             client_ip,
-            user_agent
+            user_agent,
+            decision,
+            cache_policy,
+            reason,
+            similarity
         ) VALUES (
-            $1, $2, $3, $4, $5, $6, $7
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11
         )",
     )
     .bind(context.request_id)
@@ -39,6 +52,10 @@ pub async fn record_cache_event(pool: &PgPool, context: &CacheLogContext) -> App
     .bind(context.latency_ms)
     .bind(client_ip)
     .bind(context.user_agent.as_deref())
+    .bind(context.decision)
+    .bind(context.cache_policy)
+    .bind(context.reason)
+    .bind(context.similarity)
     .execute(pool)
     .await?;
 
@@ -3,6 +3,7 @@ use sqlx::PgPool;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use super::types::{CachePolicy, CacheSettings};
 use crate::error::AppResult;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +14,11 @@ pub struct GatewayKey {
     pub enabled: bool,
     pub rate_limit_rps: Option<i32>,
     pub rate_limit_rpm: Option<i32>,
+    pub rate_limit_tpm: Option<i32>,
+    pub rate_limit_tpd: Option<i32>,
+    pub max_concurrent_requests: Option<i32>,
+    #[serde(flatten)]
+    pub cache: CacheSettings,
     #[serde(default)]
     pub allowed_models: Vec<String>,
     #[serde(with = "time::serde::rfc3339")]
@@ -27,6 +33,12 @@ struct GatewayKeyRow {
     enabled: bool,
     rate_limit_rps: Option<i32>,
     rate_limit_rpm: Option<i32>,
+    rate_limit_tpm: Option<i32>,
+    rate_limit_tpd: Option<i32>,
+    max_concurrent_requests: Option<i32>,
+    cache_ttl_secs: Option<i32>,
+    cache_policy: Option<CachePolicy>,
+    cache_non_deterministic: Option<bool>,
     created_at: OffsetDateTime,
 }
 
@@ -39,6 +51,14 @@ impl From<GatewayKeyRow> for GatewayKey {
             enabled: row.enabled,
             rate_limit_rps: row.rate_limit_rps,
             rate_limit_rpm: row.rate_limit_rpm,
+            rate_limit_tpm: row.rate_limit_tpm,
+            rate_limit_tpd: row.rate_limit_tpd,
+            max_concurrent_requests: row.max_concurrent_requests,
+            cache: CacheSettings {
+                cache_ttl_secs: row.cache_ttl_secs,
+                cache_policy: row.cache_policy,
+                cache_non_deterministic: row.cache_non_deterministic,
+            },
             allowed_models: Vec::new(),
             created_at: row.created_at,
         }
@@ -48,10 +68,12 @@ impl From<GatewayKeyRow> for GatewayKey {
 pub async fn fetch_gateway_key(pool: &PgPool, api_key: &str) -> AppResult<Option<GatewayKey>> {
     let row = sqlx::query_as!(
         GatewayKeyRow,
-        "SELECT id, name, key, enabled, rate_limit_rps, rate_limit_rpm, created_at
+        r#"SELECT id, name, key, enabled, rate_limit_rps, rate_limit_rpm, rate_limit_tpm,
+                rate_limit_tpd, max_concurrent_requests, cache_ttl_secs,
+                cache_policy AS "cache_policy: CachePolicy", cache_non_deterministic, created_at
          FROM gateway_keys
          WHERE key = $1 AND enabled = true
-         LIMIT 1",
+         LIMIT 1"#,
         api_key
     )
     .fetch_optional(pool)
@@ -63,9 +85,11 @@ pub async fn fetch_gateway_key(pool: &PgPool, api_key: &str) -> AppResult<Option
 pub async fn fetch_gateway_key_by_id(pool: &PgPool, id: Uuid) -> AppResult<Option<GatewayKey>> {
     let row = sqlx::query_as!(
         GatewayKeyRow,
-        "SELECT id, name, key, enabled, rate_limit_rps, rate_limit_rpm, created_at
+        r#"SELECT id, name, key, enabled, rate_limit_rps, rate_limit_rpm, rate_limit_tpm,
+                rate_limit_tpd, max_concurrent_requests, cache_ttl_secs,
+                cache_policy AS "cache_policy: CachePolicy", cache_non_deterministic, created_at
          FROM gateway_keys
-         WHERE id = $1",
+         WHERE id = $1"#,
         id
     )
     .fetch_optional(pool)
@@ -74,12 +98,21 @@ pub async fn fetch_gateway_key_by_id(pool: &PgPool, id: Uuid) -> AppResult<Optio
     Ok(row.map(Into::into))
 }
 
-pub async fn fetch_limits(
-    pool: &PgPool,
-    gateway_key_id: Uuid,
-) -> AppResult<(Option<i32>, Option<i32>)> {
-    let row = sqlx::query!(
-        "SELECT rate_limit_rps, rate_limit_rpm
+/// Rate limits configured on a gateway key; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct KeyLimits {
+    pub rate_limit_rps: Option<i32>,
+    pub rate_limit_rpm: Option<i32>,
+    pub rate_limit_tpm: Option<i32>,
+    pub rate_limit_tpd: Option<i32>,
+    pub max_concurrent_requests: Option<i32>,
+}
+
+pub async fn fetch_limits(pool: &PgPool, gateway_key_id: Uuid) -> AppResult<KeyLimits> {
+    let limits = sqlx::query_as!(
+        KeyLimits,
+        "SELECT rate_limit_rps, rate_limit_rpm, rate_limit_tpm, rate_limit_tpd,
+                max_concurrent_requests
          FROM gateway_keys
          WHERE id = $1 AND enabled = true
          LIMIT 1",
@@ -88,11 +121,22 @@ pub async fn fetch_limits(
     .fetch_optional(pool)
     .await?;
 
-    let Some(row) = row else {
-        return Ok((None, None));
-    };
+    Ok(limits.unwrap_or_default())
+}
 
-    Ok((row.rate_limit_rps, row.rate_limit_rpm))
+pub async fn fetch_cache_settings(pool: &PgPool, gateway_key_id: Uuid) -> AppResult<CacheSettings> {
+    let settings = sqlx::query_as!(
+        CacheSettings,
+        r#"SELECT cache_ttl_secs, cache_policy AS "cache_policy: CachePolicy",
+                cache_non_deterministic
+         FROM gateway_keys
+         WHERE id = $1 AND enabled = true"#,
+        gateway_key_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(settings.unwrap_or_default())
 }
 
 pub async fn list_gateway_keys(
@@ -102,10 +146,12 @@ pub async fn list_gateway_keys(
 ) -> AppResult<Vec<GatewayKey>> {
     let rows = sqlx::query_as!(
         GatewayKeyRow,
-        "SELECT id, name, key, enabled, rate_limit_rps, rate_limit_rpm, created_at
+        r#"SELECT id, name, key, enabled, rate_limit_rps, rate_limit_rpm, rate_limit_tpm,
+                rate_limit_tpd, max_concurrent_requests, cache_ttl_secs,
+                cache_policy AS "cache_policy: CachePolicy", cache_non_deterministic, created_at
          FROM gateway_keys
          ORDER BY created_at DESC
-         LIMIT $1 OFFSET $2",
+         LIMIT $1 OFFSET $2"#,
         limit,
         offset
     )
@@ -118,8 +164,8 @@ pub async fn list_gateway_keys(
 pub struct CreateGatewayKeyParams {
     pub name: Option<String>,
     pub key: String,
-    pub rate_limit_rps: Option<i32>,
-    pub rate_limit_rpm: Option<i32>,
+    pub limits: KeyLimits,
+    pub cache: CacheSettings,
 }
 
 pub async fn create_gateway_key(
@@ -128,13 +174,24 @@ pub async fn create_gateway_key(
 ) -> AppResult<GatewayKey> {
     let row = sqlx::query_as!(
         GatewayKeyRow,
-        "INSERT INTO gateway_keys (name, key, rate_limit_rps, rate_limit_rpm)
-         VALUES ($1, $2, $3, $4)
-         RETURNING id, name, key, enabled, rate_limit_rps, rate_limit_rpm, created_at",
+        r#"INSERT INTO gateway_keys (name, key, rate_limit_rps, rate_limit_rpm, rate_limit_tpm,
+                                   rate_limit_tpd, max_concurrent_requests, cache_ttl_secs,
+                                   cache_policy, cache_non_deterministic)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+         RETURNING id, name, key, enabled, rate_limit_rps, rate_limit_rpm, rate_limit_tpm,
+                   rate_limit_tpd, max_concurrent_requests, cache_ttl_secs,
+                   cache_policy AS "cache_policy: CachePolicy", cache_non_deterministic,
+                   created_at"#,
         params.name,
         params.key,
-        params.rate_limit_rps,
-        params.rate_limit_rpm
+        params.limits.rate_limit_rps,
+        params.limits.rate_limit_rpm,
+        params.limits.rate_limit_tpm,
+        params.limits.rate_limit_tpd,
+        params.limits.max_concurrent_requests,
+        params.cache.cache_ttl_secs,
+        params.cache.cache_policy as _,
+        params.cache.cache_non_deterministic
     )
     .fetch_one(pool)
     .await?;
@@ -145,8 +202,8 @@ pub async fn create_gateway_key(
 pub struct UpdateGatewayKeyParams {
     pub name: Option<String>,
     pub enabled: Option<bool>,
-    pub rate_limit_rps: Option<i32>,
-    pub rate_limit_rpm: Option<i32>,
+    pub limits: KeyLimits,
+    pub cache: CacheSettings,
 }
 
 pub async fn update_gateway_key(
@@ -156,17 +213,32 @@ pub async fn update_gateway_key(
 ) -> AppResult<Option<GatewayKey>> {
     let row = sqlx::query_as!(
         GatewayKeyRow,
-        "UPDATE gateway_keys
+        r#"UPDATE gateway_keys
          SET name = COALESCE($1, name),
              enabled = COALESCE($2, enabled),
              rate_limit_rps = $3,
-             rate_limit_rpm = $4
-         WHERE id = $5
-         RETURNING id, name, key, enabled, rate_limit_rps, rate_limit_rpm, created_at",
+             rate_limit_rpm = $4,
+             rate_limit_tpm = $5,
+             rate_limit_tpd = $6,
+             max_concurrent_requests = $7,
+             cache_ttl_secs = $8,
+             cache_policy = $9,
+             cache_non_deterministic = $10
+         WHERE id = $11
+         RETURNING id, name, key, enabled, rate_limit_rps, rate_limit_rpm, rate_limit_tpm,
+                   rate_limit_tpd, max_concurrent_requests, cache_ttl_secs,
+                   cache_policy AS "cache_policy: CachePolicy", cache_non_deterministic,
+                   created_at"#,
         params.name,
         params.enabled,
-        params.rate_limit_rps,
-        params.rate_limit_rpm,
+        params.limits.rate_limit_rps,
+        params.limits.rate_limit_rpm,
+        params.limits.rate_limit_tpm,
+        params.limits.rate_limit_tpd,
+        params.limits.max_concurrent_requests,
+        params.cache.cache_ttl_secs,
+        params.cache.cache_policy as _,
+        params.cache.cache_non_deterministic,
         id
     )
     .fetch_optional(pool)
@@ -0,0 +1,19 @@
+use sqlx::PgPool;
+
+use crate::error::AppResult;
+
+pub async fn ping(pool: &PgPool) -> AppResult<()> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+/// Versions recorded as successfully applied by the migrator.
+pub async fn applied_migrations(pool: &PgPool) -> AppResult<Vec<i64>> {
+    let versions = sqlx::query_scalar::<_, i64>(
+        "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(versions)
+}
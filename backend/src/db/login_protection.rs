@@ -0,0 +1,54 @@
+use sqlx::PgPool;
+
+use crate::error::AppResult;
+
+pub async fn is_banned(pool: &PgPool, ip: &str) -> AppResult<bool> {
+    let banned =
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM login_bans WHERE ip = $1)")
+            .bind(ip)
+            .fetch_one(pool)
+            .await?;
+
+    Ok(banned)
+}
+
+/// Records a failed login and returns how many failures the address has
+/// within the window, this one included. Older failures are pruned.
+pub async fn record_failure(pool: &PgPool, ip: &str, window_secs: f64) -> AppResult<i64> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "DELETE FROM login_failures
+         WHERE ip = $1 AND failed_at <= now() - make_interval(secs => $2)",
+    )
+    .bind(ip)
+    .bind(window_secs)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("INSERT INTO login_failures (ip, failed_at) VALUES ($1, now())")
+        .bind(ip)
+        .execute(&mut *tx)
+        .await?;
+
+    let failures =
+        sqlx::query_scalar::<_, i64>("SELECT count(*) FROM login_failures WHERE ip = $1")
+            .bind(ip)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    tx.commit().await?;
+    Ok(failures)
+}
+
+pub async fn ban(pool: &PgPool, ip: &str) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO login_bans (ip, banned_at) VALUES ($1, now())
+         ON CONFLICT (ip) DO NOTHING",
+    )
+    .bind(ip)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -1,12 +1,23 @@
 pub mod alias_targets;
 pub mod aliases;
+pub mod budgets;
+pub mod cache_embeddings;
+pub mod cache_entries;
 pub mod cache_log;
 pub mod gateway_key_models;
 pub mod gateway_keys;
+pub mod health;
+pub mod login_protection;
+pub mod models;
+pub mod pricing;
 pub mod provider_endpoints;
 pub mod provider_keys;
 pub mod providers;
+pub mod rate_limits;
 pub mod request_logs;
 pub mod stats;
+pub mod stored_responses;
 pub mod types;
 pub mod users;
+
+pub static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
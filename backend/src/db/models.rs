@@ -0,0 +1,42 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::error::AppResult;
+
+/// A model name clients can send: an alias or a `brief:model` pair.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ModelEntry {
+    pub id: String,
+    /// Provider name for `brief:model` entries, `None` for aliases.
+    pub provider_name: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+/// Enabled aliases followed by the `brief:model` pairs of the models their
+/// enabled targets route to on enabled providers that have a brief.
+pub async fn list_model_entries(pool: &PgPool) -> AppResult<Vec<ModelEntry>> {
+    let entries = sqlx::query_as::<_, ModelEntry>(
+        "SELECT id, provider_name, created_at
+         FROM (
+             SELECT a.name AS id, NULL::text AS provider_name, a.created_at, 0 AS kind
+             FROM aliases a
+             WHERE a.enabled = true
+             UNION ALL
+             SELECT p.brief || ':' || at.model_id AS id,
+                    p.name AS provider_name,
+                    MIN(at.created_at) AS created_at,
+                    1 AS kind
+             FROM alias_targets at
+             JOIN aliases a ON a.id = at.alias_id AND a.enabled = true
+             JOIN providers p
+               ON p.id = at.provider_id AND p.enabled = true AND p.brief IS NOT NULL
+             WHERE at.enabled = true
+             GROUP BY p.brief, at.model_id, p.name
+         ) models
+         ORDER BY kind, id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(entries)
+}
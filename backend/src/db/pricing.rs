@@ -0,0 +1,148 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::error::AppResult;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ModelPricing {
+    pub id: Uuid,
+    pub provider_id: Uuid,
+    pub provider_name: String,
+    pub model_id: String,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    pub cached_input_per_million: Option<f64>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+pub struct ModelPricingParams<'a> {
+    pub provider_id: Uuid,
+    pub model_id: &'a str,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    pub cached_input_per_million: Option<f64>,
+}
+
+const SELECT_PRICING: &str = "SELECT mp.id,
+            mp.provider_id,
+            p.name AS provider_name,
+            mp.model_id,
+            mp.input_per_million,
+            mp.output_per_million,
+            mp.cached_input_per_million,
+            mp.created_at,
+            mp.updated_at
+     FROM model_prices mp
+     JOIN providers p ON p.id = mp.provider_id";
+
+pub async fn list_pricing(pool: &PgPool) -> AppResult<Vec<ModelPricing>> {
+    let pricing = sqlx::query_as::<_, ModelPricing>(&format!(
+        "{SELECT_PRICING} ORDER BY p.name, mp.model_id"
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(pricing)
+}
+
+pub async fn fetch_pricing_by_id(pool: &PgPool, id: Uuid) -> AppResult<Option<ModelPricing>> {
+    let pricing = sqlx::query_as::<_, ModelPricing>(&format!("{SELECT_PRICING} WHERE mp.id = $1"))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(pricing)
+}
+
+/// Price of an upstream model, looked up by the provider name and model id
+/// recorded in request logs.
+pub async fn fetch_pricing_for_model(
+    pool: &PgPool,
+    provider_name: &str,
+    model_id: &str,
+) -> AppResult<Option<ModelPricing>> {
+    let pricing = sqlx::query_as::<_, ModelPricing>(&format!(
+        "{SELECT_PRICING} WHERE p.name = $1 AND mp.model_id = $2"
+    ))
+    .bind(provider_name)
+    .bind(model_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(pricing)
+}
+
+pub async fn fetch_pricing_id(
+    pool: &PgPool,
+    provider_id: Uuid,
+    model_id: &str,
+) -> AppResult<Option<Uuid>> {
+    let id = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM model_prices WHERE provider_id = $1 AND model_id = $2",
+    )
+    .bind(provider_id)
+    .bind(model_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(id)
+}
+
+pub async fn insert_pricing(pool: &PgPool, params: &ModelPricingParams<'_>) -> AppResult<Uuid> {
+    let id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO model_prices
+             (provider_id, model_id, input_per_million, output_per_million, cached_input_per_million)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id",
+    )
+    .bind(params.provider_id)
+    .bind(params.model_id)
+    .bind(params.input_per_million)
+    .bind(params.output_per_million)
+    .bind(params.cached_input_per_million)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(id)
+}
+
+pub async fn update_pricing(
+    pool: &PgPool,
+    id: Uuid,
+    params: &ModelPricingParams<'_>,
+) -> AppResult<bool> {
+    let result = sqlx::query(
+        "UPDATE model_prices
+         SET provider_id = $2,
+             model_id = $3,
+             input_per_million = $4,
+             output_per_million = $5,
+             cached_input_per_million = $6,
+             updated_at = now()
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(params.provider_id)
+    .bind(params.model_id)
+    .bind(params.input_per_million)
+    .bind(params.output_per_million)
+    .bind(params.cached_input_per_million)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_pricing(pool: &PgPool, id: Uuid) -> AppResult<bool> {
+    let result = sqlx::query("DELETE FROM model_prices WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
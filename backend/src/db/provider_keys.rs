@@ -3,6 +3,7 @@ use sqlx::PgPool;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use super::types::{ApiType, KeyDisabledReason};
 use crate::error::AppResult;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,42 +14,39 @@ pub struct ProviderKey {
     pub key: String,
     pub usage_count: i64,
     pub enabled: bool,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub cooldown_until: Option<OffsetDateTime>,
+    pub disabled_reason: Option<KeyDisabledReason>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub disabled_at: Option<OffsetDateTime>,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
 }
 
 pub async fn fetch_provider_keys(pool: &PgPool, provider_id: Uuid) -> AppResult<Vec<ProviderKey>> {
-    let rows = sqlx::query!(
-        "SELECT
+    let keys = sqlx::query_as!(
+        ProviderKey,
+        r#"SELECT
             id,
             provider_id,
             name,
             key,
             usage_count,
             enabled,
+            cooldown_until,
+            disabled_reason AS "disabled_reason: KeyDisabledReason",
+            disabled_at,
             created_at
          FROM provider_keys
          WHERE provider_id = $1
            AND enabled = true
-         ORDER BY usage_count ASC",
+           AND (cooldown_until IS NULL OR cooldown_until <= now())
+         ORDER BY usage_count ASC"#,
         provider_id
     )
     .fetch_all(pool)
     .await?;
 
-    let mut keys = Vec::with_capacity(rows.len());
-    for row in rows {
-        keys.push(ProviderKey {
-            id: row.id,
-            provider_id: row.provider_id,
-            name: row.name,
-            key: row.key,
-            usage_count: row.usage_count,
-            enabled: row.enabled,
-            created_at: row.created_at,
-        });
-    }
-
     Ok(keys)
 }
 
@@ -56,36 +54,27 @@ pub async fn list_keys_by_provider(
     pool: &PgPool,
     provider_id: Uuid,
 ) -> AppResult<Vec<ProviderKey>> {
-    let rows = sqlx::query!(
-        "SELECT
+    let keys = sqlx::query_as!(
+        ProviderKey,
+        r#"SELECT
             id,
             provider_id,
             name,
             key,
             usage_count,
             enabled,
+            cooldown_until,
+            disabled_reason AS "disabled_reason: KeyDisabledReason",
+            disabled_at,
             created_at
          FROM provider_keys
          WHERE provider_id = $1
-         ORDER BY created_at DESC",
+         ORDER BY created_at DESC"#,
         provider_id
     )
     .fetch_all(pool)
     .await?;
 
-    let mut keys = Vec::with_capacity(rows.len());
-    for row in rows {
-        keys.push(ProviderKey {
-            id: row.id,
-            provider_id: row.provider_id,
-            name: row.name,
-            key: row.key,
-            usage_count: row.usage_count,
-            enabled: row.enabled,
-            created_at: row.created_at,
-        });
-    }
-
     Ok(keys)
 }
 
@@ -96,10 +85,12 @@ pub struct CreateKeyParams {
 }
 
 pub async fn create_key(pool: &PgPool, params: CreateKeyParams) -> AppResult<ProviderKey> {
-    let row = sqlx::query!(
-        "INSERT INTO provider_keys (provider_id, name, key)
+    let key = sqlx::query_as!(
+        ProviderKey,
+        r#"INSERT INTO provider_keys (provider_id, name, key)
          VALUES ($1, $2, $3)
-         RETURNING id, provider_id, name, key, usage_count, enabled, created_at",
+         RETURNING id, provider_id, name, key, usage_count, enabled, cooldown_until,
+                   disabled_reason AS "disabled_reason: KeyDisabledReason", disabled_at, created_at"#,
         params.provider_id,
         params.name,
         params.key
@@ -107,15 +98,7 @@ pub async fn create_key(pool: &PgPool, params: CreateKeyParams) -> AppResult<Pro
     .fetch_one(pool)
     .await?;
 
-    Ok(ProviderKey {
-        id: row.id,
-        provider_id: row.provider_id,
-        name: row.name,
-        key: row.key,
-        usage_count: row.usage_count,
-        enabled: row.enabled,
-        created_at: row.created_at,
-    })
+    Ok(key)
 }
 
 pub struct UpdateKeyParams {
@@ -128,12 +111,24 @@ pub async fn update_key(
     id: Uuid,
     params: UpdateKeyParams,
 ) -> AppResult<Option<ProviderKey>> {
-    let row = sqlx::query!(
-        "UPDATE provider_keys
+    let key = sqlx::query_as!(
+        ProviderKey,
+        r#"UPDATE provider_keys
          SET name = COALESCE($1, name),
-             enabled = COALESCE($2, enabled)
+             enabled = COALESCE($2, enabled),
+             disabled_reason = CASE
+                 WHEN $2 IS NULL OR $2 = enabled THEN disabled_reason
+                 WHEN $2 THEN NULL
+                 ELSE 'manual'::key_disabled_reason
+             END,
+             disabled_at = CASE
+                 WHEN $2 IS NULL OR $2 = enabled THEN disabled_at
+                 WHEN $2 THEN NULL
+                 ELSE now()
+             END
          WHERE id = $3
-         RETURNING id, provider_id, name, key, usage_count, enabled, created_at",
+         RETURNING id, provider_id, name, key, usage_count, enabled, cooldown_until,
+                   disabled_reason AS "disabled_reason: KeyDisabledReason", disabled_at, created_at"#,
         params.name,
         params.enabled,
         id
@@ -141,19 +136,7 @@ pub async fn update_key(
     .fetch_optional(pool)
     .await?;
 
-    let Some(row) = row else {
-        return Ok(None);
-    };
-
-    Ok(Some(ProviderKey {
-        id: row.id,
-        provider_id: row.provider_id,
-        name: row.name,
-        key: row.key,
-        usage_count: row.usage_count,
-        enabled: row.enabled,
-        created_at: row.created_at,
-    }))
+    Ok(key)
 }
 
 pub async fn delete_key(pool: &PgPool, id: Uuid) -> AppResult<bool> {
@@ -172,3 +155,95 @@ pub async fn increment_usage_count(pool: &PgPool, id: Uuid) -> AppResult<()> {
     .await?;
     Ok(())
 }
+
+/// Keeps the later of the existing and the new cooldown so a short
+/// `Retry-After` never shortens a quota cooldown.
+pub async fn set_cooldown(
+    pool: &PgPool,
+    id: Uuid,
+    cooldown_until: OffsetDateTime,
+) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE provider_keys
+         SET cooldown_until = GREATEST(COALESCE(cooldown_until, $1), $1)
+         WHERE id = $2",
+        cooldown_until,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Disables an enabled key, recording why. Keys that are already disabled keep
+/// their original reason.
+pub async fn disable_key(pool: &PgPool, id: Uuid, reason: KeyDisabledReason) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE provider_keys
+         SET enabled = false,
+             disabled_reason = $1,
+             disabled_at = now()
+         WHERE id = $2 AND enabled = true",
+        reason as _,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Re-enables a key only if it is still disabled for `reason`, so a manual
+/// disable made in the meantime is left alone.
+pub async fn reenable_key(pool: &PgPool, id: Uuid, reason: KeyDisabledReason) -> AppResult<bool> {
+    let result = sqlx::query!(
+        "UPDATE provider_keys
+         SET enabled = true,
+             disabled_reason = NULL,
+             disabled_at = NULL
+         WHERE id = $1 AND enabled = false AND disabled_reason = $2",
+        id,
+        reason as _
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[derive(Debug, Clone)]
+pub struct DisabledKeyProbe {
+    pub id: Uuid,
+    pub provider_id: Uuid,
+    pub key: String,
+    pub endpoint_url: String,
+    pub endpoint_api_type: ApiType,
+}
+
+/// Keys disabled for `reason`, each paired with an enabled endpoint of their
+/// provider whose type is in `api_types` to probe them against. Endpoint
+/// types earlier in the slice are preferred.
+pub async fn fetch_disabled_key_probes(
+    pool: &PgPool,
+    reason: KeyDisabledReason,
+    api_types: &[ApiType],
+) -> AppResult<Vec<DisabledKeyProbe>> {
+    let probes = sqlx::query_as!(
+        DisabledKeyProbe,
+        r#"SELECT DISTINCT ON (pk.id)
+            pk.id,
+            pk.provider_id,
+            pk.key,
+            pe.url AS endpoint_url,
+            pe.api_type AS "endpoint_api_type: ApiType"
+         FROM provider_keys pk
+         JOIN provider_endpoints pe
+           ON pe.provider_id = pk.provider_id AND pe.api_type = ANY($2) AND pe.enabled = true
+         WHERE pk.enabled = false AND pk.disabled_reason = $1
+         ORDER BY pk.id, array_position($2, pe.api_type), pe.created_at"#,
+        reason as _,
+        api_types as _
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(probes)
+}
@@ -0,0 +1,178 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::AppResult;
+
+/// A token bucket as stored in `rate_limit_buckets`. Capacity and refill
+/// rate come from the key's current limits on every call, so changed limits
+/// apply immediately.
+pub struct BucketLimit<'a> {
+    pub name: &'a str,
+    pub capacity: f64,
+    pub refill_per_sec: f64,
+}
+
+pub enum ConsumeOutcome {
+    Consumed,
+    /// The bucket at `index` holds only `tokens`.
+    Rejected {
+        index: usize,
+        tokens: f64,
+    },
+}
+
+/// Takes `amount` from every bucket in one transaction, or from none if any
+/// cannot cover it. Missing buckets start full; amounts above a bucket's
+/// capacity take a full bucket.
+pub async fn consume_buckets(
+    pool: &PgPool,
+    gateway_key_id: Uuid,
+    buckets: &[BucketLimit<'_>],
+    amount: f64,
+) -> AppResult<ConsumeOutcome> {
+    let mut tx = pool.begin().await?;
+
+    for (index, bucket) in buckets.iter().enumerate() {
+        let consumed = sqlx::query_scalar::<_, f64>(
+            "INSERT INTO rate_limit_buckets AS b (gateway_key_id, bucket, tokens, refilled_at)
+             SELECT $1, $2, $3 - $5, now()
+             WHERE $5 <= $3
+             ON CONFLICT (gateway_key_id, bucket) DO UPDATE
+             SET tokens = LEAST($3, b.tokens
+                     + EXTRACT(EPOCH FROM now() - b.refilled_at)::double precision * $4) - $5,
+                 refilled_at = now()
+             WHERE LEAST($3, b.tokens
+                     + EXTRACT(EPOCH FROM now() - b.refilled_at)::double precision * $4) >= $5
+             RETURNING tokens",
+        )
+        .bind(gateway_key_id)
+        .bind(bucket.name)
+        .bind(bucket.capacity)
+        .bind(bucket.refill_per_sec)
+        .bind(amount.min(bucket.capacity))
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if consumed.is_none() {
+            let tokens = bucket_tokens(&mut tx, gateway_key_id, bucket).await?;
+            tx.rollback().await?;
+            return Ok(ConsumeOutcome::Rejected { index, tokens });
+        }
+    }
+
+    tx.commit().await?;
+    Ok(ConsumeOutcome::Consumed)
+}
+
+/// Corrects earlier consumption. The result may be negative, down to minus
+/// the capacity.
+pub async fn adjust_buckets(
+    pool: &PgPool,
+    gateway_key_id: Uuid,
+    buckets: &[BucketLimit<'_>],
+    amount: f64,
+) -> AppResult<()> {
+    for bucket in buckets {
+        sqlx::query(
+            "UPDATE rate_limit_buckets
+             SET tokens = GREATEST(-$3, LEAST($3, tokens
+                     + EXTRACT(EPOCH FROM now() - refilled_at)::double precision * $4) - $5),
+                 refilled_at = now()
+             WHERE gateway_key_id = $1 AND bucket = $2",
+        )
+        .bind(gateway_key_id)
+        .bind(bucket.name)
+        .bind(bucket.capacity)
+        .bind(bucket.refill_per_sec)
+        .bind(amount)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Current tokens of each bucket, refilled up to now.
+pub async fn fetch_bucket_tokens(
+    pool: &PgPool,
+    gateway_key_id: Uuid,
+    buckets: &[BucketLimit<'_>],
+) -> AppResult<Vec<f64>> {
+    let mut conn = pool.acquire().await?;
+    let mut tokens = Vec::with_capacity(buckets.len());
+    for bucket in buckets {
+        tokens.push(bucket_tokens(&mut conn, gateway_key_id, bucket).await?);
+    }
+    Ok(tokens)
+}
+
+async fn bucket_tokens(
+    conn: &mut sqlx::PgConnection,
+    gateway_key_id: Uuid,
+    bucket: &BucketLimit<'_>,
+) -> AppResult<f64> {
+    let tokens = sqlx::query_scalar::<_, f64>(
+        "SELECT LEAST($3, tokens
+                + EXTRACT(EPOCH FROM now() - refilled_at)::double precision * $4)
+         FROM rate_limit_buckets
+         WHERE gateway_key_id = $1 AND bucket = $2",
+    )
+    .bind(gateway_key_id)
+    .bind(bucket.name)
+    .bind(bucket.capacity)
+    .bind(bucket.refill_per_sec)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(tokens.unwrap_or(bucket.capacity))
+}
+
+/// Records an in-flight request unless the key already has `limit` of them.
+/// Rows older than the lease are left over from replicas that stopped
+/// without releasing them and no longer count.
+pub async fn acquire_in_flight(
+    pool: &PgPool,
+    id: Uuid,
+    gateway_key_id: Uuid,
+    limit: i64,
+    lease_secs: f64,
+) -> AppResult<bool> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1::text, 0))")
+        .bind(gateway_key_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "DELETE FROM rate_limit_in_flight
+         WHERE gateway_key_id = $1 AND acquired_at <= now() - make_interval(secs => $2)",
+    )
+    .bind(gateway_key_id)
+    .bind(lease_secs)
+    .execute(&mut *tx)
+    .await?;
+
+    let inserted = sqlx::query(
+        "INSERT INTO rate_limit_in_flight (id, gateway_key_id, acquired_at)
+         SELECT $1, $2, now()
+         WHERE (SELECT count(*) FROM rate_limit_in_flight WHERE gateway_key_id = $2) < $3",
+    )
+    .bind(id)
+    .bind(gateway_key_id)
+    .bind(limit)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(inserted.rows_affected() > 0)
+}
+
+pub async fn release_in_flight(pool: &PgPool, id: Uuid) -> AppResult<()> {
+    sqlx::query("DELETE FROM rate_limit_in_flight WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -4,13 +4,14 @@ use uuid::Uuid;
 
 use super::types::ApiType;
 use crate::error::AppResult;
-use crate::utils::request_body_hash::hash_request_body_hex;
+use crate::utils::request_body_hash::{RequestBodyHash, request_body_hash_hex};
 
 #[derive(Debug, Default)]
 pub struct RequestLogFilter {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
     pub request_id: Option<Uuid>,
+    pub parent_request_id: Option<Uuid>,
     pub model: Option<String>,
     pub alias: Option<String>,
     pub provider: Option<String>,
@@ -21,6 +22,7 @@ pub struct RequestLogFilter {
 #[derive(Debug, sqlx::FromRow, Serialize)]
 pub struct RequestLog {
     pub request_id: Uuid,
+    pub parent_request_id: Option<Uuid>,
     pub gateway_key_id: Option<Uuid>,
     pub api_type: ApiType,
     pub model: Option<String>,
@@ -38,6 +40,9 @@ pub struct RequestLog {
     pub prompt_tokens: Option<i32>,
     pub completion_tokens: Option<i32>,
     pub total_tokens: Option<i32>,
+    pub cached_tokens: Option<i32>,
+    pub cost: Option<f64>,
+    pub ttft_ms: Option<i32>,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: time::OffsetDateTime,
     pub cache_layer: Option<String>,
@@ -46,6 +51,7 @@ pub struct RequestLog {
 #[derive(Debug, sqlx::FromRow, Serialize)]
 pub struct RequestLogSummary {
     pub request_id: Uuid,
+    pub parent_request_id: Option<Uuid>,
     pub gateway_key_id: Option<Uuid>,
     pub api_type: ApiType,
     pub model: Option<String>,
@@ -62,6 +68,9 @@ pub struct RequestLogSummary {
     pub prompt_tokens: Option<i32>,
     pub completion_tokens: Option<i32>,
     pub total_tokens: Option<i32>,
+    pub cached_tokens: Option<i32>,
+    pub cost: Option<f64>,
+    pub ttft_ms: Option<i32>,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: time::OffsetDateTime,
     pub cache_layer: Option<String>,
@@ -69,6 +78,7 @@ pub struct RequestLogSummary {
 
 pub struct RequestLogContext {
     pub request_id: Uuid,
+    pub parent_request_id: Option<Uuid>,
     pub gateway_key_id: Option<Uuid>,
     pub api_type: Option<ApiType>,
     pub model: Option<String>,
@@ -86,6 +96,13 @@ pub struct RequestLogContext {
     pub prompt_tokens: Option<i32>,
     pub completion_tokens: Option<i32>,
     pub total_tokens: Option<i32>,
+    pub cached_tokens: Option<i32>,
+    /// Time to the first streamed chunk; `None` for buffered responses.
+    pub ttft_ms: Option<i32>,
+    /// Whether the client asked for a streamed response.
+    pub stream: bool,
+    /// Key the response is cached under; `None` keeps it out of the cache.
+    pub request_body_hash: Option<RequestBodyHash>,
 }
 
 #[derive(Clone, Debug, sqlx::FromRow)]
@@ -94,6 +111,17 @@ pub struct CachedResponse {
     pub status_code: i32,
     pub response_body: Vec<u8>,
     pub response_content_type: Option<String>,
+    /// Whether the body is a captured SSE stream.
+    pub stream: bool,
+    pub created_at: time::OffsetDateTime,
+}
+
+impl CachedResponse {
+    pub fn age(&self) -> std::time::Duration {
+        (time::OffsetDateTime::now_utc() - self.created_at)
+            .try_into()
+            .unwrap_or_default()
+    }
 }
 
 fn append_filters<'a>(
@@ -131,6 +159,11 @@ fn append_filters<'a>(
         builder.push(format!(" AND {}.request_id = ", main_alias));
         builder.push_bind(request_id);
     }
+
+    if let Some(parent_request_id) = filter.parent_request_id {
+        builder.push(format!(" AND {}.parent_request_id = ", rl_alias));
+        builder.push_bind(parent_request_id);
+    }
 }
 
 pub async fn fetch_request_logs(
@@ -144,6 +177,7 @@ pub async fn fetch_request_logs(
         r#"
         SELECT 
             request_id,
+            parent_request_id,
             gateway_key_id,
             api_type,
             model,
@@ -159,6 +193,9 @@ pub async fn fetch_request_logs(
             prompt_tokens,
             completion_tokens,
             total_tokens,
+            cached_tokens,
+            cost,
+            ttft_ms,
             created_at,
             NULL::text as cache_layer
         FROM request_logs
@@ -174,6 +211,7 @@ pub async fn fetch_request_logs(
         r#"
         SELECT
             cl.request_id,
+            NULL::uuid as parent_request_id,
             cl.gateway_key_id,
             rl.api_type,
             rl.model,
@@ -189,6 +227,9 @@ pub async fn fetch_request_logs(
             rl.prompt_tokens,
             rl.completion_tokens,
             rl.total_tokens,
+            rl.cached_tokens,
+            0::double precision as cost,
+            NULL::integer as ttft_ms,
             cl.created_at,
             cl.cache_layer
         FROM cache_log cl
@@ -259,6 +300,7 @@ pub async fn fetch_request_log_detail(
         r#"
         SELECT 
             request_id,
+            parent_request_id,
             gateway_key_id,
             api_type,
             model,
@@ -276,6 +318,9 @@ pub async fn fetch_request_log_detail(
             prompt_tokens,
             completion_tokens,
             total_tokens,
+            cached_tokens,
+            cost,
+            ttft_ms,
             created_at,
             NULL::text as cache_layer
         FROM request_logs
@@ -283,6 +328,7 @@ pub async fn fetch_request_log_detail(
         UNION ALL
         SELECT
             cl.request_id,
+            NULL::uuid as parent_request_id,
             cl.gateway_key_id,
             rl.api_type,
             rl.model,
@@ -300,6 +346,9 @@ pub async fn fetch_request_log_detail(
             rl.prompt_tokens,
             rl.completion_tokens,
             rl.total_tokens,
+            rl.cached_tokens,
+            0::double precision as cost,
+            NULL::integer as ttft_ms,
             cl.created_at,
             cl.cache_layer
         FROM cache_log cl
@@ -314,11 +363,16 @@ pub async fn fetch_request_log_detail(
     Ok(log)
 }
 
-pub async fn record_request(pool: &PgPool, context: &RequestLogContext) -> AppResult<()> {
+pub async fn record_request(
+    pool: &PgPool,
+    context: &RequestLogContext,
+    cost: Option<f64>,
+) -> AppResult<()> {
     let Some(api_type) = context.api_type else {
         return Ok(());
     };
-    let request_body_hash = context.request_body.as_deref().map(hash_request_body_hex);
+    // Logs without a hash are never matched by the response cache.
+    let request_body_hash = context.request_body_hash.map(request_body_hash_hex);
 
     let client_ip = context
         .client_ip
@@ -328,6 +382,7 @@ pub async fn record_request(pool: &PgPool, context: &RequestLogContext) -> AppRe
     sqlx::query(
         "INSERT INTO request_logs (
             request_id,
+            parent_request_id,
             gateway_key_id,
             api_type,
             model,
@@ -345,14 +400,20 @@ pub async fn record_request(pool: &PgPool, context: &RequestLogContext) -> AppRe
             response_content_type,
             prompt_tokens,
             completion_tokens,
-            total_tokens
+            total_tokens,
+            cached_tokens,
+            cost,
+            ttft_ms,
+            stream
         ) VALUES (
-            $1, $2, $3, $4, $5, $6, $7,
-            $8, $9, $10::inet, $11, $12, $13,
-            $14, $15, $16, $17, $18, $19
+            $1, $2, $3, $4, $5, $6, $7, $8,
+            $9, $10, $11::inet, $12, $13, $14,
+            $15, $16, $17, $18, $19, $20,
+            $21, $22, $23, $24
         )",
     )
     .bind(context.request_id)
+    .bind(context.parent_request_id)
     .bind(context.gateway_key_id)
     .bind(api_type)
     .bind(context.model.as_deref())
@@ -371,15 +432,27 @@ pub async fn record_request(pool: &PgPool, context: &RequestLogContext) -> AppRe
     .bind(context.prompt_tokens)
     .bind(context.completion_tokens)
     .bind(context.total_tokens)
+    .bind(context.cached_tokens)
+    .bind(cost)
+    .bind(context.ttft_ms)
+    .bind(context.stream)
     .execute(pool)
     .await?;
 
     Ok(())
 }
 
+/// Latest successful response to a request with this hash, logged after
+/// `not_before` and with a body of at most `max_body_bytes`. Responses in
+/// the `stream` mode are preferred; the other mode is only returned when
+/// `any_stream_mode` is set.
 pub async fn find_cached_response(
     pool: &PgPool,
     request_body_hash: &str,
+    not_before: time::OffsetDateTime,
+    max_body_bytes: i64,
+    stream: bool,
+    any_stream_mode: bool,
 ) -> AppResult<Option<CachedResponse>> {
     let cached = sqlx::query_as::<_, CachedResponse>(
         r#"
@@ -387,16 +460,25 @@ pub async fn find_cached_response(
             id as source_request_log_id,
             status_code,
             response_body,
-            response_content_type
+            response_content_type,
+            stream,
+            created_at
         FROM request_logs
         WHERE request_body_hash = $1
           AND status_code BETWEEN 200 AND 299
           AND response_body IS NOT NULL
-        ORDER BY created_at DESC
+          AND created_at >= $2
+          AND octet_length(response_body) <= $3
+          AND ($5 OR stream = $4)
+        ORDER BY stream = $4 DESC, created_at DESC
         LIMIT 1
         "#,
     )
     .bind(request_body_hash)
+    .bind(not_before)
+    .bind(max_body_bytes)
+    .bind(stream)
+    .bind(any_stream_mode)
     .fetch_optional(pool)
     .await?;
 
@@ -91,7 +91,7 @@ pub async fn cache_hit_rate(
         cache_hit_count AS (
             SELECT count(*)::bigint AS total
             FROM cache_log
-            WHERE created_at >= $1 AND created_at <= $2
+            WHERE decision = 'hit' AND created_at >= $1 AND created_at <= $2
         )
         SELECT
             chc.total AS cache_hit_requests,
@@ -111,3 +111,196 @@ pub async fn cache_hit_rate(
 
     Ok(stats)
 }
+
+/// Spend logged from the pricing catalog; cache hits cost nothing.
+pub async fn total_cost(
+    pool: &PgPool,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> AppResult<f64> {
+    let cost = sqlx::query_scalar::<_, f64>(
+        r#"
+        SELECT COALESCE(SUM(cost), 0)::double precision
+        FROM request_logs
+        WHERE created_at >= $1 AND created_at <= $2
+        "#,
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(cost)
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct TokenUsagePoint {
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+    pub cost: f64,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct UsageBreakdown {
+    pub category: Option<String>,
+    pub requests: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+    pub cost: f64,
+}
+
+/// What request logs are grouped by in a breakdown.
+#[derive(Debug, Clone, Copy)]
+pub enum UsageDimension {
+    Provider,
+    GatewayKey,
+    Alias,
+    Model,
+    ApiType,
+}
+
+impl UsageDimension {
+    /// Expressions for the category label and the grouping; keys are grouped
+    /// by id since names need not be unique.
+    fn expressions(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Provider => ("rl.provider", "rl.provider"),
+            Self::GatewayKey => (
+                "COALESCE(gk.name, rl.gateway_key_id::text)",
+                "rl.gateway_key_id, gk.name",
+            ),
+            Self::Alias => ("rl.alias", "rl.alias"),
+            Self::Model => ("rl.model", "rl.model"),
+            Self::ApiType => ("rl.api_type::text", "rl.api_type"),
+        }
+    }
+}
+
+pub async fn tokens_over_time(
+    pool: &PgPool,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    granularity_seconds: i64,
+) -> AppResult<Vec<TokenUsagePoint>> {
+    let rows = sqlx::query_as::<_, TokenUsagePoint>(
+        r#"
+        SELECT
+            to_timestamp(floor(extract(epoch from created_at) / $3) * $3) AS time,
+            COALESCE(SUM(prompt_tokens), 0)::bigint AS prompt_tokens,
+            COALESCE(SUM(completion_tokens), 0)::bigint AS completion_tokens,
+            COALESCE(SUM(total_tokens), 0)::bigint AS total_tokens,
+            COALESCE(SUM(cost), 0)::double precision AS cost
+        FROM request_logs
+        WHERE created_at >= $1 AND created_at <= $2
+        GROUP BY 1
+        ORDER BY 1 ASC
+        "#,
+    )
+    .bind(start)
+    .bind(end)
+    .bind(granularity_seconds as f64)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// The `limit` largest categories by cost, then tokens, then requests.
+pub async fn usage_breakdown(
+    pool: &PgPool,
+    dimension: UsageDimension,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    limit: i64,
+) -> AppResult<Vec<UsageBreakdown>> {
+    let (category, group_by) = dimension.expressions();
+    let rows = sqlx::query_as::<_, UsageBreakdown>(&format!(
+        r#"
+        SELECT
+            {category} AS category,
+            count(*)::bigint AS requests,
+            COALESCE(SUM(rl.prompt_tokens), 0)::bigint AS prompt_tokens,
+            COALESCE(SUM(rl.completion_tokens), 0)::bigint AS completion_tokens,
+            COALESCE(SUM(rl.total_tokens), 0)::bigint AS total_tokens,
+            COALESCE(SUM(rl.cost), 0)::double precision AS cost
+        FROM request_logs rl
+        LEFT JOIN gateway_keys gk ON gk.id = rl.gateway_key_id
+        WHERE rl.created_at >= $1 AND rl.created_at <= $2
+        GROUP BY {group_by}
+        ORDER BY cost DESC, total_tokens DESC, requests DESC
+        LIMIT $3
+        "#
+    ))
+    .bind(start)
+    .bind(end)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Latency and failures of one category in one time bucket. Transport
+/// errors are attempts that got no HTTP response from the upstream.
+#[derive(Serialize, sqlx::FromRow)]
+pub struct PerformancePoint {
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    pub category: Option<String>,
+    pub requests: i64,
+    pub client_errors: i64,
+    pub server_errors: i64,
+    pub transport_errors: i64,
+    pub error_rate: f64,
+    pub latency_p50_ms: Option<f64>,
+    pub latency_p90_ms: Option<f64>,
+    pub latency_p99_ms: Option<f64>,
+    pub ttft_p50_ms: Option<f64>,
+    pub ttft_p90_ms: Option<f64>,
+    pub ttft_p99_ms: Option<f64>,
+}
+
+pub async fn performance_over_time(
+    pool: &PgPool,
+    dimension: UsageDimension,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    granularity_seconds: i64,
+) -> AppResult<Vec<PerformancePoint>> {
+    let (category, group_by) = dimension.expressions();
+    let rows = sqlx::query_as::<_, PerformancePoint>(&format!(
+        r#"
+        SELECT
+            to_timestamp(floor(extract(epoch from rl.created_at) / $3) * $3) AS time,
+            {category} AS category,
+            count(*)::bigint AS requests,
+            count(*) FILTER (WHERE rl.status_code BETWEEN 400 AND 499)::bigint AS client_errors,
+            count(*) FILTER (WHERE rl.status_code >= 500)::bigint AS server_errors,
+            count(*) FILTER (WHERE rl.status_code IS NULL)::bigint AS transport_errors,
+            (count(*) FILTER (WHERE rl.status_code IS NULL OR rl.status_code >= 400))::double precision
+                / count(*)::double precision AS error_rate,
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY rl.latency_ms) AS latency_p50_ms,
+            percentile_cont(0.9) WITHIN GROUP (ORDER BY rl.latency_ms) AS latency_p90_ms,
+            percentile_cont(0.99) WITHIN GROUP (ORDER BY rl.latency_ms) AS latency_p99_ms,
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY rl.ttft_ms) AS ttft_p50_ms,
+            percentile_cont(0.9) WITHIN GROUP (ORDER BY rl.ttft_ms) AS ttft_p90_ms,
+            percentile_cont(0.99) WITHIN GROUP (ORDER BY rl.ttft_ms) AS ttft_p99_ms
+        FROM request_logs rl
+        LEFT JOIN gateway_keys gk ON gk.id = rl.gateway_key_id
+        WHERE rl.created_at >= $1 AND rl.created_at <= $2
+        GROUP BY 1, {group_by}
+        ORDER BY 1 ASC, 2 ASC
+        "#
+    ))
+    .bind(start)
+    .bind(end)
+    .bind(granularity_seconds as f64)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
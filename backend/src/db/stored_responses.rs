@@ -0,0 +1,66 @@
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::AppResult;
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct StoredResponse {
+    pub input: Value,
+    pub output: Value,
+}
+
+pub struct NewStoredResponse {
+    pub id: String,
+    pub gateway_key_id: Uuid,
+    pub model: String,
+    pub input: Value,
+    pub output: Value,
+}
+
+pub async fn fetch_stored_response(
+    pool: &PgPool,
+    id: &str,
+    gateway_key_id: Uuid,
+) -> AppResult<Option<StoredResponse>> {
+    let response = sqlx::query_as::<_, StoredResponse>(
+        "SELECT input, output
+         FROM stored_responses
+         WHERE id = $1 AND gateway_key_id = $2",
+    )
+    .bind(id)
+    .bind(gateway_key_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(response)
+}
+
+pub async fn insert_stored_response(pool: &PgPool, response: &NewStoredResponse) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO stored_responses (id, gateway_key_id, model, input, output)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (id) DO NOTHING",
+    )
+    .bind(&response.id)
+    .bind(response.gateway_key_id)
+    .bind(&response.model)
+    .bind(&response.input)
+    .bind(&response.output)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_stored_responses_older_than(pool: &PgPool, age_secs: f64) -> AppResult<u64> {
+    let result = sqlx::query(
+        "DELETE FROM stored_responses
+         WHERE created_at < now() - make_interval(secs => $1)",
+    )
+    .bind(age_secs)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
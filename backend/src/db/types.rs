@@ -19,6 +19,9 @@ pub enum ApiType {
     #[sqlx(rename = "anthropic_messages")]
     #[serde(rename = "anthropic_messages")]
     AnthropicMessages,
+    #[sqlx(rename = "gemini_generate_content")]
+    #[serde(rename = "gemini_generate_content")]
+    GeminiGenerateContent,
 }
 
 impl std::fmt::Display for ApiType {
@@ -29,6 +32,7 @@ impl std::fmt::Display for ApiType {
             Self::OpenAiResponses => write!(f, "openai_responses"),
             Self::OpenAiModels => write!(f, "openai_models"),
             Self::AnthropicMessages => write!(f, "anthropic_messages"),
+            Self::GeminiGenerateContent => write!(f, "gemini_generate_content"),
         }
     }
 }
@@ -43,7 +47,126 @@ impl std::str::FromStr for ApiType {
             "openai_responses" => Ok(Self::OpenAiResponses),
             "openai_models" => Ok(Self::OpenAiModels),
             "anthropic_messages" => Ok(Self::AnthropicMessages),
+            "gemini_generate_content" => Ok(Self::GeminiGenerateContent),
             _ => Err(anyhow::anyhow!("unknown api_type: {}", s)),
         }
     }
 }
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    sqlx::Type,
+)]
+#[sqlx(type_name = "lb_strategy", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum LbStrategy {
+    #[default]
+    RoundRobin,
+    WeightedRandom,
+    PriorityWithFallback,
+    LeastLatency,
+}
+
+impl std::fmt::Display for LbStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RoundRobin => write!(f, "round_robin"),
+            Self::WeightedRandom => write!(f, "weighted_random"),
+            Self::PriorityWithFallback => write!(f, "priority_with_fallback"),
+            Self::LeastLatency => write!(f, "least_latency"),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, sqlx::Type,
+)]
+#[sqlx(type_name = "key_disabled_reason", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum KeyDisabledReason {
+    Manual,
+    Unauthorized,
+}
+
+impl std::fmt::Display for KeyDisabledReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Manual => write!(f, "manual"),
+            Self::Unauthorized => write!(f, "unauthorized"),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, sqlx::Type,
+)]
+#[sqlx(type_name = "budget_period", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetPeriod {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl std::fmt::Display for BudgetPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Daily => write!(f, "daily"),
+            Self::Weekly => write!(f, "weekly"),
+            Self::Monthly => write!(f, "monthly"),
+        }
+    }
+}
+
+/// Who may be served a cached response, ordered from most to least
+/// restrictive.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    sqlx::Type,
+)]
+#[sqlx(type_name = "cache_policy", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CachePolicy {
+    Disabled,
+    /// Only the gateway key whose request produced the response.
+    PerKey,
+    /// Every gateway key whose policy is also shared.
+    Shared,
+}
+
+impl std::fmt::Display for CachePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "disabled"),
+            Self::PerKey => write!(f, "per_key"),
+            Self::Shared => write!(f, "shared"),
+        }
+    }
+}
+
+/// Response cache settings of an alias or gateway key; `None` inherits.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, sqlx::FromRow,
+)]
+pub struct CacheSettings {
+    pub cache_ttl_secs: Option<i32>,
+    pub cache_policy: Option<CachePolicy>,
+    pub cache_non_deterministic: Option<bool>,
+}
@@ -1,11 +1,13 @@
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
 use thiserror::Error;
 
+use crate::services::rate_limit::RateLimited;
+
 pub type AppResult<T> = Result<T, AppError>;
 
 #[derive(Debug, Error)]
@@ -16,10 +18,18 @@ pub enum AppError {
     Unauthorized,
     #[error("forbidden: {0}")]
     Forbidden(String),
-    #[error("too many requests")]
-    TooManyRequests,
+    #[error("payment required: {0}")]
+    PaymentRequired(String),
+    #[error("too many requests: {0}")]
+    TooManyRequests(RateLimited),
     #[error("not found")]
     NotFound,
+    #[error("bad gateway: {0}")]
+    BadGateway(String),
+    #[error("service unavailable: {0}")]
+    ServiceUnavailable(String),
+    #[error("gateway timeout: {0}")]
+    GatewayTimeout(String),
     #[error(transparent)]
     Internal(#[from] anyhow::Error),
 }
@@ -41,8 +51,14 @@ impl IntoResponse for AppError {
             AppError::BadRequest(message) => (StatusCode::BAD_REQUEST, message.clone()),
             AppError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
             AppError::Forbidden(message) => (StatusCode::FORBIDDEN, message.clone()),
-            AppError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            AppError::PaymentRequired(message) => (StatusCode::PAYMENT_REQUIRED, message.clone()),
+            AppError::TooManyRequests(_) => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
             AppError::NotFound => (StatusCode::NOT_FOUND, self.to_string()),
+            AppError::BadGateway(message) => (StatusCode::BAD_GATEWAY, message.clone()),
+            AppError::ServiceUnavailable(message) => {
+                (StatusCode::SERVICE_UNAVAILABLE, message.clone())
+            }
+            AppError::GatewayTimeout(message) => (StatusCode::GATEWAY_TIMEOUT, message.clone()),
             AppError::Internal(err) => {
                 tracing::error!(error = %err, "internal error");
                 (
@@ -52,6 +68,14 @@ impl IntoResponse for AppError {
             }
         };
 
-        (status, Json(ErrorResponse { error: message })).into_response()
+        let mut response = (status, Json(ErrorResponse { error: message })).into_response();
+        if let AppError::TooManyRequests(limited) = &self {
+            // Whole seconds, rounded up so clients never retry too early.
+            let retry_after = limited.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
     }
 }
@@ -9,7 +9,8 @@ use uuid::Uuid;
 use crate::{
     db::{
         alias_targets::{AliasTarget, AliasTargetDetail},
-        aliases::Alias,
+        aliases::{Alias, CacheSettingsUpdate},
+        types::{CacheSettings, LbStrategy},
     },
     error::{AppError, AppResult},
     services::aliases,
@@ -43,13 +44,24 @@ pub async fn list_aliases(
 #[derive(Debug, Deserialize)]
 pub struct CreateAliasRequest {
     pub name: String,
+    pub strategy: Option<LbStrategy>,
+    #[serde(flatten)]
+    pub cache: CacheSettings,
+    pub semantic_cache_threshold: Option<f32>,
 }
 
 pub async fn create_alias(
     State(state): State<AppState>,
     Json(payload): Json<CreateAliasRequest>,
 ) -> AppResult<Json<Alias>> {
-    let alias = aliases::create_alias(&state.pool, payload.name).await?;
+    let alias = aliases::create_alias(
+        &state.pool,
+        payload.name,
+        payload.strategy,
+        payload.cache,
+        payload.semantic_cache_threshold,
+    )
+    .await?;
     Ok(Json(alias))
 }
 
@@ -67,6 +79,9 @@ pub async fn get_alias(
 pub struct UpdateAliasRequest {
     pub name: Option<String>,
     pub enabled: Option<bool>,
+    pub strategy: Option<LbStrategy>,
+    #[serde(flatten)]
+    pub cache: CacheSettingsUpdate,
 }
 
 pub async fn update_alias(
@@ -79,6 +94,8 @@ pub async fn update_alias(
         id,
         payload.name,
         payload.enabled,
+        payload.strategy,
+        payload.cache,
     )
     .await?
     .ok_or(AppError::NotFound)?;
@@ -108,6 +125,8 @@ pub struct CreateAliasTargetRequest {
     pub provider_id: Uuid,
     pub model_id: String,
     pub extra_fields: Option<Value>,
+    pub weight: Option<i32>,
+    pub priority: Option<i32>,
 }
 
 pub async fn create_alias_target(
@@ -115,9 +134,16 @@ pub async fn create_alias_target(
     Path(alias_id): Path<Uuid>,
     Json(payload): Json<CreateAliasTargetRequest>,
 ) -> AppResult<Json<AliasTarget>> {
-    let target =
-        aliases::create_alias_target(&state.pool, alias_id, payload.provider_id, payload.model_id, payload.extra_fields)
-            .await?;
+    let target = aliases::create_alias_target(
+        &state.pool,
+        alias_id,
+        payload.provider_id,
+        payload.model_id,
+        payload.extra_fields,
+        payload.weight,
+        payload.priority,
+    )
+    .await?;
     Ok(Json(target))
 }
 
@@ -127,6 +153,8 @@ pub struct UpdateAliasTargetRequest {
     pub model_id: Option<String>,
     pub enabled: Option<bool>,
     pub extra_fields: Option<Value>,
+    pub weight: Option<i32>,
+    pub priority: Option<i32>,
 }
 
 pub async fn update_alias_target(
@@ -141,6 +169,8 @@ pub async fn update_alias_target(
         payload.model_id,
         payload.enabled,
         payload.extra_fields,
+        payload.weight,
+        payload.priority,
     )
     .await?
     .ok_or(AppError::NotFound)?;
@@ -0,0 +1,95 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    db::cache_entries::CacheEntry,
+    error::AppResult,
+    services::cache_entries::{self, PurgeFilter},
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct ListCacheEntriesQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub alias: Option<String>,
+    pub gateway_key_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+pub struct ListCacheEntriesResponse {
+    pub data: Vec<CacheEntry>,
+    pub total: i64,
+}
+
+pub async fn list_cache_entries(
+    State(state): State<AppState>,
+    Query(query): Query<ListCacheEntriesQuery>,
+) -> AppResult<Json<ListCacheEntriesResponse>> {
+    let (entries, total) = cache_entries::list_entries(
+        &state.pool,
+        query.alias,
+        query.gateway_key_id,
+        query.limit.unwrap_or(20),
+        query.offset.unwrap_or(0),
+    )
+    .await?;
+
+    Ok(Json(ListCacheEntriesResponse {
+        data: entries,
+        total,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct PurgeCacheQuery {
+    pub alias: Option<String>,
+    pub gateway_key_id: Option<Uuid>,
+    pub older_than_secs: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct PurgeCacheResponse {
+    pub purged: usize,
+}
+
+pub async fn purge_cache_entries(
+    State(state): State<AppState>,
+    Query(query): Query<PurgeCacheQuery>,
+) -> AppResult<Json<PurgeCacheResponse>> {
+    let filter = PurgeFilter {
+        request_body_hash: None,
+        alias: query.alias,
+        gateway_key_id: query.gateway_key_id,
+        older_than_secs: query.older_than_secs,
+    };
+    let purged = cache_entries::purge(&state.pool, &state.response_cache, filter).await?;
+    Ok(Json(PurgeCacheResponse { purged }))
+}
+
+pub async fn purge_cache_entry(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> AppResult<Json<PurgeCacheResponse>> {
+    let filter = PurgeFilter {
+        request_body_hash: Some(hash),
+        ..Default::default()
+    };
+    let purged = cache_entries::purge(&state.pool, &state.response_cache, filter).await?;
+    Ok(Json(PurgeCacheResponse { purged }))
+}
+
+#[derive(Serialize)]
+pub struct FlushCacheResponse {
+    pub flushed: u64,
+}
+
+/// Empties the in-memory layer; stored entries are left alone.
+pub async fn flush_cache(State(state): State<AppState>) -> Json<FlushCacheResponse> {
+    let flushed = state.response_cache.flush();
+    Json(FlushCacheResponse { flushed })
+}
@@ -2,13 +2,19 @@ use axum::{
     Json,
     extract::{Path, Query, State},
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    db::gateway_keys::GatewayKey,
+    db::{
+        gateway_keys::{GatewayKey, KeyLimits},
+        types::CacheSettings,
+    },
     error::{AppError, AppResult},
-    services::gateway_keys,
+    services::{
+        budgets::{self, Budget, BudgetStatus},
+        gateway_keys,
+    },
     state::AppState,
 };
 
@@ -39,8 +45,10 @@ pub async fn list(
 #[derive(Debug, Deserialize)]
 pub struct CreateGatewayKeyRequest {
     pub name: Option<String>,
-    pub rate_limit_rps: Option<i32>,
-    pub rate_limit_rpm: Option<i32>,
+    #[serde(flatten)]
+    pub limits: KeyLimits,
+    #[serde(flatten)]
+    pub cache: CacheSettings,
     pub allowed_models: Option<Vec<String>>,
 }
 
@@ -51,30 +59,40 @@ pub async fn create(
     let key = gateway_keys::create_gateway_key(
         &state.pool,
         payload.name,
-        payload.rate_limit_rps,
-        payload.rate_limit_rpm,
+        payload.limits,
+        payload.cache,
         payload.allowed_models,
     )
     .await?;
     Ok(Json(key))
 }
 
+#[derive(Debug, Serialize)]
+pub struct GatewayKeyDetails {
+    #[serde(flatten)]
+    pub key: GatewayKey,
+    pub budget: Option<BudgetStatus>,
+}
+
 pub async fn get(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
-) -> AppResult<Json<GatewayKey>> {
+) -> AppResult<Json<GatewayKeyDetails>> {
     let key = gateway_keys::get_gateway_key(&state.pool, id)
         .await?
         .ok_or(AppError::NotFound)?;
-    Ok(Json(key))
+    let budget = budgets::fetch_budget_status(&state.pool, &state.budget_spend, id).await?;
+    Ok(Json(GatewayKeyDetails { key, budget }))
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateGatewayKeyRequest {
     pub name: Option<String>,
     pub enabled: Option<bool>,
-    pub rate_limit_rps: Option<i32>,
-    pub rate_limit_rpm: Option<i32>,
+    #[serde(flatten)]
+    pub limits: KeyLimits,
+    #[serde(flatten)]
+    pub cache: CacheSettings,
     pub allowed_models: Option<Vec<String>>,
 }
 
@@ -88,8 +106,8 @@ pub async fn update(
         id,
         payload.name,
         payload.enabled,
-        payload.rate_limit_rps,
-        payload.rate_limit_rpm,
+        payload.limits,
+        payload.cache,
         payload.allowed_models,
     )
     .await?
@@ -104,3 +122,20 @@ pub async fn delete(State(state): State<AppState>, Path(id): Path<Uuid>) -> AppR
         Err(AppError::NotFound)
     }
 }
+
+pub async fn set_budget(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<Budget>,
+) -> AppResult<Json<BudgetStatus>> {
+    let status = budgets::set_budget(&state.pool, &state.budget_spend, id, payload).await?;
+    Ok(Json(status))
+}
+
+pub async fn delete_budget(State(state): State<AppState>, Path(id): Path<Uuid>) -> AppResult<()> {
+    if budgets::delete_budget(&state.pool, id).await? {
+        Ok(())
+    } else {
+        Err(AppError::NotFound)
+    }
+}
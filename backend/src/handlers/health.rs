@@ -0,0 +1,30 @@
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Serialize;
+
+use crate::{
+    services::health::{self, CheckStatus, Readiness},
+    state::AppState,
+};
+
+#[derive(Debug, Serialize)]
+pub struct Liveness {
+    pub status: CheckStatus,
+    pub version: &'static str,
+}
+
+pub async fn healthz() -> Json<Liveness> {
+    Json(Liveness {
+        status: CheckStatus::Pass,
+        version: env!("CARGO_PKG_VERSION"),
+    })
+}
+
+pub async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let readiness =
+        health::check_readiness(&state.pool, state.background_tasks.is_draining()).await;
+    let status = match readiness.status {
+        CheckStatus::Pass => StatusCode::OK,
+        CheckStatus::Fail => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(readiness))
+}
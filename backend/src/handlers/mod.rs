@@ -1,7 +1,11 @@
 pub mod aliases;
 pub mod auth;
+pub mod cache;
 pub mod gateway_keys;
+pub mod health;
+pub mod metrics;
 pub mod openai;
+pub mod pricing;
 pub mod providers;
 pub mod request_logs;
 pub mod stats;
@@ -1,10 +1,18 @@
-use axum::{Extension, Json, body::Body, extract::State, http::Response};
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, Response},
+};
+use serde::Deserialize;
 use serde_json::Value;
 
 use crate::{
     error::AppResult,
     middleware::{auth::GatewayKeyId, request_log::ClientInfo},
+    services::models::{self, ModelListFormat},
     state::AppState,
+    utils::gemini_target,
 };
 
 pub async fn chat_completions(
@@ -54,3 +62,56 @@ pub async fn anthropic_messages(
         .anthropic_messages(gateway_key_id, payload, client_info)
         .await
 }
+
+#[derive(Debug, Deserialize)]
+pub struct GeminiQuery {
+    /// Response format; streams are a JSON array unless it is `sse`.
+    pub alt: Option<String>,
+}
+
+pub async fn gemini_generate_content(
+    State(state): State<AppState>,
+    gateway_key_id: GatewayKeyId,
+    Extension(client_info): Extension<ClientInfo>,
+    Path(target): Path<String>,
+    Query(query): Query<GeminiQuery>,
+    Json(payload): Json<Value>,
+) -> AppResult<Response<Body>> {
+    let (model, stream) = gemini_target(&target)?;
+    state
+        .openai
+        .gemini_generate_content(
+            gateway_key_id,
+            model,
+            stream,
+            query.alt,
+            payload,
+            client_info,
+        )
+        .await
+}
+
+pub async fn list_models(
+    State(state): State<AppState>,
+    gateway_key_id: GatewayKeyId,
+    headers: HeaderMap,
+) -> AppResult<Json<Value>> {
+    let entries = models::list_models(&state.pool, gateway_key_id.0).await?;
+    Ok(Json(models::render_list(
+        &entries,
+        ModelListFormat::from_headers(&headers),
+    )))
+}
+
+pub async fn get_model(
+    State(state): State<AppState>,
+    gateway_key_id: GatewayKeyId,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> AppResult<Json<Value>> {
+    let entry = models::get_model(&state.pool, gateway_key_id.0, &id).await?;
+    Ok(Json(models::render_model(
+        &entry,
+        ModelListFormat::from_headers(&headers),
+    )))
+}
@@ -0,0 +1,54 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use uuid::Uuid;
+
+use crate::{
+    db::pricing::ModelPricing,
+    error::{AppError, AppResult},
+    services::pricing::{self, ModelPricingInput},
+    state::AppState,
+};
+
+pub async fn list(State(state): State<AppState>) -> AppResult<Json<Vec<ModelPricing>>> {
+    let pricing = pricing::list_pricing(&state.pool).await?;
+    Ok(Json(pricing))
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Json(payload): Json<ModelPricingInput>,
+) -> AppResult<Json<ModelPricing>> {
+    let pricing = pricing::create_pricing(&state.pool, &payload).await?;
+    Ok(Json(pricing))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ModelPricing>> {
+    let pricing = pricing::get_pricing(&state.pool, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(pricing))
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<ModelPricingInput>,
+) -> AppResult<Json<ModelPricing>> {
+    let pricing = pricing::update_pricing(&state.pool, id, &payload)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(pricing))
+}
+
+pub async fn delete(State(state): State<AppState>, Path(id): Path<Uuid>) -> AppResult<()> {
+    if pricing::delete_pricing(&state.pool, id).await? {
+        Ok(())
+    } else {
+        Err(AppError::NotFound)
+    }
+}
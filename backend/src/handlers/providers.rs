@@ -11,7 +11,7 @@ use crate::{
         types::ApiType,
     },
     error::{AppError, AppResult},
-    services::providers,
+    services::providers::{self, ProviderHealth},
     state::AppState,
 };
 
@@ -220,3 +220,15 @@ pub async fn list_provider_models(
     let models = state.openai.list_models(provider_id).await?;
     Ok(Json(models))
 }
+
+// Health
+
+pub async fn get_provider_health(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ProviderHealth>> {
+    let health = providers::get_provider_health(&state.pool, &state.circuit_breakers, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(health))
+}
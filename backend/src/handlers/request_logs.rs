@@ -17,6 +17,7 @@ pub struct ListRequestLogsQuery {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
     pub request_id: Option<Uuid>,
+    pub parent_request_id: Option<Uuid>,
     pub model: Option<String>,
     pub alias: Option<String>,
     pub provider: Option<String>,
@@ -38,6 +39,7 @@ pub async fn list_request_logs(
         limit: query.limit.or(Some(20)),
         offset: query.offset.or(Some(0)),
         request_id: query.request_id,
+        parent_request_id: query.parent_request_id,
         model: query.model,
         alias: query.alias,
         provider: query.provider,
@@ -6,9 +6,13 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 use crate::{
-    db::stats::{CategoryCount, TimeSeriesPoint},
+    constants::{DEFAULT_STATS_TOP_N, MAX_STATS_TOP_N},
+    db::stats::{
+        CategoryCount, PerformancePoint, TimeSeriesPoint, TokenUsagePoint, UsageBreakdown,
+        UsageDimension,
+    },
     error::AppResult,
-    services::stats,
+    services::stats::{self, StatsGranularity},
     state::AppState,
 };
 
@@ -20,15 +24,25 @@ pub struct StatsQuery {
     #[serde(default)]
     #[serde(with = "time::serde::rfc3339::option")]
     pub end: Option<OffsetDateTime>,
+    pub granularity: Option<StatsGranularity>,
+    /// Number of entries in each top-N breakdown.
+    pub top: Option<i64>,
 }
 
 #[derive(Serialize)]
 pub struct DashboardStats {
+    pub granularity_seconds: i64,
     pub requests_over_time: Vec<TimeSeriesPoint>,
+    pub tokens_over_time: Vec<TokenUsagePoint>,
     pub requests_by_provider: Vec<CategoryCount>,
+    pub top_gateway_keys: Vec<UsageBreakdown>,
+    pub top_aliases: Vec<UsageBreakdown>,
+    pub top_models: Vec<UsageBreakdown>,
+    pub top_api_types: Vec<UsageBreakdown>,
     pub cache_hit_requests: i64,
     pub cache_total_requests: i64,
     pub cache_hit_rate: f64,
+    pub total_cost: f64,
 }
 
 pub async fn get_dashboard_stats(
@@ -37,16 +51,90 @@ pub async fn get_dashboard_stats(
 ) -> AppResult<Json<DashboardStats>> {
     let end = query.end.unwrap_or_else(OffsetDateTime::now_utc);
     let start = query.start.unwrap_or_else(|| end - time::Duration::days(7));
+    let granularity_seconds = stats::bucket_seconds(start, end, query.granularity)?;
+    let top = query
+        .top
+        .unwrap_or(DEFAULT_STATS_TOP_N)
+        .clamp(1, MAX_STATS_TOP_N);
+    let pool = &state.pool;
 
-    let requests_over_time = stats::get_requests_over_time(&state.pool, start, end).await?;
-    let requests_by_provider = stats::get_requests_by_provider(&state.pool, start, end).await?;
-    let cache_hit_rate_stats = stats::get_cache_hit_rate(&state.pool, start, end).await?;
+    let requests_over_time =
+        stats::get_requests_over_time(pool, start, end, granularity_seconds).await?;
+    let tokens_over_time =
+        stats::get_tokens_over_time(pool, start, end, granularity_seconds).await?;
+    let requests_by_provider = stats::get_requests_by_provider(pool, start, end).await?;
+    let top_gateway_keys =
+        stats::get_usage_breakdown(pool, UsageDimension::GatewayKey, start, end, top).await?;
+    let top_aliases =
+        stats::get_usage_breakdown(pool, UsageDimension::Alias, start, end, top).await?;
+    let top_models =
+        stats::get_usage_breakdown(pool, UsageDimension::Model, start, end, top).await?;
+    let top_api_types =
+        stats::get_usage_breakdown(pool, UsageDimension::ApiType, start, end, top).await?;
+    let cache_hit_rate_stats = stats::get_cache_hit_rate(pool, start, end).await?;
+    let total_cost = stats::get_total_cost(pool, start, end).await?;
 
     Ok(Json(DashboardStats {
+        granularity_seconds,
         requests_over_time,
+        tokens_over_time,
         requests_by_provider,
+        top_gateway_keys,
+        top_aliases,
+        top_models,
+        top_api_types,
         cache_hit_requests: cache_hit_rate_stats.cache_hit_requests,
         cache_total_requests: cache_hit_rate_stats.cache_total_requests,
         cache_hit_rate: cache_hit_rate_stats.cache_hit_rate,
+        total_cost,
+    }))
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PerformanceGroupBy {
+    #[default]
+    Provider,
+    Alias,
+}
+
+#[derive(Deserialize)]
+pub struct PerformanceQuery {
+    #[serde(default)]
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub start: Option<OffsetDateTime>,
+    #[serde(default)]
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub end: Option<OffsetDateTime>,
+    pub granularity: Option<StatsGranularity>,
+    #[serde(default)]
+    pub group_by: PerformanceGroupBy,
+}
+
+#[derive(Serialize)]
+pub struct PerformanceStats {
+    pub granularity_seconds: i64,
+    pub series: Vec<PerformancePoint>,
+}
+
+pub async fn get_performance_stats(
+    State(state): State<AppState>,
+    Query(query): Query<PerformanceQuery>,
+) -> AppResult<Json<PerformanceStats>> {
+    let end = query.end.unwrap_or_else(OffsetDateTime::now_utc);
+    let start = query.start.unwrap_or_else(|| end - time::Duration::days(7));
+    let granularity_seconds = stats::bucket_seconds(start, end, query.granularity)?;
+    let dimension = match query.group_by {
+        PerformanceGroupBy::Provider => UsageDimension::Provider,
+        PerformanceGroupBy::Alias => UsageDimension::Alias,
+    };
+
+    let series =
+        stats::get_performance_over_time(&state.pool, dimension, start, end, granularity_seconds)
+            .await?;
+
+    Ok(Json(PerformanceStats {
+        granularity_seconds,
+        series,
     }))
 }
@@ -18,10 +18,17 @@ use sqlx::postgres::PgPoolOptions;
 use tracing_subscriber::EnvFilter;
 
 use crate::{
-    config::load_config,
+    config::{RateLimitBackend, load_config},
     services::{
-        auth::LoginProtection, background::BackgroundTasks, openai::OpenAiService,
-        rate_limit::RateLimiter, response_cache::ResponseCache,
+        auth::LoginProtection,
+        background::BackgroundTasks,
+        budgets::BudgetSpend,
+        circuit_breaker::CircuitBreakers,
+        key_revalidation,
+        metrics::Metrics,
+        openai::{self, OpenAiService},
+        rate_limit::RateLimiter,
+        response_cache::ResponseCache,
     },
     state::AppState,
 };
@@ -70,17 +77,44 @@ async fn main() -> anyhow::Result<()> {
         .await?;
 
     tracing::info!("running database migrations");
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    db::MIGRATOR.run(&pool).await?;
     tracing::info!("database migrations complete");
 
     let background_tasks = BackgroundTasks::new();
     let http_client = reqwest::Client::builder()
         .user_agent(constants::UPSTREAM_USER_AGENT)
+        .connect_timeout(Duration::from_secs(
+            constants::UPSTREAM_CONNECT_TIMEOUT_SECS,
+        ))
+        .read_timeout(Duration::from_secs(constants::UPSTREAM_READ_TIMEOUT_SECS))
         .build()?;
-    let openai = OpenAiService::new(pool.clone(), http_client, background_tasks.clone());
-    let rate_limiter = RateLimiter::new();
-    let login_protection = LoginProtection::new();
-    let response_cache = ResponseCache::new();
+    let circuit_breakers = CircuitBreakers::new();
+    let (rate_limiter, login_protection) = match config.rate_limit.backend {
+        RateLimitBackend::Memory => (RateLimiter::new(), LoginProtection::new()),
+        RateLimitBackend::Postgres => (
+            RateLimiter::postgres(pool.clone(), background_tasks.clone()),
+            LoginProtection::postgres(pool.clone()),
+        ),
+    };
+    key_revalidation::spawn_key_revalidation(pool.clone(), http_client.clone(), &background_tasks);
+    openai::spawn_stored_responses_purge(
+        pool.clone(),
+        Duration::from_secs(config.responses.store_retention_secs),
+        &background_tasks,
+    );
+    let metrics = Metrics::new();
+    let budget_spend = BudgetSpend::new();
+    let openai = OpenAiService::new(
+        pool.clone(),
+        http_client,
+        background_tasks.clone(),
+        circuit_breakers.clone(),
+        rate_limiter.clone(),
+        budget_spend.clone(),
+        metrics.clone(),
+        config.responses.store_enabled,
+    );
+    let response_cache = ResponseCache::new(&config.response_cache);
     let state = AppState {
         pool: pool.clone(),
         openai,
@@ -88,9 +122,33 @@ async fn main() -> anyhow::Result<()> {
         rate_limiter,
         login_protection,
         response_cache,
+        circuit_breakers,
+        budget_spend,
         background_tasks: background_tasks.clone(),
+        metrics,
     };
+    let metrics_app = router::metrics(state.clone());
     let app = router::app(state);
+    let app = match config.server.metrics_port {
+        Some(port) => {
+            let addr: SocketAddr = format!("{}:{}", config.server.host, port)
+                .parse()
+                .expect("valid metrics listen address");
+            tracing::info!("serving metrics on {}", addr);
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            let shutdown_token = background_tasks.token();
+            tokio::spawn(async move {
+                let result = axum::serve(listener, metrics_app)
+                    .with_graceful_shutdown(shutdown_token.cancelled_owned())
+                    .await;
+                if let Err(err) = result {
+                    tracing::error!(error = %err, "metrics server failed");
+                }
+            });
+            app
+        }
+        None => app.merge(metrics_app),
+    };
 
     let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port)
         .parse()
@@ -117,29 +175,45 @@ async fn main() -> anyhow::Result<()> {
         }
         signal = wait_for_shutdown_signal() => {
             let signal = signal?;
-            shutdown_started_at = Some(Instant::now());
+            background_tasks.begin_drain();
 
             tracing::info!(
                 signal = signal.as_str(),
-                timeout_secs = config.server.graceful_shutdown_timeout_secs,
-                "shutdown signal received, stopping new connections"
+                drain_secs = config.server.shutdown_drain_secs,
+                "shutdown signal received, reporting unready while draining"
             );
-            server_shutdown_token.cancel();
+            let drain = Duration::from_secs(config.server.shutdown_drain_secs);
+            let stopped_while_draining = tokio::select! {
+                result = &mut server => {
+                    server_result = result.map_err(anyhow::Error::from);
+                    true
+                }
+                _ = tokio::time::sleep(drain) => false,
+            };
 
+            shutdown_started_at = Some(Instant::now());
             tracing::info!(
                 timeout_secs = config.server.graceful_shutdown_timeout_secs,
-                "waiting for in-flight requests to complete"
+                "stopping new connections"
             );
-            match tokio::time::timeout(shutdown_timeout, &mut server).await {
-                Ok(result) => {
-                    server_result = result.map_err(anyhow::Error::from);
-                    tracing::info!("http server stopped accepting connections");
-                }
-                Err(_) => {
-                    tracing::warn!(
-                        timeout_secs = config.server.graceful_shutdown_timeout_secs,
-                        "graceful shutdown timed out while waiting for in-flight requests"
-                    );
+            server_shutdown_token.cancel();
+
+            if !stopped_while_draining {
+                tracing::info!(
+                    timeout_secs = config.server.graceful_shutdown_timeout_secs,
+                    "waiting for in-flight requests to complete"
+                );
+                match tokio::time::timeout(shutdown_timeout, &mut server).await {
+                    Ok(result) => {
+                        server_result = result.map_err(anyhow::Error::from);
+                        tracing::info!("http server stopped accepting connections");
+                    }
+                    Err(_) => {
+                        tracing::warn!(
+                            timeout_secs = config.server.graceful_shutdown_timeout_secs,
+                            "graceful shutdown timed out while waiting for in-flight requests"
+                        );
+                    }
                 }
             }
         }
@@ -1,7 +1,7 @@
 use axum::{
     body::{self, Body},
     extract::{ConnectInfo, State},
-    http::{Request, header},
+    http::{Method, Request, header},
     middleware::Next,
     response::IntoResponse,
 };
@@ -9,8 +9,11 @@ use serde_json::Value;
 use std::net::SocketAddr;
 
 use crate::{
-    constants::MAX_REQUEST_BODY_BYTES, error::AppError, services::auth, state::AppState,
-    utils::extract_model_from_payload,
+    constants::{GEMINI_MODELS_PATH, MAX_REQUEST_BODY_BYTES},
+    error::AppError,
+    services::auth,
+    state::AppState,
+    utils::{extract_model_from_payload, gemini_target},
 };
 use uuid::Uuid;
 
@@ -32,7 +35,8 @@ pub async fn auth_middleware(
         .into_response();
     }
 
-    let api_key = auth::extract_api_key(req.headers());
+    let api_key =
+        auth::extract_api_key(req.headers()).or_else(|| auth::extract_query_api_key(req.uri()));
     let Some(api_key) = api_key else {
         state.login_protection.record_failure(&ip).await;
         return AppError::Unauthorized.into_response();
@@ -50,6 +54,12 @@ pub async fn auth_middleware(
     let mut req = req;
     req.extensions_mut().insert(GatewayKeyId(gateway_key.id));
 
+    // Model listings carry no model in the body; they are filtered by the
+    // whitelist in the handler instead.
+    if req.method() == Method::GET && req.uri().path().starts_with("/v1/models") {
+        return next.run(req).await;
+    }
+
     let whitelist = match auth::fetch_model_whitelist(&state.pool, gateway_key.id).await {
         Ok(models) => models,
         Err(err) => return err.into_response(),
@@ -98,6 +108,9 @@ fn extract_model_for_whitelist_check(
     content_type: Option<&header::HeaderValue>,
     body_bytes: &[u8],
 ) -> Result<String, AppError> {
+    if let Some(target) = path.strip_prefix(GEMINI_MODELS_PATH) {
+        return gemini_target(target).map(|(model, _)| model.to_string());
+    }
     if !matches!(
         path,
         "/v1/chat/completions" | "/v1/embeddings" | "/v1/responses" | "/v1/messages"
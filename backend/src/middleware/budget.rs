@@ -0,0 +1,69 @@
+use axum::{
+    extract::State,
+    http::{HeaderValue, Method, Request},
+    middleware::Next,
+    response::IntoResponse,
+};
+
+use crate::{
+    constants::BUDGET_WARNING_HEADER, error::AppError, middleware::auth::GatewayKeyId,
+    services::budgets, state::AppState,
+};
+
+/// Rejects requests from keys over their hard budget before they are routed,
+/// and flags responses once the soft budget is reached. Model listings cost
+/// nothing and are never held back.
+pub async fn budget_middleware(
+    State(state): State<AppState>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> impl IntoResponse {
+    if req.method() != Method::POST {
+        return next.run(req).await;
+    }
+
+    let Some(gateway_key_id) = req.extensions().get::<GatewayKeyId>().copied() else {
+        return AppError::Unauthorized.into_response();
+    };
+
+    let status = match budgets::fetch_budget_status(
+        &state.pool,
+        &state.budget_spend,
+        gateway_key_id.0,
+    )
+    .await
+    {
+        Ok(Some(status)) => status,
+        Ok(None) => return next.run(req).await,
+        Err(err) => return err.into_response(),
+    };
+
+    if status.hard_limit_reached() {
+        return AppError::PaymentRequired(format!(
+            "{} budget exhausted: spent {:.2} of {:.2}",
+            status.budget.period,
+            status.spend,
+            status.budget.hard_limit.unwrap_or_default()
+        ))
+        .into_response();
+    }
+
+    let mut response = next.run(req).await;
+    if status.soft_limit_reached() {
+        tracing::warn!(
+            gateway_key_id = %gateway_key_id.0,
+            spend = status.spend,
+            "gateway key passed its soft budget"
+        );
+        let warning = format!(
+            "{} spend {:.2} passed soft limit {:.2}",
+            status.budget.period,
+            status.spend,
+            status.budget.soft_limit.unwrap_or_default()
+        );
+        if let Ok(value) = HeaderValue::try_from(warning) {
+            response.headers_mut().insert(BUDGET_WARNING_HEADER, value);
+        }
+    }
+    response
+}
@@ -1,5 +1,6 @@
 pub mod admin_auth;
 pub mod auth;
+pub mod budget;
 pub mod rate_limit;
 pub mod request_log;
 pub mod response_cache;
@@ -1,7 +1,21 @@
-use axum::{extract::State, http::Request, middleware::Next, response::IntoResponse};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderMap, HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use uuid::Uuid;
 
 use crate::{
-    error::AppError, middleware::auth::GatewayKeyId, services::rate_limit, state::AppState,
+    db::gateway_keys::KeyLimits,
+    error::AppError,
+    middleware::auth::GatewayKeyId,
+    services::rate_limit::{
+        self, InFlightPermit, LimitState, RateLimitSnapshot, RateLimited, RateLimiter,
+    },
+    state::AppState,
 };
 
 pub async fn rate_limit_middleware(
@@ -15,18 +29,80 @@ pub async fn rate_limit_middleware(
     };
 
     let gateway_key_id = gateway_key_id.0;
-    let (rps, rpm) = match rate_limit::fetch_limits(&state.pool, gateway_key_id).await {
+    let limits = match rate_limit::fetch_limits(&state.pool, gateway_key_id).await {
         Ok(limits) => limits,
         Err(err) => return err.into_response(),
     };
 
-    let allowed = state
-        .rate_limiter
-        .check_and_consume(gateway_key_id, rps, rpm)
-        .await;
-    if !allowed {
-        return AppError::TooManyRequests.into_response();
-    }
+    let mut response: Response = match admit(&state.rate_limiter, gateway_key_id, &limits).await {
+        Ok(Some(permit)) => hold_until_body_ends(next.run(req).await, permit),
+        Ok(None) => next.run(req).await,
+        Err(limited) => {
+            state.metrics.record_rate_limit_rejection(limited.kind);
+            AppError::TooManyRequests(limited).into_response()
+        }
+    };
 
-    next.run(req).await
+    // Taken after the handler so token limits include this request's reservation.
+    let snapshot = state.rate_limiter.snapshot(gateway_key_id, &limits).await;
+    insert_rate_limit_headers(response.headers_mut(), &snapshot);
+    response
+}
+
+/// The concurrency limit is checked first so a request turned away there
+/// does not use up request tokens.
+async fn admit(
+    rate_limiter: &RateLimiter,
+    gateway_key_id: Uuid,
+    limits: &KeyLimits,
+) -> Result<Option<InFlightPermit>, RateLimited> {
+    let permit = rate_limiter
+        .acquire_in_flight(gateway_key_id, limits)
+        .await?;
+    rate_limiter
+        .check_and_consume(gateway_key_id, limits)
+        .await?;
+    Ok(permit)
+}
+
+/// Streamed responses outlive the handler, so the permit travels with the
+/// body and is released when it ends or the client disconnects.
+fn hold_until_body_ends(response: Response, permit: InFlightPermit) -> Response {
+    let (parts, body) = response.into_parts();
+    let mut data = body.into_data_stream();
+    let body_stream = async_stream::stream! {
+        let _permit = permit;
+        while let Some(chunk) = data.next().await {
+            yield chunk;
+        }
+    };
+    Response::from_parts(parts, Body::from_stream(body_stream))
+}
+
+/// Headers in the shape OpenAI sends, so SDKs can pace themselves.
+fn insert_rate_limit_headers(headers: &mut HeaderMap, snapshot: &RateLimitSnapshot) {
+    let limits = [("requests", snapshot.requests), ("tokens", snapshot.tokens)];
+    for (unit, state) in limits {
+        let Some(LimitState {
+            limit,
+            remaining,
+            reset,
+        }) = state
+        else {
+            continue;
+        };
+        let values = [
+            ("limit", HeaderValue::from(limit)),
+            ("remaining", HeaderValue::from(remaining)),
+            (
+                "reset",
+                HeaderValue::try_from(rate_limit::format_reset(reset)).expect("ascii duration"),
+            ),
+        ];
+        for (name, value) in values {
+            let name = HeaderName::try_from(format!("x-ratelimit-{name}-{unit}"))
+                .expect("valid header name");
+            headers.insert(name, value);
+        }
+    }
 }
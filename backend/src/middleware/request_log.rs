@@ -7,12 +7,15 @@ use axum::{
     response::IntoResponse,
 };
 
-use crate::state::AppState;
+use crate::{state::AppState, utils::request_body_hash::RequestBodyHash};
 
 #[derive(Clone, Debug)]
 pub struct ClientInfo {
     pub client_ip: Option<String>,
     pub user_agent: Option<String>,
+    /// Key the response may be cached under, decided by the response cache
+    /// middleware; `None` keeps it out of the cache.
+    pub request_body_hash: Option<RequestBodyHash>,
 }
 
 pub async fn request_log_middleware(
@@ -31,6 +34,7 @@ pub async fn request_log_middleware(
     req.extensions_mut().insert(ClientInfo {
         client_ip: client_ip.clone(),
         user_agent: user_agent.clone(),
+        request_body_hash: None,
     });
 
     next.run(req).await
@@ -1,9 +1,9 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use axum::{
     body::{self, Body},
     extract::State,
-    http::{Request, Response, StatusCode, header},
+    http::{HeaderValue, Request, Response, StatusCode, header},
     middleware::Next,
     response::IntoResponse,
 };
@@ -11,14 +11,21 @@ use serde_json::Value;
 use uuid::Uuid;
 
 use crate::{
-    constants::MAX_REQUEST_BODY_BYTES,
-    db::{cache_log::CacheLogContext, types::ApiType},
+    constants::{CACHE_LAYER_HEADER, CACHE_STATUS_HEADER, MAX_REQUEST_BODY_BYTES},
+    db::{
+        cache_log::CacheLogContext,
+        request_logs::CachedResponse,
+        types::{ApiType, CachePolicy},
+    },
     error::AppError,
     middleware::{auth::GatewayKeyId, request_log::ClientInfo},
     services::{
-        logging,
-        response_cache::{ResponseCacheKey, request_body_hash_hex},
+        cache_replay, logging,
+        response_cache::{
+            self, BypassReason, CacheDirectives, ResponseCacheKey, request_body_hash_hex,
+        },
         routing,
+        semantic_cache::{self, SemanticQuery},
     },
     state::AppState,
     utils::extract_model_from_payload,
@@ -28,6 +35,7 @@ use crate::{
 enum CacheLayer {
     Moka,
     Database,
+    Semantic,
 }
 
 impl CacheLayer {
@@ -35,6 +43,24 @@ impl CacheLayer {
         match self {
             Self::Moka => "moka",
             Self::Database => "database",
+            Self::Semantic => "semantic",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum CacheDecision {
+    Hit,
+    Miss,
+    Bypass,
+}
+
+impl CacheDecision {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Hit => "hit",
+            Self::Miss => "miss",
+            Self::Bypass => "bypass",
         }
     }
 }
@@ -61,8 +87,13 @@ pub async fn response_cache_middleware(
         return next.run(req).await;
     }
 
+    let directives = match CacheDirectives::from_headers(req.headers()) {
+        Ok(directives) => directives,
+        Err(err) => return err.into_response(),
+    };
+
     let start = Instant::now();
-    let (parts, body) = req.into_parts();
+    let (mut parts, body) = req.into_parts();
     let request_bytes = match body::to_bytes(body, MAX_REQUEST_BODY_BYTES).await {
         Ok(bytes) => bytes,
         Err(err) => {
@@ -71,65 +102,228 @@ pub async fn response_cache_middleware(
         }
     };
 
-    if request_bytes.is_empty() {
-        tracing::debug!("request body is empty, skipping response cache");
+    let Ok(payload) = serde_json::from_slice::<Value>(&request_bytes) else {
+        tracing::debug!("request body is not valid JSON, skipping response cache");
         let req = Request::from_parts(parts, Body::from(request_bytes));
         return next.run(req).await;
+    };
+
+    let plan = match response_cache::plan_request(
+        &state.pool,
+        state.response_cache.default_ttl(),
+        gateway_key_id.0,
+        api_type,
+        &payload,
+        &directives,
+    )
+    .await
+    {
+        Ok(plan) => plan,
+        Err(err) => {
+            tracing::error!(error = %err, "failed to load response cache settings");
+            let req = Request::from_parts(parts, Body::from(request_bytes));
+            return with_cache_miss(next.run(req).await);
+        }
+    };
+
+    let stream = payload.get("stream").and_then(Value::as_bool) == Some(true);
+    let model = extract_model_from_payload(&payload).ok();
+    let include_usage = payload
+        .pointer("/stream_options/include_usage")
+        .and_then(Value::as_bool)
+        == Some(true);
+    // Responses are stored under the lookup key, whichever target serves
+    // them, so later lookups find them.
+    let cache_key_body = match plan.store_policy {
+        CachePolicy::Disabled => None,
+        _ => Some(build_cache_key_body(&state, api_type, payload, &request_bytes).await),
+    };
+    let cache_key = cache_key_body
+        .as_deref()
+        .map(|body| ResponseCacheKey::new(body, plan.scope(gateway_key_id.0), stream));
+    if let Some(client_info) = parts.extensions.get_mut::<ClientInfo>() {
+        client_info.request_body_hash = cache_key.as_ref().map(|key| key.request_body_hash);
     }
 
-    let request_body = request_bytes.to_vec();
-    let cache_key_body = build_cache_key_body(&state, api_type, &request_body).await;
-    let cache_key = ResponseCacheKey::new(&cache_key_body);
+    let log = CacheLogArgs {
+        gateway_key_id: gateway_key_id.0,
+        decision: CacheDecision::Miss,
+        cache_layer: None,
+        source_request_log_id: None,
+        policy: plan.policy,
+        reason: None,
+        similarity: None,
+        latency_ms: 0,
+        client_info,
+    };
 
-    if let Some(cached) = state.response_cache.get(&cache_key) {
+    if let Some(reason) = plan.bypass {
+        tracing::debug!(
+            gateway_key_id = %gateway_key_id.0,
+            %api_type,
+            reason = reason.as_str(),
+            "bypassing response cache"
+        );
+        spawn_cache_log(
+            &state,
+            CacheLogArgs {
+                decision: CacheDecision::Bypass,
+                reason: Some(reason),
+                latency_ms: elapsed_ms(start),
+                ..log
+            },
+        );
+        let req = Request::from_parts(parts, Body::from(request_bytes));
+        return with_cache_miss(next.run(req).await);
+    }
+    // Only a bypass disables storing, so the key is always built here.
+    let (Some(cache_key_body), Some(cache_key)) = (cache_key_body, cache_key) else {
+        let req = Request::from_parts(parts, Body::from(request_bytes));
+        return with_cache_miss(next.run(req).await);
+    };
+
+    let synthesis =
+        state.response_cache.stream_synthesis() && cache_replay::supports_synthesis(api_type);
+    let in_stream_mode =
+        |cached: CachedResponse| cache_replay::synthesize(api_type, cached, stream, include_usage);
+
+    let cached = state
+        .response_cache
+        .get(&cache_key, plan.ttl)
+        .or_else(|| {
+            synthesis
+                .then(|| {
+                    state
+                        .response_cache
+                        .get(&cache_key.with_stream(!stream), plan.ttl)
+                })
+                .flatten()
+        })
+        .and_then(in_stream_mode);
+    state
+        .metrics
+        .record_cache_lookup(CacheLayer::Moka.as_str(), cached.is_some());
+    if let Some(cached) = cached {
         tracing::debug!(gateway_key_id = %gateway_key_id.0, %api_type, "response cache hit (moka)");
-        spawn_cache_hit_log(
+        spawn_cache_log(
             &state,
-            CacheHitLogArgs {
-                gateway_key_id: gateway_key_id.0,
-                cached: cached.clone(),
+            CacheLogArgs {
+                decision: CacheDecision::Hit,
+                cache_layer: Some(CacheLayer::Moka),
+                source_request_log_id: Some(cached.source_request_log_id),
                 latency_ms: elapsed_ms(start),
-                cache_layer: CacheLayer::Moka,
-                client_info: client_info.clone(),
+                ..log
             },
         );
-        return build_cached_response(cached);
+        return build_cached_response(&state, cached, CacheLayer::Moka);
     }
 
     let request_body_hash = request_body_hash_hex(cache_key.request_body_hash);
-    match logging::find_cached_response(&state.pool, &request_body_hash).await {
+    let cached = logging::find_cached_response(
+        &state.pool,
+        &request_body_hash,
+        plan.ttl,
+        state.response_cache.max_body_bytes(),
+        stream,
+        synthesis,
+    )
+    .await;
+    if let Ok(cached) = &cached {
+        state
+            .metrics
+            .record_cache_lookup(CacheLayer::Database.as_str(), cached.is_some());
+    }
+    let cached = cached.map(|cached| {
+        cached.and_then(|cached| {
+            state.response_cache.insert(
+                cache_key.with_stream(cached.stream),
+                cached.clone(),
+                plan.ttl,
+            );
+            in_stream_mode(cached)
+        })
+    });
+    match cached {
         Ok(Some(cached)) => {
             tracing::debug!(gateway_key_id = %gateway_key_id.0, %api_type, "response cache hit (database)");
-            state.response_cache.insert(cache_key, cached.clone());
-            spawn_cache_hit_log(
+            spawn_cache_log(
                 &state,
-                CacheHitLogArgs {
-                    gateway_key_id: gateway_key_id.0,
-                    cached: cached.clone(),
+                CacheLogArgs {
+                    decision: CacheDecision::Hit,
+                    cache_layer: Some(CacheLayer::Database),
+                    source_request_log_id: Some(cached.source_request_log_id),
                     latency_ms: elapsed_ms(start),
-                    cache_layer: CacheLayer::Database,
-                    client_info,
+                    ..log
                 },
             );
-            build_cached_response(cached)
+            build_cached_response(&state, cached, CacheLayer::Database)
         }
         Ok(None) => {
+            let semantic_query = prepare_semantic_query(
+                &state,
+                api_type,
+                model.as_deref(),
+                &cache_key_body,
+                plan.scope(gateway_key_id.0),
+                gateway_key_id,
+                log.client_info.clone(),
+            )
+            .await;
+            let semantic_hit = match &semantic_query {
+                Some(query) => find_semantic_hit(&state, query, plan.ttl, stream, synthesis).await,
+                None => None,
+            };
+            if let Some((cached, similarity)) = semantic_hit.and_then(|(cached, similarity)| {
+                in_stream_mode(cached).map(|cached| (cached, similarity))
+            }) {
+                tracing::debug!(
+                    gateway_key_id = %gateway_key_id.0,
+                    %api_type,
+                    similarity,
+                    "response cache hit (semantic)"
+                );
+                spawn_cache_log(
+                    &state,
+                    CacheLogArgs {
+                        decision: CacheDecision::Hit,
+                        cache_layer: Some(CacheLayer::Semantic),
+                        source_request_log_id: Some(cached.source_request_log_id),
+                        similarity: Some(similarity),
+                        latency_ms: elapsed_ms(start),
+                        ..log
+                    },
+                );
+                return build_cached_response(&state, cached, CacheLayer::Semantic);
+            }
+            if let Some(query) = semantic_query {
+                spawn_record_embedding(&state, query, request_body_hash);
+            }
+
             tracing::debug!(gateway_key_id = %gateway_key_id.0, %api_type, "response cache miss");
+            spawn_cache_log(
+                &state,
+                CacheLogArgs {
+                    latency_ms: elapsed_ms(start),
+                    ..log
+                },
+            );
             let req = Request::from_parts(parts, Body::from(request_bytes));
-            next.run(req).await
+            with_cache_miss(next.run(req).await)
         }
         Err(err) => {
             tracing::error!(error = %err, "failed to query response cache from database");
             let req = Request::from_parts(parts, Body::from(request_bytes));
-            next.run(req).await
+            with_cache_miss(next.run(req).await)
         }
     }
 }
 
-async fn build_cache_key_body(state: &AppState, api_type: ApiType, request_body: &[u8]) -> Vec<u8> {
-    let Ok(mut payload) = serde_json::from_slice::<Value>(request_body) else {
-        return request_body.to_vec();
-    };
+async fn build_cache_key_body(
+    state: &AppState,
+    api_type: ApiType,
+    mut payload: Value,
+    request_body: &[u8],
+) -> Vec<u8> {
     let Ok(model) = extract_model_from_payload(&payload) else {
         return request_body.to_vec();
     };
@@ -154,17 +348,128 @@ async fn build_cache_key_body(state: &AppState, api_type: ApiType, request_body:
     serde_json::to_vec(&payload).unwrap_or_else(|_| request_body.to_vec())
 }
 
-fn build_cached_response(cached: crate::db::request_logs::CachedResponse) -> Response<Body> {
+/// Embeds the request for the semantic cache when it is configured and the
+/// request's alias enables it. Failures only cost the semantic lookup.
+async fn prepare_semantic_query(
+    state: &AppState,
+    api_type: ApiType,
+    alias: Option<&str>,
+    cache_key_body: &[u8],
+    scope: Option<Uuid>,
+    gateway_key_id: GatewayKeyId,
+    client_info: Option<ClientInfo>,
+) -> Option<SemanticQuery> {
+    let embeddings_alias = state.response_cache.semantic_embeddings_alias()?;
+    let request = semantic_cache::semantic_request(api_type, cache_key_body, scope)?;
+    let client_info = ClientInfo {
+        request_body_hash: None,
+        ..client_info.unwrap_or(ClientInfo {
+            client_ip: None,
+            user_agent: None,
+            request_body_hash: None,
+        })
+    };
+
+    match semantic_cache::prepare_query(
+        &state.pool,
+        &state.openai,
+        embeddings_alias,
+        alias?,
+        request,
+        gateway_key_id,
+        client_info,
+    )
+    .await
+    {
+        Ok(query) => query,
+        Err(err) => {
+            tracing::warn!(error = %err, "failed to embed request for semantic cache");
+            None
+        }
+    }
+}
+
+async fn find_semantic_hit(
+    state: &AppState,
+    query: &SemanticQuery,
+    ttl: Duration,
+    stream: bool,
+    any_stream_mode: bool,
+) -> Option<(CachedResponse, f32)> {
+    let matched = match semantic_cache::find_match(&state.pool, query, ttl).await {
+        Ok(matched) => matched,
+        Err(err) => {
+            tracing::error!(error = %err, "failed to query semantic cache");
+            None
+        }
+    };
+    let cached = match &matched {
+        Some(matched) => logging::find_cached_response(
+            &state.pool,
+            &matched.request_body_hash,
+            ttl,
+            state.response_cache.max_body_bytes(),
+            stream,
+            any_stream_mode,
+        )
+        .await
+        .unwrap_or_else(|err| {
+            tracing::error!(error = %err, "failed to load semantic cache match");
+            None
+        }),
+        None => None,
+    };
+    state
+        .metrics
+        .record_cache_lookup(CacheLayer::Semantic.as_str(), cached.is_some());
+
+    Some((cached?, matched?.similarity))
+}
+
+fn spawn_record_embedding(state: &AppState, query: SemanticQuery, request_body_hash: String) {
+    let pool = state.pool.clone();
+    let shutdown_token = state.background_tasks.token();
+    state
+        .background_tasks
+        .spawn("semantic_cache.record_embedding", async move {
+            if shutdown_token.is_cancelled() {
+                return;
+            }
+            if let Err(err) = semantic_cache::record(&pool, &query, &request_body_hash).await {
+                tracing::error!(error = %err, "failed to record semantic cache embedding");
+            }
+        });
+}
+
+/// Cached streams are replayed event by event rather than in one chunk.
+fn build_cached_response(
+    state: &AppState,
+    cached: CachedResponse,
+    cache_layer: CacheLayer,
+) -> Response<Body> {
     let status = u16::try_from(cached.status_code)
         .ok()
         .and_then(|code| StatusCode::from_u16(code).ok())
         .unwrap_or(StatusCode::OK);
-    let mut builder = Response::builder().status(status);
+    let mut builder = Response::builder()
+        .status(status)
+        .header(CACHE_STATUS_HEADER, "hit")
+        .header(CACHE_LAYER_HEADER, cache_layer.as_str());
     if let Some(content_type) = cached.response_content_type {
         builder = builder.header(header::CONTENT_TYPE, content_type);
     }
 
-    match builder.body(Body::from(cached.response_body)) {
+    let body = if cached.stream {
+        cache_replay::replay_stream(
+            cached.response_body,
+            state.response_cache.stream_replay_interval(),
+            state.metrics.track_stream(),
+        )
+    } else {
+        Body::from(cached.response_body)
+    };
+
+    match builder.body(body) {
         Ok(response) => response,
         Err(err) => {
             tracing::error!(error = %err, "failed to build cached response");
@@ -176,27 +481,42 @@ fn build_cached_response(cached: crate::db::request_logs::CachedResponse) -> Res
     }
 }
 
-struct CacheHitLogArgs {
+fn with_cache_miss(mut response: Response<Body>) -> Response<Body> {
+    response
+        .headers_mut()
+        .insert(CACHE_STATUS_HEADER, HeaderValue::from_static("miss"));
+    response
+}
+
+struct CacheLogArgs {
     gateway_key_id: Uuid,
-    cached: crate::db::request_logs::CachedResponse,
+    decision: CacheDecision,
+    cache_layer: Option<CacheLayer>,
+    source_request_log_id: Option<i64>,
+    policy: CachePolicy,
+    reason: Option<BypassReason>,
+    similarity: Option<f32>,
     latency_ms: i32,
-    cache_layer: CacheLayer,
     client_info: Option<ClientInfo>,
 }
 
-fn spawn_cache_hit_log(state: &AppState, args: CacheHitLogArgs) {
-    let CacheHitLogArgs {
+fn spawn_cache_log(state: &AppState, args: CacheLogArgs) {
+    let CacheLogArgs {
         gateway_key_id,
-        cached,
-        latency_ms,
+        decision,
         cache_layer,
+        source_request_log_id,
+        policy,
+        reason,
+        similarity,
+        latency_ms,
         client_info,
     } = args;
     let pool = state.pool.clone();
     let shutdown_token = state.background_tasks.token();
     state
         .background_tasks
-        .spawn("cache_log.record_hit", async move {
+        .spawn("cache_log.record_event", async move {
             if shutdown_token.is_cancelled() {
                 return;
             }
@@ -204,9 +524,13 @@ fn spawn_cache_hit_log(state: &AppState, args: CacheHitLogArgs) {
 
             let cache_context = CacheLogContext {
                 request_id,
-                source_request_log_id: Some(cached.source_request_log_id),
+                source_request_log_id,
                 gateway_key_id: Some(gateway_key_id),
-                cache_layer: cache_layer.as_str(),
+                cache_layer: cache_layer.map(CacheLayer::as_str),
+                decision: decision.as_str(),
+                cache_policy: Some(policy),
+                reason: reason.map(BypassReason::as_str),
+                similarity,
                 latency_ms: Some(latency_ms),
                 client_ip: client_info.as_ref().and_then(|i| i.client_ip.clone()),
                 user_agent: client_info.as_ref().and_then(|i| i.user_agent.clone()),
@@ -2,7 +2,7 @@ use axum::{
     Router,
     extract::DefaultBodyLimit,
     middleware as axum_middleware,
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
 };
 use tower_http::trace::{
     DefaultMakeSpan, DefaultOnFailure, DefaultOnRequest, DefaultOnResponse, TraceLayer,
@@ -11,6 +11,13 @@ use tracing::Level;
 
 use crate::{handlers, middleware, state::AppState};
 
+/// Prometheus scrape endpoint, served on the main listener or on its own.
+pub fn metrics(state: AppState) -> Router {
+    Router::new()
+        .route("/metrics", get(handlers::metrics::metrics))
+        .with_state(state)
+}
+
 pub fn app(state: AppState) -> Router {
     let auth_routes = Router::new().route("/auth/login", post(handlers::auth::login));
 
@@ -24,6 +31,10 @@ pub fn app(state: AppState) -> Router {
             get(handlers::gateway_keys::get)
                 .put(handlers::gateway_keys::update)
                 .delete(handlers::gateway_keys::delete),
+        )
+        .route(
+            "/gateway-keys/{id}/budget",
+            put(handlers::gateway_keys::set_budget).delete(handlers::gateway_keys::delete_budget),
         );
 
     let provider_routes = Router::new()
@@ -56,6 +67,10 @@ pub fn app(state: AppState) -> Router {
         .route(
             "/providers/{id}/models",
             get(handlers::providers::list_provider_models),
+        )
+        .route(
+            "/providers/{id}/health",
+            get(handlers::providers::get_provider_health),
         );
 
     let alias_routes = Router::new()
@@ -93,6 +108,14 @@ pub fn app(state: AppState) -> Router {
             get(handlers::request_logs::get_request_log),
         );
 
+    let cache_routes = Router::new()
+        .route(
+            "/cache",
+            get(handlers::cache::list_cache_entries).delete(handlers::cache::purge_cache_entries),
+        )
+        .route("/cache/flush", post(handlers::cache::flush_cache))
+        .route("/cache/{hash}", delete(handlers::cache::purge_cache_entry));
+
     let user_routes = Router::new()
         .route(
             "/users",
@@ -109,7 +132,28 @@ pub fn app(state: AppState) -> Router {
             put(handlers::users::update_password),
         );
 
-    let stats_routes = Router::new().route("/stats", get(handlers::stats::get_dashboard_stats));
+    let pricing_routes = Router::new()
+        .route(
+            "/pricing",
+            get(handlers::pricing::list).post(handlers::pricing::create),
+        )
+        .route(
+            "/pricing/{id}",
+            get(handlers::pricing::get)
+                .put(handlers::pricing::update)
+                .delete(handlers::pricing::delete),
+        );
+
+    let stats_routes = Router::new()
+        .route("/stats", get(handlers::stats::get_dashboard_stats))
+        .route(
+            "/stats/performance",
+            get(handlers::stats::get_performance_stats),
+        );
+
+    let health_routes = Router::new()
+        .route("/healthz", get(handlers::health::healthz))
+        .route("/readyz", get(handlers::health::readyz));
 
     let ai_routes = Router::new()
         .route(
@@ -119,10 +163,20 @@ pub fn app(state: AppState) -> Router {
         .route("/v1/embeddings", post(handlers::openai::embeddings))
         .route("/v1/responses", post(handlers::openai::responses))
         .route("/v1/messages", post(handlers::openai::anthropic_messages))
+        .route(
+            "/v1beta/models/{*target}",
+            post(handlers::openai::gemini_generate_content),
+        )
+        .route("/v1/models", get(handlers::openai::list_models))
+        .route("/v1/models/{*id}", get(handlers::openai::get_model))
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
             middleware::response_cache::response_cache_middleware,
         ))
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            middleware::budget::budget_middleware,
+        ))
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
             middleware::rate_limit::rate_limit_middleware,
@@ -141,7 +195,9 @@ pub fn app(state: AppState) -> Router {
                     .merge(provider_routes)
                     .merge(alias_routes)
                     .merge(request_log_routes)
+                    .merge(cache_routes)
                     .merge(user_routes)
+                    .merge(pricing_routes)
                     .merge(stats_routes)
                     .layer(axum_middleware::from_fn_with_state(
                         state.clone(),
@@ -150,6 +206,7 @@ pub fn app(state: AppState) -> Router {
             ),
         )
         .merge(ai_routes)
+        .merge(health_routes)
         .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
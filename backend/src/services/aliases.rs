@@ -4,9 +4,11 @@ use crate::db::{
     alias_targets::{
         self, AliasTarget, AliasTargetDetail, CreateAliasTargetParams, UpdateAliasTargetParams,
     },
-    aliases::{self, Alias, CreateAliasParams, UpdateAliasParams},
+    aliases::{self, Alias, CacheSettingsUpdate, CreateAliasParams, UpdateAliasParams},
+    types::{CacheSettings, LbStrategy},
 };
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
+use crate::services::response_cache;
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -14,8 +16,25 @@ pub async fn list_aliases(pool: &PgPool, page: i64, page_size: i64) -> AppResult
     aliases::list_aliases(pool, page, page_size).await
 }
 
-pub async fn create_alias(pool: &PgPool, name: String) -> AppResult<Alias> {
-    aliases::create_alias(pool, CreateAliasParams { name }).await
+pub async fn create_alias(
+    pool: &PgPool,
+    name: String,
+    strategy: Option<LbStrategy>,
+    cache: CacheSettings,
+    semantic_cache_threshold: Option<f32>,
+) -> AppResult<Alias> {
+    response_cache::validate_settings(&cache)?;
+    response_cache::validate_semantic_threshold(semantic_cache_threshold)?;
+    aliases::create_alias(
+        pool,
+        CreateAliasParams {
+            name,
+            strategy,
+            cache,
+            semantic_cache_threshold,
+        },
+    )
+    .await
 }
 
 pub async fn get_alias(pool: &PgPool, id: Uuid) -> AppResult<Option<Alias>> {
@@ -27,8 +46,22 @@ pub async fn update_alias(
     id: Uuid,
     name: Option<String>,
     enabled: Option<bool>,
+    strategy: Option<LbStrategy>,
+    cache: CacheSettingsUpdate,
 ) -> AppResult<Option<Alias>> {
-    aliases::update_alias(pool, id, UpdateAliasParams { name, enabled }).await
+    response_cache::validate_ttl(cache.cache_ttl_secs.flatten())?;
+    response_cache::validate_semantic_threshold(cache.semantic_cache_threshold.flatten())?;
+    aliases::update_alias(
+        pool,
+        id,
+        UpdateAliasParams {
+            name,
+            enabled,
+            strategy,
+            cache,
+        },
+    )
+    .await
 }
 
 pub async fn delete_alias(pool: &PgPool, id: Uuid) -> AppResult<bool> {
@@ -50,7 +83,10 @@ pub async fn create_alias_target(
     provider_id: Uuid,
     model_id: String,
     extra_fields: Option<Value>,
+    weight: Option<i32>,
+    priority: Option<i32>,
 ) -> AppResult<AliasTarget> {
+    validate_weight(weight)?;
     alias_targets::create_alias_target(
         pool,
         CreateAliasTargetParams {
@@ -58,11 +94,14 @@ pub async fn create_alias_target(
             provider_id,
             model_id,
             extra_fields,
+            weight,
+            priority,
         },
     )
     .await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn update_alias_target(
     pool: &PgPool,
     id: Uuid,
@@ -70,7 +109,10 @@ pub async fn update_alias_target(
     model_id: Option<String>,
     enabled: Option<bool>,
     extra_fields: Option<Value>,
+    weight: Option<i32>,
+    priority: Option<i32>,
 ) -> AppResult<Option<AliasTarget>> {
+    validate_weight(weight)?;
     alias_targets::update_alias_target(
         pool,
         id,
@@ -79,6 +121,8 @@ pub async fn update_alias_target(
             model_id,
             enabled,
             extra_fields,
+            weight,
+            priority,
         },
     )
     .await
@@ -87,3 +131,12 @@ pub async fn update_alias_target(
 pub async fn delete_alias_target(pool: &PgPool, id: Uuid) -> AppResult<bool> {
     alias_targets::delete_alias_target(pool, id).await
 }
+
+fn validate_weight(weight: Option<i32>) -> AppResult<()> {
+    match weight {
+        Some(weight) if weight <= 0 => Err(AppError::BadRequest(
+            "weight must be greater than 0".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -1,17 +1,23 @@
-use axum::http::{HeaderMap, header};
+use axum::{
+    extract::Query,
+    http::{HeaderMap, Uri, header},
+};
 use dashmap::DashMap;
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::{
+    collections::HashMap,
     sync::Arc,
     time::{Duration, Instant},
 };
 use uuid::Uuid;
 
+use crate::constants::{LOGIN_FAILURE_WINDOW_SECS, LOGIN_MAX_FAILURES};
 use crate::db::{
     gateway_key_models,
     gateway_keys::{self, GatewayKey},
+    login_protection,
 };
 use crate::error::{AppError, AppResult};
 
@@ -21,49 +27,101 @@ struct IpRecord {
     banned: bool,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
+enum LoginStore {
+    Memory(Arc<DashMap<String, IpRecord>>),
+    /// Shared by every replica using the database.
+    Postgres(PgPool),
+}
+
+/// Bans addresses after repeated failed logins. Lookups against the shared
+/// store fail open, logging the error.
+#[derive(Debug, Clone)]
 pub struct LoginProtection {
-    records: Arc<DashMap<String, IpRecord>>,
+    store: LoginStore,
+}
+
+impl Default for LoginProtection {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LoginProtection {
     pub fn new() -> Self {
         Self {
-            records: Arc::new(DashMap::new()),
+            store: LoginStore::Memory(Arc::new(DashMap::new())),
+        }
+    }
+
+    pub fn postgres(pool: PgPool) -> Self {
+        Self {
+            store: LoginStore::Postgres(pool),
         }
     }
 
     pub async fn is_banned(&self, ip: &str) -> bool {
-        self.records
-            .get(ip)
-            .map(|record| record.banned)
-            .unwrap_or(false)
+        match &self.store {
+            LoginStore::Memory(records) => {
+                records.get(ip).map(|record| record.banned).unwrap_or(false)
+            }
+            LoginStore::Postgres(pool) => login_protection::is_banned(pool, ip)
+                .await
+                .unwrap_or_else(|err| {
+                    tracing::error!(error = %err, "failed to check shared login ban");
+                    false
+                }),
+        }
     }
 
     pub async fn record_failure(&self, ip: &str) {
-        let mut record = self.records.entry(ip.to_string()).or_insert(IpRecord {
-            failures: Vec::new(),
-            banned: false,
-        });
-
-        if record.banned {
-            return;
+        match &self.store {
+            LoginStore::Memory(records) => record_memory_failure(records, ip),
+            LoginStore::Postgres(pool) => {
+                if let Err(err) = record_shared_failure(pool, ip).await {
+                    tracing::error!(error = %err, "failed to record shared login failure");
+                }
+            }
         }
+    }
+}
 
-        let now = Instant::now();
-        record.failures.push(now);
+fn record_memory_failure(records: &DashMap<String, IpRecord>, ip: &str) {
+    let mut record = records.entry(ip.to_string()).or_insert(IpRecord {
+        failures: Vec::new(),
+        banned: false,
+    });
 
-        // Remove old failures (> 1 minute)
-        record
-            .failures
-            .retain(|&t| now.duration_since(t) <= Duration::from_secs(60));
+    if record.banned {
+        return;
+    }
 
-        if record.failures.len() > 5 {
-            record.banned = true;
-        }
+    let now = Instant::now();
+    record.failures.push(now);
+
+    // Remove old failures (> 1 minute)
+    record
+        .failures
+        .retain(|&t| now.duration_since(t) <= Duration::from_secs(LOGIN_FAILURE_WINDOW_SECS));
+
+    if record.failures.len() > LOGIN_MAX_FAILURES {
+        record.banned = true;
     }
 }
 
+async fn record_shared_failure(pool: &PgPool, ip: &str) -> AppResult<()> {
+    if login_protection::is_banned(pool, ip).await? {
+        return Ok(());
+    }
+
+    let failures =
+        login_protection::record_failure(pool, ip, LOGIN_FAILURE_WINDOW_SECS as f64).await?;
+    if failures > LOGIN_MAX_FAILURES as i64 {
+        login_protection::ban(pool, ip).await?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
@@ -102,13 +160,23 @@ pub fn extract_api_key(headers: &HeaderMap) -> Option<String> {
         .and_then(|value| value.strip_prefix("Bearer "))
         .map(str::to_string)
         .or_else(|| {
-            headers
-                .get("x-api-key")
-                .and_then(|value| value.to_str().ok())
-                .map(str::to_string)
+            ["x-api-key", "x-goog-api-key"]
+                .into_iter()
+                .find_map(|name| {
+                    headers
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                })
         })
 }
 
+/// Gemini clients may pass the key as a `key` query parameter instead.
+pub fn extract_query_api_key(uri: &Uri) -> Option<String> {
+    let Query(mut params) = Query::<HashMap<String, String>>::try_from_uri(uri).ok()?;
+    params.remove("key")
+}
+
 pub async fn fetch_gateway_key(pool: &PgPool, api_key: &str) -> AppResult<Option<GatewayKey>> {
     let key = gateway_keys::fetch_gateway_key(pool, api_key).await?;
 
@@ -6,13 +6,16 @@ use tokio_util::{sync::CancellationToken, task::TaskTracker};
 pub struct BackgroundTasks {
     tracker: TaskTracker,
     shutdown_token: CancellationToken,
+    drain_token: CancellationToken,
 }
 
 impl BackgroundTasks {
     pub fn new() -> Self {
+        let shutdown_token = CancellationToken::new();
         Self {
             tracker: TaskTracker::new(),
-            shutdown_token: CancellationToken::new(),
+            drain_token: shutdown_token.child_token(),
+            shutdown_token,
         }
     }
 
@@ -39,6 +42,17 @@ impl BackgroundTasks {
         self.shutdown_token.clone()
     }
 
+    /// True once a shutdown signal has been received, including the drain
+    /// period before new connections are refused.
+    pub fn is_draining(&self) -> bool {
+        self.drain_token.is_cancelled()
+    }
+
+    /// Reports the instance as unready while it keeps serving.
+    pub fn begin_drain(&self) {
+        self.drain_token.cancel();
+    }
+
     pub fn begin_shutdown(&self) {
         self.shutdown_token.cancel();
         self.tracker.close();
@@ -0,0 +1,343 @@
+//! Spend budgets per gateway key. Spend is not tracked separately: it is
+//! the key's token usage in `request_logs` since the start of the budget
+//! period, priced with the per-model prices stored with the budget or, for
+//! models the budget does not price, the cost logged from the pricing
+//! catalog.
+
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime, Time, UtcOffset};
+use uuid::Uuid;
+
+use crate::{
+    constants::BUDGET_SPEND_REFRESH_SECS,
+    db::{
+        budgets::{self, BudgetRow, ModelUsage},
+        gateway_keys,
+        types::BudgetPeriod,
+    },
+    error::{AppError, AppResult},
+};
+
+/// Prices in the budget's currency per million tokens.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Budget {
+    pub hard_limit: Option<f64>,
+    pub soft_limit: Option<f64>,
+    pub period: BudgetPeriod,
+    /// Keyed by upstream model or alias name; the model wins when both are
+    /// priced. Other models are charged their logged cost.
+    #[serde(default)]
+    pub model_prices: HashMap<String, ModelPrice>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStatus {
+    #[serde(flatten)]
+    pub budget: Budget,
+    pub spend: f64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub period_start: OffsetDateTime,
+}
+
+impl BudgetStatus {
+    pub fn hard_limit_reached(&self) -> bool {
+        self.budget
+            .hard_limit
+            .is_some_and(|limit| self.spend >= limit)
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        self.budget
+            .soft_limit
+            .is_some_and(|limit| self.spend >= limit)
+    }
+}
+
+impl TryFrom<BudgetRow> for Budget {
+    type Error = AppError;
+
+    fn try_from(row: BudgetRow) -> AppResult<Self> {
+        let model_prices = serde_json::from_value(row.model_prices)
+            .map_err(|err| AppError::Internal(err.into()))?;
+        Ok(Self {
+            hard_limit: row.hard_limit,
+            soft_limit: row.soft_limit,
+            period: row.period,
+            model_prices,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct SpendEntry {
+    period_start: OffsetDateTime,
+    loaded_at: Instant,
+    usage: Vec<ModelUsage>,
+}
+
+/// Usage of keys with a budget in the current period. It is summed from
+/// `request_logs` at most every `BUDGET_SPEND_REFRESH_SECS` and kept current
+/// in between with the usage of requests as they settle, before their logs
+/// are written; the refresh picks up usage seen by other replicas.
+#[derive(Debug, Clone, Default)]
+pub struct BudgetSpend {
+    entries: Arc<DashMap<Uuid, SpendEntry>>,
+}
+
+impl BudgetSpend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn usage(
+        &self,
+        pool: &PgPool,
+        gateway_key_id: Uuid,
+        period_start: OffsetDateTime,
+    ) -> AppResult<Vec<ModelUsage>> {
+        if let Some(entry) = self.entries.get(&gateway_key_id)
+            && entry.period_start == period_start
+            && entry.loaded_at.elapsed().as_secs() < BUDGET_SPEND_REFRESH_SECS
+        {
+            return Ok(entry.usage.clone());
+        }
+
+        let usage = budgets::usage_since(pool, gateway_key_id, period_start).await?;
+        self.entries.insert(
+            gateway_key_id,
+            SpendEntry {
+                period_start,
+                loaded_at: Instant::now(),
+                usage: usage.clone(),
+            },
+        );
+        Ok(usage)
+    }
+
+    /// Adds the usage of a settled request. Keys without a loaded entry are
+    /// skipped; their next lookup reads the written log.
+    pub fn record(&self, gateway_key_id: Uuid, usage: ModelUsage) {
+        let Some(mut entry) = self.entries.get_mut(&gateway_key_id) else {
+            return;
+        };
+        match entry
+            .usage
+            .iter_mut()
+            .find(|known| known.model == usage.model && known.alias == usage.alias)
+        {
+            Some(known) => {
+                known.prompt_tokens += usage.prompt_tokens;
+                known.completion_tokens += usage.completion_tokens;
+                known.cost += usage.cost;
+            }
+            None => entry.usage.push(usage),
+        }
+    }
+}
+
+/// Current spend of a key against its budget, if it has one.
+pub async fn fetch_budget_status(
+    pool: &PgPool,
+    spend: &BudgetSpend,
+    gateway_key_id: Uuid,
+) -> AppResult<Option<BudgetStatus>> {
+    let Some(row) = budgets::fetch_budget(pool, gateway_key_id).await? else {
+        return Ok(None);
+    };
+    let budget = Budget::try_from(row)?;
+    budget_status(pool, spend, gateway_key_id, budget)
+        .await
+        .map(Some)
+}
+
+pub async fn set_budget(
+    pool: &PgPool,
+    spend: &BudgetSpend,
+    gateway_key_id: Uuid,
+    budget: Budget,
+) -> AppResult<BudgetStatus> {
+    validate_budget(&budget)?;
+    if gateway_keys::fetch_gateway_key_by_id(pool, gateway_key_id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    let row = BudgetRow {
+        hard_limit: budget.hard_limit,
+        soft_limit: budget.soft_limit,
+        period: budget.period,
+        model_prices: serde_json::to_value(&budget.model_prices)
+            .map_err(|err| AppError::Internal(err.into()))?,
+    };
+    let budget = Budget::try_from(budgets::upsert_budget(pool, gateway_key_id, &row).await?)?;
+    budget_status(pool, spend, gateway_key_id, budget).await
+}
+
+pub async fn delete_budget(pool: &PgPool, gateway_key_id: Uuid) -> AppResult<bool> {
+    budgets::delete_budget(pool, gateway_key_id).await
+}
+
+async fn budget_status(
+    pool: &PgPool,
+    budget_spend: &BudgetSpend,
+    gateway_key_id: Uuid,
+    budget: Budget,
+) -> AppResult<BudgetStatus> {
+    let period_start = period_start(budget.period, OffsetDateTime::now_utc());
+    let usage = budget_spend
+        .usage(pool, gateway_key_id, period_start)
+        .await?;
+    let spend = spend(&budget.model_prices, &usage);
+    Ok(BudgetStatus {
+        budget,
+        spend,
+        period_start,
+    })
+}
+
+fn validate_budget(budget: &Budget) -> AppResult<()> {
+    let amounts = [budget.hard_limit, budget.soft_limit]
+        .into_iter()
+        .flatten()
+        .chain(
+            budget
+                .model_prices
+                .values()
+                .flat_map(|price| [price.prompt_per_million, price.completion_per_million]),
+        );
+    for amount in amounts {
+        if !amount.is_finite() || amount < 0.0 {
+            return Err(AppError::BadRequest(
+                "budget limits and prices must be non-negative numbers".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Budget periods start at midnight UTC; weeks start on Monday.
+fn period_start(period: BudgetPeriod, now: OffsetDateTime) -> OffsetDateTime {
+    let today = now.to_offset(UtcOffset::UTC).replace_time(Time::MIDNIGHT);
+    match period {
+        BudgetPeriod::Daily => today,
+        BudgetPeriod::Weekly => {
+            today - Duration::days(i64::from(today.weekday().number_days_from_monday()))
+        }
+        BudgetPeriod::Monthly => today - Duration::days(i64::from(today.day() - 1)),
+    }
+}
+
+fn spend(model_prices: &HashMap<String, ModelPrice>, usage: &[ModelUsage]) -> f64 {
+    usage
+        .iter()
+        .map(|usage| {
+            let price = [&usage.model, &usage.alias]
+                .into_iter()
+                .flatten()
+                .find_map(|name| model_prices.get(name));
+            match price {
+                Some(price) => {
+                    (usage.prompt_tokens as f64 * price.prompt_per_million
+                        + usage.completion_tokens as f64 * price.completion_per_million)
+                        / 1_000_000.0
+                }
+                None => usage.cost,
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::format_description::well_known::Rfc3339;
+
+    use super::*;
+
+    fn datetime(value: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(value, &Rfc3339).unwrap()
+    }
+
+    #[test]
+    fn spend_prices_usage_since_period_start() {
+        let now = datetime("2026-03-12T15:30:00Z");
+        assert_eq!(
+            period_start(BudgetPeriod::Daily, now),
+            datetime("2026-03-12T00:00:00Z")
+        );
+        assert_eq!(
+            period_start(BudgetPeriod::Weekly, now),
+            datetime("2026-03-09T00:00:00Z")
+        );
+        assert_eq!(
+            period_start(BudgetPeriod::Monthly, now),
+            datetime("2026-03-01T00:00:00Z")
+        );
+
+        let prices = HashMap::from([(
+            "fast".to_string(),
+            ModelPrice {
+                prompt_per_million: 1.0,
+                completion_per_million: 4.0,
+            },
+        )]);
+        let usage = [
+            ModelUsage {
+                model: Some("gpt-4o-mini".to_string()),
+                alias: Some("fast".to_string()),
+                prompt_tokens: 2_000_000,
+                completion_tokens: 500_000,
+                cost: 9.0,
+            },
+            ModelUsage {
+                model: Some("catalog".to_string()),
+                alias: None,
+                prompt_tokens: 1_000_000,
+                completion_tokens: 0,
+                cost: 0.5,
+            },
+        ];
+        assert_eq!(spend(&prices, &usage), 4.5);
+    }
+
+    #[test]
+    fn budget_spend_adds_settled_usage_to_loaded_keys() {
+        let key = Uuid::nil();
+        let usage = |model: &str, prompt_tokens, cost| ModelUsage {
+            model: Some(model.to_string()),
+            alias: None,
+            prompt_tokens,
+            completion_tokens: 0,
+            cost,
+        };
+        let budget_spend = BudgetSpend::new();
+        budget_spend.record(key, usage("a", 10, 1.0));
+        assert!(budget_spend.entries.is_empty());
+
+        budget_spend.entries.insert(
+            key,
+            SpendEntry {
+                period_start: datetime("2026-03-12T00:00:00Z"),
+                loaded_at: Instant::now(),
+                usage: vec![usage("a", 10, 1.0)],
+            },
+        );
+        budget_spend.record(key, usage("a", 5, 0.5));
+        budget_spend.record(key, usage("b", 1, 0.25));
+
+        let entry = budget_spend.entries.get(&key).unwrap();
+        assert_eq!(spend(&HashMap::new(), &entry.usage), 1.75);
+        assert_eq!(entry.usage[0].prompt_tokens, 15);
+    }
+}
@@ -0,0 +1,128 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::db::cache_entries::{self, CacheEntry, CacheEntryFilter};
+use crate::error::{AppError, AppResult};
+use crate::services::response_cache::{
+    ResponseCache, parse_request_body_hash_hex, request_body_hash_hex,
+};
+
+/// Which cache entries to purge; at least one field must be set.
+#[derive(Debug, Default)]
+pub struct PurgeFilter {
+    pub request_body_hash: Option<String>,
+    pub alias: Option<String>,
+    pub gateway_key_id: Option<Uuid>,
+    pub older_than_secs: Option<i64>,
+}
+
+impl PurgeFilter {
+    fn into_entry_filter(self, now: OffsetDateTime) -> AppResult<CacheEntryFilter> {
+        if self.request_body_hash.is_none()
+            && self.alias.is_none()
+            && self.gateway_key_id.is_none()
+            && self.older_than_secs.is_none()
+        {
+            return Err(AppError::BadRequest(
+                "Specify a hash, alias, gateway_key_id or older_than_secs to purge".to_string(),
+            ));
+        }
+        let request_body_hash = self
+            .request_body_hash
+            .as_deref()
+            .map(normalize_hash)
+            .transpose()?;
+        let created_before = match self.older_than_secs {
+            Some(secs) if secs < 0 => {
+                return Err(AppError::BadRequest(
+                    "older_than_secs must not be negative".to_string(),
+                ));
+            }
+            Some(secs) => Some(now - time::Duration::seconds(secs)),
+            None => None,
+        };
+        Ok(CacheEntryFilter {
+            request_body_hash,
+            alias: self.alias,
+            gateway_key_id: self.gateway_key_id,
+            created_before,
+        })
+    }
+}
+
+/// Lowercase hex form of a request body hash given by a client.
+fn normalize_hash(hash: &str) -> AppResult<String> {
+    parse_request_body_hash_hex(hash)
+        .map(request_body_hash_hex)
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid cache entry hash: {hash}")))
+}
+
+pub async fn list_entries(
+    pool: &PgPool,
+    alias: Option<String>,
+    gateway_key_id: Option<Uuid>,
+    limit: i64,
+    offset: i64,
+) -> AppResult<(Vec<CacheEntry>, i64)> {
+    let filter = CacheEntryFilter {
+        alias,
+        gateway_key_id,
+        ..Default::default()
+    };
+    let entries = cache_entries::list_entries(pool, &filter, limit, offset).await?;
+    let total = cache_entries::count_entries(pool, &filter).await?;
+    Ok((entries, total))
+}
+
+/// Removes matching entries from the stored and in-memory cache layers and
+/// returns how many distinct requests were purged.
+pub async fn purge(pool: &PgPool, cache: &ResponseCache, filter: PurgeFilter) -> AppResult<usize> {
+    let filter = filter.into_entry_filter(OffsetDateTime::now_utc())?;
+    let hashes = cache_entries::purge_entries(pool, &filter).await?;
+    for hash in hashes
+        .iter()
+        .filter_map(|hash| parse_request_body_hash_hex(hash))
+    {
+        cache.invalidate(hash);
+    }
+    Ok(hashes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purge_filter_requires_a_valid_selection() {
+        let now = OffsetDateTime::UNIX_EPOCH + time::Duration::hours(1);
+
+        assert!(PurgeFilter::default().into_entry_filter(now).is_err());
+        assert!(
+            PurgeFilter {
+                request_body_hash: Some("not-a-hash".to_string()),
+                ..Default::default()
+            }
+            .into_entry_filter(now)
+            .is_err()
+        );
+
+        let filter = PurgeFilter {
+            request_body_hash: Some("00112233445566778899AABBCCDDEEFF".to_string()),
+            older_than_secs: Some(60),
+            ..Default::default()
+        }
+        .into_entry_filter(now)
+        .ok();
+        assert_eq!(
+            filter
+                .as_ref()
+                .and_then(|filter| filter.request_body_hash.as_deref()),
+            Some("00112233445566778899aabbccddeeff")
+        );
+        assert_eq!(
+            filter.and_then(|filter| filter.created_before),
+            Some(now - time::Duration::seconds(60))
+        );
+    }
+}
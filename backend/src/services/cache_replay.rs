@@ -0,0 +1,555 @@
+//! Serves cached responses in the stream mode the client asked for: captured
+//! streams are replayed event by event, and for chat completions and
+//! Anthropic messages a buffered response can be turned into a stream and
+//! the other way round.
+
+use std::{collections::BTreeMap, convert::Infallible, time::Duration};
+
+use axum::body::{Body, Bytes};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    db::{request_logs::CachedResponse, types::ApiType},
+    services::metrics::StreamGuard,
+    utils::sse::{SseParser, format_event, split_events},
+};
+
+const SSE_CONTENT_TYPE: &str = "text/event-stream";
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Whether cached responses of this API type can change stream mode.
+pub fn supports_synthesis(api_type: ApiType) -> bool {
+    matches!(
+        api_type,
+        ApiType::OpenAiChatCompletions | ApiType::AnthropicMessages
+    )
+}
+
+/// Rewrites a cached response into the requested stream mode. Returns `None`
+/// when the API type is unsupported or the cached body cannot be parsed.
+pub fn synthesize(
+    api_type: ApiType,
+    cached: CachedResponse,
+    stream: bool,
+    include_usage: bool,
+) -> Option<CachedResponse> {
+    if cached.stream == stream {
+        return Some(cached);
+    }
+    let body = match (api_type, stream) {
+        (ApiType::OpenAiChatCompletions, true) => {
+            chat_stream_from_completion(&cached.response_body, include_usage)?
+        }
+        (ApiType::OpenAiChatCompletions, false) => {
+            chat_completion_from_stream(&cached.response_body)?
+        }
+        (ApiType::AnthropicMessages, true) => messages_stream_from_message(&cached.response_body)?,
+        (ApiType::AnthropicMessages, false) => message_from_messages_stream(&cached.response_body)?,
+        _ => return None,
+    };
+    let content_type = if stream {
+        SSE_CONTENT_TYPE
+    } else {
+        JSON_CONTENT_TYPE
+    };
+
+    Some(CachedResponse {
+        response_body: body,
+        response_content_type: Some(content_type.to_string()),
+        stream,
+        ..cached
+    })
+}
+
+/// Replays a captured SSE body one event at a time, `interval` apart.
+pub fn replay_stream(body: Vec<u8>, interval: Duration, stream_guard: StreamGuard) -> Body {
+    let events: Vec<Bytes> = split_events(&body)
+        .into_iter()
+        .map(Bytes::copy_from_slice)
+        .collect();
+    let body_stream = async_stream::stream! {
+        let _stream_guard = stream_guard;
+        for (index, event) in events.into_iter().enumerate() {
+            if index > 0 && !interval.is_zero() {
+                tokio::time::sleep(interval).await;
+            }
+            yield Ok::<_, Infallible>(event);
+        }
+    };
+    Body::from_stream(body_stream)
+}
+
+/// JSON payloads of the events in a captured stream; `[DONE]` is skipped.
+fn parse_events(body: &[u8]) -> Vec<Value> {
+    let mut parser = SseParser::new();
+    let mut events = parser.push(body);
+    events.extend(parser.finish());
+    events
+        .into_iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .collect()
+}
+
+fn push_data(out: &mut String, data: &Value) {
+    out.push_str(&format_event(None, &data.to_string()));
+}
+
+fn push_event(out: &mut String, event: &str, data: Value) {
+    out.push_str(&format_event(Some(event), &data.to_string()));
+}
+
+fn chat_stream_from_completion(body: &[u8], include_usage: bool) -> Option<Vec<u8>> {
+    let completion = serde_json::from_slice::<Value>(body).ok()?;
+    let choices = completion.get("choices")?.as_array()?;
+
+    let mut base = Map::new();
+    for field in ["id", "created", "model", "system_fingerprint"] {
+        if let Some(value) = completion.get(field) {
+            base.insert(field.to_string(), value.clone());
+        }
+    }
+    base.insert(
+        "object".to_string(),
+        Value::String("chat.completion.chunk".to_string()),
+    );
+    let chunk = |choices: Value| {
+        let mut chunk = base.clone();
+        chunk.insert("choices".to_string(), choices);
+        Value::Object(chunk)
+    };
+
+    let mut out = String::new();
+    for (position, choice) in choices.iter().enumerate() {
+        let index = choice.get("index").cloned().unwrap_or(json!(position));
+        let message = choice.get("message").unwrap_or(&Value::Null);
+        let role = message.get("role").cloned().unwrap_or(json!("assistant"));
+        push_data(
+            &mut out,
+            &chunk(json!([{
+                "index": index,
+                "delta": { "role": role, "content": "" },
+                "finish_reason": null,
+            }])),
+        );
+
+        if let Some(content) = message.get("content").and_then(Value::as_str)
+            && !content.is_empty()
+        {
+            push_data(
+                &mut out,
+                &chunk(json!([{
+                    "index": index,
+                    "delta": { "content": content },
+                    "finish_reason": null,
+                }])),
+            );
+        }
+        if let Some(Value::Array(tool_calls)) = message.get("tool_calls") {
+            let tool_calls: Vec<Value> = tool_calls
+                .iter()
+                .enumerate()
+                .map(|(call_index, tool_call)| {
+                    let mut tool_call = tool_call.clone();
+                    tool_call["index"] = json!(call_index);
+                    tool_call
+                })
+                .collect();
+            push_data(
+                &mut out,
+                &chunk(json!([{
+                    "index": index,
+                    "delta": { "tool_calls": tool_calls },
+                    "finish_reason": null,
+                }])),
+            );
+        }
+
+        push_data(
+            &mut out,
+            &chunk(json!([{
+                "index": index,
+                "delta": {},
+                "finish_reason": choice.get("finish_reason").cloned().unwrap_or(json!("stop")),
+            }])),
+        );
+    }
+
+    if include_usage && let Some(usage) = completion.get("usage") {
+        let mut usage_chunk = chunk(json!([]));
+        usage_chunk["usage"] = usage.clone();
+        push_data(&mut out, &usage_chunk);
+    }
+    out.push_str(&format_event(None, "[DONE]"));
+    Some(out.into_bytes())
+}
+
+#[derive(Default)]
+struct ChatChoice {
+    role: Option<Value>,
+    content: String,
+    tool_calls: BTreeMap<u64, Value>,
+    finish_reason: Option<Value>,
+}
+
+fn chat_completion_from_stream(body: &[u8]) -> Option<Vec<u8>> {
+    let mut first = None;
+    let mut usage = None;
+    let mut choices: BTreeMap<u64, ChatChoice> = BTreeMap::new();
+
+    for chunk in parse_events(body) {
+        if let Some(chunk_usage) = chunk.get("usage").filter(|usage| usage.is_object()) {
+            usage = Some(chunk_usage.clone());
+        }
+        for choice in chunk
+            .get("choices")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let index = choice.get("index").and_then(Value::as_u64).unwrap_or(0);
+            let entry = choices.entry(index).or_default();
+            let delta = choice.get("delta").unwrap_or(&Value::Null);
+            if let Some(role) = delta.get("role") {
+                entry.role = Some(role.clone());
+            }
+            if let Some(content) = delta.get("content").and_then(Value::as_str) {
+                entry.content.push_str(content);
+            }
+            for tool_call in delta
+                .get("tool_calls")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                merge_tool_call_delta(&mut entry.tool_calls, tool_call);
+            }
+            if let Some(finish_reason) =
+                choice.get("finish_reason").filter(|value| !value.is_null())
+            {
+                entry.finish_reason = Some(finish_reason.clone());
+            }
+        }
+        first.get_or_insert(chunk);
+    }
+
+    let first = first?;
+    let choices: Vec<Value> = choices
+        .into_iter()
+        .map(|(index, choice)| {
+            let mut message = json!({
+                "role": choice.role.unwrap_or(json!("assistant")),
+                "content": choice.content,
+            });
+            if !choice.tool_calls.is_empty() {
+                if message["content"] == "" {
+                    message["content"] = Value::Null;
+                }
+                message["tool_calls"] = Value::Array(choice.tool_calls.into_values().collect());
+            }
+            json!({
+                "index": index,
+                "message": message,
+                "finish_reason": choice.finish_reason.unwrap_or(json!("stop")),
+            })
+        })
+        .collect();
+
+    let mut completion = json!({
+        "id": first.get("id").cloned().unwrap_or_default(),
+        "object": "chat.completion",
+        "created": first.get("created").cloned().unwrap_or_default(),
+        "model": first.get("model").cloned().unwrap_or_default(),
+        "choices": choices,
+    });
+    if let Some(fingerprint) = first.get("system_fingerprint") {
+        completion["system_fingerprint"] = fingerprint.clone();
+    }
+    if let Some(usage) = usage {
+        completion["usage"] = usage;
+    }
+    serde_json::to_vec(&completion).ok()
+}
+
+fn merge_tool_call_delta(tool_calls: &mut BTreeMap<u64, Value>, delta: &Value) {
+    let index = delta.get("index").and_then(Value::as_u64).unwrap_or(0);
+    let tool_call = tool_calls.entry(index).or_insert_with(|| {
+        json!({
+            "id": "",
+            "type": "function",
+            "function": { "name": "", "arguments": "" },
+        })
+    });
+    if let Some(id) = delta.get("id").filter(|id| id.is_string()) {
+        tool_call["id"] = id.clone();
+    }
+    let function = delta.get("function").unwrap_or(&Value::Null);
+    for field in ["name", "arguments"] {
+        if let Some(part) = function.get(field).and_then(Value::as_str) {
+            let merged = format!(
+                "{}{part}",
+                tool_call["function"][field].as_str().unwrap_or_default()
+            );
+            tool_call["function"][field] = Value::String(merged);
+        }
+    }
+}
+
+fn messages_stream_from_message(body: &[u8]) -> Option<Vec<u8>> {
+    let message = serde_json::from_slice::<Value>(body).ok()?;
+    let content = message.get("content")?.as_array()?;
+    let usage = message.get("usage").cloned().unwrap_or_else(|| json!({}));
+
+    let mut start = message.clone();
+    start["content"] = json!([]);
+    start["stop_reason"] = Value::Null;
+    start["stop_sequence"] = Value::Null;
+    start["usage"]["output_tokens"] = json!(0);
+
+    let mut out = String::new();
+    push_event(
+        &mut out,
+        "message_start",
+        json!({ "type": "message_start", "message": start }),
+    );
+    for (index, block) in content.iter().enumerate() {
+        let (content_block, delta) = match block.get("type").and_then(Value::as_str) {
+            Some("text") => (
+                json!({ "type": "text", "text": "" }),
+                Some(json!({
+                    "type": "text_delta",
+                    "text": block.get("text").cloned().unwrap_or_default(),
+                })),
+            ),
+            Some("tool_use") => {
+                let mut content_block = block.clone();
+                content_block["input"] = json!({});
+                let input = block.get("input").cloned().unwrap_or_else(|| json!({}));
+                (
+                    content_block,
+                    Some(json!({
+                        "type": "input_json_delta",
+                        "partial_json": input.to_string(),
+                    })),
+                )
+            }
+            Some("thinking") => (
+                json!({ "type": "thinking", "thinking": "" }),
+                Some(json!({
+                    "type": "thinking_delta",
+                    "thinking": block.get("thinking").cloned().unwrap_or_default(),
+                })),
+            ),
+            _ => (block.clone(), None),
+        };
+        push_event(
+            &mut out,
+            "content_block_start",
+            json!({
+                "type": "content_block_start",
+                "index": index,
+                "content_block": content_block,
+            }),
+        );
+        if let Some(delta) = delta {
+            push_event(
+                &mut out,
+                "content_block_delta",
+                json!({ "type": "content_block_delta", "index": index, "delta": delta }),
+            );
+        }
+        if let Some(signature) = block.get("signature") {
+            push_event(
+                &mut out,
+                "content_block_delta",
+                json!({
+                    "type": "content_block_delta",
+                    "index": index,
+                    "delta": { "type": "signature_delta", "signature": signature },
+                }),
+            );
+        }
+        push_event(
+            &mut out,
+            "content_block_stop",
+            json!({ "type": "content_block_stop", "index": index }),
+        );
+    }
+    push_event(
+        &mut out,
+        "message_delta",
+        json!({
+            "type": "message_delta",
+            "delta": {
+                "stop_reason": message.get("stop_reason").cloned().unwrap_or_default(),
+                "stop_sequence": message.get("stop_sequence").cloned().unwrap_or_default(),
+            },
+            "usage": { "output_tokens": usage.get("output_tokens").cloned().unwrap_or(json!(0)) },
+        }),
+    );
+    push_event(&mut out, "message_stop", json!({ "type": "message_stop" }));
+    Some(out.into_bytes())
+}
+
+fn message_from_messages_stream(body: &[u8]) -> Option<Vec<u8>> {
+    let mut message: Option<Value> = None;
+    let mut blocks: BTreeMap<u64, Value> = BTreeMap::new();
+    let mut tool_inputs: BTreeMap<u64, String> = BTreeMap::new();
+
+    for event in parse_events(body) {
+        let index = event.get("index").and_then(Value::as_u64).unwrap_or(0);
+        match event.get("type").and_then(Value::as_str) {
+            Some("message_start") => message = event.get("message").cloned(),
+            Some("content_block_start") => {
+                if let Some(block) = event.get("content_block") {
+                    blocks.insert(index, block.clone());
+                }
+            }
+            Some("content_block_delta") => {
+                let Some(block) = blocks.get_mut(&index) else {
+                    continue;
+                };
+                let delta = event.get("delta").unwrap_or(&Value::Null);
+                match delta.get("type").and_then(Value::as_str) {
+                    Some("text_delta") => append_str(block, "text", delta.get("text")),
+                    Some("thinking_delta") => append_str(block, "thinking", delta.get("thinking")),
+                    Some("signature_delta") => {
+                        block["signature"] = delta.get("signature").cloned().unwrap_or_default();
+                    }
+                    Some("input_json_delta") => tool_inputs
+                        .entry(index)
+                        .or_default()
+                        .push_str(delta.get("partial_json").and_then(Value::as_str)?),
+                    _ => {}
+                }
+            }
+            Some("message_delta") => {
+                let message = message.as_mut()?;
+                if let Some(Value::Object(delta)) = event.get("delta") {
+                    for (field, value) in delta {
+                        message[field] = value.clone();
+                    }
+                }
+                if let Some(Value::Object(usage)) = event.get("usage") {
+                    for (field, value) in usage {
+                        message["usage"][field] = value.clone();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (index, input) in tool_inputs {
+        if let Some(block) = blocks.get_mut(&index) {
+            block["input"] = serde_json::from_str(&input).ok()?;
+        }
+    }
+    let mut message = message?;
+    message["content"] = Value::Array(blocks.into_values().collect());
+    serde_json::to_vec(&message).ok()
+}
+
+fn append_str(block: &mut Value, field: &str, part: Option<&Value>) {
+    let Some(part) = part.and_then(Value::as_str) else {
+        return;
+    };
+    let merged = format!("{}{part}", block[field].as_str().unwrap_or_default());
+    block[field] = Value::String(merged);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached(body: Value, stream: bool) -> CachedResponse {
+        CachedResponse {
+            source_request_log_id: 1,
+            status_code: 200,
+            response_body: serde_json::to_vec(&body).unwrap(),
+            response_content_type: Some(JSON_CONTENT_TYPE.to_string()),
+            stream,
+            created_at: time::OffsetDateTime::now_utc(),
+        }
+    }
+
+    fn json_body(cached: &CachedResponse) -> Value {
+        serde_json::from_slice(&cached.response_body).unwrap()
+    }
+
+    #[test]
+    fn synthesized_streams_round_trip_to_the_cached_response() {
+        let completion = json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "lookup", "arguments": "{\"q\":1}" },
+                    }],
+                },
+                "finish_reason": "tool_calls",
+            }],
+            "usage": { "prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7 },
+        });
+        let stream = synthesize(
+            ApiType::OpenAiChatCompletions,
+            cached(completion.clone(), false),
+            true,
+            true,
+        )
+        .unwrap();
+        assert!(stream.stream);
+        assert_eq!(
+            stream.response_content_type.as_deref(),
+            Some(SSE_CONTENT_TYPE)
+        );
+        let events = split_events(&stream.response_body);
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[4], b"data: [DONE]\n\n");
+        let buffered = synthesize(ApiType::OpenAiChatCompletions, stream, false, false).unwrap();
+        assert_eq!(json_body(&buffered), completion);
+
+        let message = json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude",
+            "content": [
+                { "type": "text", "text": "Hello" },
+                { "type": "tool_use", "id": "toolu_1", "name": "lookup", "input": { "q": 1 } },
+            ],
+            "stop_reason": "tool_use",
+            "stop_sequence": null,
+            "usage": { "input_tokens": 5, "output_tokens": 2 },
+        });
+        let stream = synthesize(
+            ApiType::AnthropicMessages,
+            cached(message.clone(), false),
+            true,
+            false,
+        )
+        .unwrap();
+        assert!(
+            String::from_utf8_lossy(&stream.response_body).starts_with("event: message_start\n")
+        );
+        let buffered = synthesize(ApiType::AnthropicMessages, stream, false, false).unwrap();
+        assert_eq!(json_body(&buffered), message);
+
+        assert!(
+            synthesize(
+                ApiType::OpenAiEmbeddings,
+                cached(json!({}), false),
+                true,
+                false
+            )
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use serde::Serialize;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::constants::{
+    CIRCUIT_BREAKER_COOLDOWN_SECS, CIRCUIT_BREAKER_ERROR_RATE, CIRCUIT_BREAKER_FAILURE_THRESHOLD,
+    CIRCUIT_BREAKER_MIN_REQUESTS, CIRCUIT_BREAKER_WINDOW_SECS,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// Result of an upstream attempt, attributed to the part that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    Success,
+    /// The endpoint is unreachable or broken (transport error, 5xx, timeout).
+    EndpointFailure,
+    /// The endpoint answered but rejected the key (rate limited, unauthorized).
+    KeyFailure,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    window_start: Instant,
+    window_requests: u32,
+    window_failures: u32,
+    opened_at: Option<Instant>,
+    probe_started_at: Option<Instant>,
+}
+
+impl Breaker {
+    fn new(now: Instant) -> Self {
+        Self {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            window_start: now,
+            window_requests: 0,
+            window_failures: 0,
+            opened_at: None,
+            probe_started_at: None,
+        }
+    }
+
+    fn cooldown() -> Duration {
+        Duration::from_secs(CIRCUIT_BREAKER_COOLDOWN_SECS)
+    }
+
+    fn roll_window(&mut self, now: Instant) {
+        if now.duration_since(self.window_start) >= Duration::from_secs(CIRCUIT_BREAKER_WINDOW_SECS)
+        {
+            self.window_start = now;
+            self.window_requests = 0;
+            self.window_failures = 0;
+        }
+    }
+
+    fn cooled_down(&self, now: Instant) -> bool {
+        self.opened_at
+            .is_some_and(|opened_at| now.duration_since(opened_at) >= Self::cooldown())
+    }
+
+    /// A probe that never reported back (e.g. the client went away) must not
+    /// keep the breaker half-open forever.
+    fn probe_in_flight(&self, now: Instant) -> bool {
+        self.probe_started_at
+            .is_some_and(|started_at| now.duration_since(started_at) < Self::cooldown())
+    }
+
+    fn allows(&self, now: Instant) -> bool {
+        match self.state {
+            BreakerState::Closed => true,
+            BreakerState::Open => self.cooled_down(now),
+            BreakerState::HalfOpen => !self.probe_in_flight(now),
+        }
+    }
+
+    fn try_acquire(&mut self, now: Instant) -> bool {
+        match self.state {
+            BreakerState::Closed => true,
+            BreakerState::Open | BreakerState::HalfOpen if self.allows(now) => {
+                self.state = BreakerState::HalfOpen;
+                self.probe_started_at = Some(now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn record_success(&mut self, now: Instant) {
+        if self.state != BreakerState::Closed {
+            *self = Self::new(now);
+        }
+        self.roll_window(now);
+        self.consecutive_failures = 0;
+        self.window_requests += 1;
+    }
+
+    /// Returns `true` when this failure opened the breaker.
+    fn record_failure(&mut self, now: Instant) -> bool {
+        match self.state {
+            // Requests that were already in flight when the breaker opened.
+            BreakerState::Open => return false,
+            BreakerState::HalfOpen => {
+                self.open(now);
+                return true;
+            }
+            BreakerState::Closed => {}
+        }
+        self.roll_window(now);
+        self.consecutive_failures += 1;
+        self.window_requests += 1;
+        self.window_failures += 1;
+
+        let error_rate = self.window_failures as f64 / self.window_requests as f64;
+        if self.consecutive_failures >= CIRCUIT_BREAKER_FAILURE_THRESHOLD
+            || (self.window_requests >= CIRCUIT_BREAKER_MIN_REQUESTS
+                && error_rate >= CIRCUIT_BREAKER_ERROR_RATE)
+        {
+            self.open(now);
+            return true;
+        }
+        false
+    }
+
+    /// Lets another probe through when this attempt says nothing about the
+    /// health of this breaker's subject.
+    fn release_probe(&mut self) {
+        self.probe_started_at = None;
+    }
+
+    fn open(&mut self, now: Instant) {
+        self.state = BreakerState::Open;
+        self.opened_at = Some(now);
+        self.probe_started_at = None;
+    }
+
+    fn snapshot(&self, now: Instant) -> BreakerSnapshot {
+        let state = match self.state {
+            BreakerState::Open if self.cooled_down(now) => BreakerState::HalfOpen,
+            state => state,
+        };
+        let open_until = match self.state {
+            BreakerState::Open => self.opened_at.map(|opened_at| {
+                let remaining = (opened_at + Self::cooldown()).saturating_duration_since(now);
+                OffsetDateTime::now_utc() + remaining
+            }),
+            _ => None,
+        };
+        BreakerSnapshot {
+            state,
+            consecutive_failures: self.consecutive_failures,
+            window_requests: self.window_requests,
+            window_failures: self.window_failures,
+            open_until,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerSnapshot {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    pub window_requests: u32,
+    pub window_failures: u32,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub open_until: Option<OffsetDateTime>,
+}
+
+impl Default for BreakerSnapshot {
+    fn default() -> Self {
+        Self {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            window_requests: 0,
+            window_failures: 0,
+            open_until: None,
+        }
+    }
+}
+
+/// In-process circuit breakers for provider endpoints and provider keys.
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBreakers {
+    endpoints: Arc<DashMap<Uuid, Breaker>>,
+    keys: Arc<DashMap<Uuid, Breaker>>,
+}
+
+impl CircuitBreakers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether routing should consider this endpoint/key pair at all.
+    pub fn allows(&self, endpoint_id: Uuid, key_id: Uuid) -> bool {
+        let now = Instant::now();
+        let endpoint_ok = self
+            .endpoints
+            .get(&endpoint_id)
+            .is_none_or(|breaker| breaker.allows(now));
+        let key_ok = self
+            .keys
+            .get(&key_id)
+            .is_none_or(|breaker| breaker.allows(now));
+        endpoint_ok && key_ok
+    }
+
+    /// Claims the right to send a request, taking the half-open probe slot
+    /// when a breaker has cooled down.
+    pub fn try_acquire(&self, endpoint_id: Uuid, key_id: Uuid) -> bool {
+        let now = Instant::now();
+        if !self.allows(endpoint_id, key_id) {
+            return false;
+        }
+        let endpoint_ok = self
+            .endpoints
+            .get_mut(&endpoint_id)
+            .is_none_or(|mut breaker| breaker.try_acquire(now));
+        if !endpoint_ok {
+            return false;
+        }
+        let key_ok = self
+            .keys
+            .get_mut(&key_id)
+            .is_none_or(|mut breaker| breaker.try_acquire(now));
+        if !key_ok && let Some(mut breaker) = self.endpoints.get_mut(&endpoint_id) {
+            breaker.release_probe();
+        }
+        key_ok
+    }
+
+    pub fn record(&self, endpoint_id: Uuid, key_id: Uuid, outcome: AttemptOutcome) {
+        let now = Instant::now();
+        let mut endpoint = self
+            .endpoints
+            .entry(endpoint_id)
+            .or_insert_with(|| Breaker::new(now));
+        match outcome {
+            AttemptOutcome::Success | AttemptOutcome::KeyFailure => endpoint.record_success(now),
+            AttemptOutcome::EndpointFailure => {
+                if endpoint.record_failure(now) {
+                    tracing::warn!(%endpoint_id, "circuit breaker opened for provider endpoint");
+                }
+            }
+        }
+        drop(endpoint);
+
+        let mut key = self.keys.entry(key_id).or_insert_with(|| Breaker::new(now));
+        match outcome {
+            AttemptOutcome::Success => key.record_success(now),
+            AttemptOutcome::KeyFailure => {
+                if key.record_failure(now) {
+                    tracing::warn!(%key_id, "circuit breaker opened for provider key");
+                }
+            }
+            AttemptOutcome::EndpointFailure => key.release_probe(),
+        }
+    }
+
+    pub fn endpoint_snapshot(&self, endpoint_id: Uuid) -> BreakerSnapshot {
+        self.endpoints
+            .get(&endpoint_id)
+            .map(|breaker| breaker.snapshot(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    pub fn key_snapshot(&self, key_id: Uuid) -> BreakerSnapshot {
+        self.keys
+            .get(&key_id)
+            .map(|breaker| breaker.snapshot(Instant::now()))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaker_opens_after_consecutive_failures_and_half_opens_for_one_probe() {
+        let start = Instant::now();
+        let mut breaker = Breaker::new(start);
+
+        for _ in 0..CIRCUIT_BREAKER_FAILURE_THRESHOLD {
+            assert!(breaker.try_acquire(start));
+            breaker.record_failure(start);
+        }
+        assert_eq!(breaker.state, BreakerState::Open);
+        assert!(!breaker.try_acquire(start));
+
+        let cooled = start + Breaker::cooldown();
+        assert!(breaker.try_acquire(cooled));
+        assert_eq!(breaker.state, BreakerState::HalfOpen);
+        assert!(!breaker.try_acquire(cooled));
+
+        breaker.record_success(cooled);
+        assert_eq!(breaker.state, BreakerState::Closed);
+        assert!(breaker.try_acquire(cooled));
+    }
+
+    #[test]
+    fn failed_probe_reopens_breaker() {
+        let start = Instant::now();
+        let mut breaker = Breaker::new(start);
+        breaker.open(start);
+
+        let cooled = start + Breaker::cooldown();
+        assert!(breaker.try_acquire(cooled));
+        assert!(breaker.record_failure(cooled));
+        assert_eq!(breaker.state, BreakerState::Open);
+        assert!(!breaker.allows(cooled));
+    }
+}
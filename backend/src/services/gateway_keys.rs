@@ -4,9 +4,11 @@ use uuid::Uuid;
 
 use crate::db::{
     gateway_key_models,
-    gateway_keys::{self, GatewayKey},
+    gateway_keys::{self, GatewayKey, KeyLimits},
+    types::CacheSettings,
 };
 use crate::error::AppResult;
+use crate::services::response_cache;
 
 pub async fn list_gateway_keys(
     pool: &PgPool,
@@ -56,17 +58,18 @@ fn normalize_allowed_models(models: Option<Vec<String>>) -> Option<Vec<String>>
 pub async fn create_gateway_key(
     pool: &PgPool,
     name: Option<String>,
-    rate_limit_rps: Option<i32>,
-    rate_limit_rpm: Option<i32>,
+    limits: KeyLimits,
+    cache: CacheSettings,
     allowed_models: Option<Vec<String>>,
 ) -> AppResult<GatewayKey> {
+    response_cache::validate_settings(&cache)?;
     let key = generate_random_key();
 
     let params = gateway_keys::CreateGatewayKeyParams {
         name,
         key,
-        rate_limit_rps,
-        rate_limit_rpm,
+        limits,
+        cache,
     };
 
     let mut created = gateway_keys::create_gateway_key(pool, params).await?;
@@ -94,15 +97,16 @@ pub async fn update_gateway_key(
     id: Uuid,
     name: Option<String>,
     enabled: Option<bool>,
-    rate_limit_rps: Option<i32>,
-    rate_limit_rpm: Option<i32>,
+    limits: KeyLimits,
+    cache: CacheSettings,
     allowed_models: Option<Vec<String>>,
 ) -> AppResult<Option<GatewayKey>> {
+    response_cache::validate_settings(&cache)?;
     let params = gateway_keys::UpdateGatewayKeyParams {
         name,
         enabled,
-        rate_limit_rps,
-        rate_limit_rpm,
+        limits,
+        cache,
     };
 
     let mut key = match gateway_keys::update_gateway_key(pool, id, params).await? {
@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    constants::READINESS_CHECK_TIMEOUT_SECS,
+    db::{self, health},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl CheckResult {
+    fn pass() -> Self {
+        Self {
+            status: CheckStatus::Pass,
+            message: None,
+        }
+    }
+
+    fn fail(message: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Fail,
+            message: Some(message.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessChecks {
+    pub database: CheckResult,
+    pub migrations: CheckResult,
+    pub shutdown: CheckResult,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: CheckStatus,
+    pub checks: ReadinessChecks,
+}
+
+/// Runs the readiness checks. The database checks are skipped once draining
+/// so a shutting-down instance answers immediately.
+pub async fn check_readiness(pool: &PgPool, shutting_down: bool) -> Readiness {
+    let checks = if shutting_down {
+        ReadinessChecks {
+            database: CheckResult::fail("skipped while draining"),
+            migrations: CheckResult::fail("skipped while draining"),
+            shutdown: CheckResult::fail("shutdown in progress"),
+        }
+    } else {
+        let (database, migrations) = check_database(pool).await;
+        ReadinessChecks {
+            database,
+            migrations,
+            shutdown: CheckResult::pass(),
+        }
+    };
+
+    let ready = [&checks.database, &checks.migrations, &checks.shutdown]
+        .iter()
+        .all(|check| check.status == CheckStatus::Pass);
+    Readiness {
+        status: if ready {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail
+        },
+        checks,
+    }
+}
+
+async fn check_database(pool: &PgPool) -> (CheckResult, CheckResult) {
+    let timeout = Duration::from_secs(READINESS_CHECK_TIMEOUT_SECS);
+    let applied = tokio::time::timeout(timeout, async {
+        health::ping(pool).await?;
+        health::applied_migrations(pool).await
+    })
+    .await;
+
+    let applied = match applied {
+        Ok(Ok(applied)) => applied,
+        Ok(Err(err)) => {
+            tracing::warn!(error = %err, "readiness database check failed");
+            return (
+                CheckResult::fail("database unreachable"),
+                CheckResult::fail("database unreachable"),
+            );
+        }
+        Err(_) => {
+            return (
+                CheckResult::fail("database check timed out"),
+                CheckResult::fail("database check timed out"),
+            );
+        }
+    };
+
+    let expected = db::MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version);
+    let pending = pending_migrations(expected, &applied);
+    let migrations = if pending.is_empty() {
+        CheckResult::pass()
+    } else {
+        let versions = pending
+            .iter()
+            .map(i64::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        CheckResult::fail(format!("pending migrations: {versions}"))
+    };
+
+    (CheckResult::pass(), migrations)
+}
+
+fn pending_migrations(expected: impl Iterator<Item = i64>, applied: &[i64]) -> Vec<i64> {
+    expected
+        .filter(|version| !applied.contains(version))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_migrations_lists_versions_not_applied() {
+        let expected = [20260101000000, 20260201000000, 20260301000000];
+        let applied = [20260101000000, 20260301000000];
+
+        assert_eq!(
+            pending_migrations(expected.into_iter(), &applied),
+            vec![20260201000000]
+        );
+        assert!(pending_migrations(expected.into_iter(), &expected).is_empty());
+    }
+}
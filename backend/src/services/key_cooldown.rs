@@ -0,0 +1,213 @@
+use std::time::Duration;
+
+use reqwest::{StatusCode, header::HeaderMap};
+use time::{
+    OffsetDateTime,
+    format_description::well_known::{Rfc2822, Rfc3339},
+};
+
+use crate::constants::{KEY_COOLDOWN_DEFAULT_SECS, KEY_COOLDOWN_MAX_SECS, KEY_QUOTA_COOLDOWN_SECS};
+
+/// Reset headers paired with the "remaining" header that tells whether that
+/// particular limit is the one that was hit.
+const RESET_HEADERS: [(&str, Option<&str>); 6] = [
+    ("retry-after", None),
+    ("x-ratelimit-reset", None),
+    (
+        "x-ratelimit-reset-requests",
+        Some("x-ratelimit-remaining-requests"),
+    ),
+    (
+        "x-ratelimit-reset-tokens",
+        Some("x-ratelimit-remaining-tokens"),
+    ),
+    (
+        "anthropic-ratelimit-requests-reset",
+        Some("anthropic-ratelimit-requests-remaining"),
+    ),
+    (
+        "anthropic-ratelimit-tokens-reset",
+        Some("anthropic-ratelimit-tokens-remaining"),
+    ),
+];
+
+/// How long a provider key should sit out after this upstream response, or
+/// `None` when the response says nothing about the key's limits.
+///
+/// The body is optional because it is only available when the response was
+/// buffered; it lets a 429 that reports an exhausted quota cool down longer.
+pub fn cooldown_for_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: Option<&[u8]>,
+) -> Option<Duration> {
+    let quota_exhausted = match status {
+        StatusCode::TOO_MANY_REQUESTS => body.is_some_and(mentions_quota),
+        StatusCode::PAYMENT_REQUIRED => true,
+        _ => return None,
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let from_headers = RESET_HEADERS
+        .iter()
+        .filter(|(_, remaining)| remaining.is_none_or(|name| limit_exhausted(headers, name)))
+        .filter_map(|(name, _)| headers.get(*name)?.to_str().ok())
+        .filter_map(|value| parse_reset(value, now))
+        .max();
+
+    let cooldown = match (from_headers, quota_exhausted) {
+        (Some(cooldown), false) => cooldown,
+        (None, false) => Duration::from_secs(KEY_COOLDOWN_DEFAULT_SECS),
+        (cooldown, true) => cooldown
+            .unwrap_or_default()
+            .max(Duration::from_secs(KEY_QUOTA_COOLDOWN_SECS)),
+    };
+
+    Some(cooldown.min(Duration::from_secs(KEY_COOLDOWN_MAX_SECS)))
+}
+
+fn mentions_quota(body: &[u8]) -> bool {
+    String::from_utf8_lossy(body)
+        .to_ascii_lowercase()
+        .contains("quota")
+}
+
+/// A limit counts as exhausted unless the upstream says some of it is left.
+fn limit_exhausted(headers: &HeaderMap, remaining_header: &str) -> bool {
+    headers
+        .get(remaining_header)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .is_none_or(|remaining| remaining <= 0.0)
+}
+
+/// Parses the reset formats seen in the wild: delay seconds, unix
+/// timestamps, RFC 3339 / HTTP dates and Go style durations such as `6m0s`.
+fn parse_reset(value: &str, now: OffsetDateTime) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(number) = value.parse::<f64>() {
+        if !number.is_finite() || number < 0.0 {
+            return None;
+        }
+        // Values this large are epoch timestamps rather than delays.
+        if number > 1_000_000_000.0 {
+            let until = OffsetDateTime::from_unix_timestamp(number as i64).ok()?;
+            return until_from(now, until);
+        }
+        return Some(Duration::from_secs_f64(number));
+    }
+
+    if let Ok(until) = OffsetDateTime::parse(value, &Rfc3339) {
+        return until_from(now, until);
+    }
+    if let Ok(until) = OffsetDateTime::parse(value, &Rfc2822) {
+        return until_from(now, until);
+    }
+
+    parse_go_duration(value)
+}
+
+fn until_from(now: OffsetDateTime, until: OffsetDateTime) -> Option<Duration> {
+    let delta = until - now;
+    if delta.is_negative() {
+        return Some(Duration::ZERO);
+    }
+    delta.try_into().ok()
+}
+
+fn parse_go_duration(value: &str) -> Option<Duration> {
+    if value.is_empty() {
+        return None;
+    }
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let unit_secs = match &rest[..unit_end] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            "us" | "µs" => 0.000_001,
+            "ns" => 0.000_000_001,
+            _ => return None,
+        };
+        rest = &rest[unit_end..];
+        total += number * unit_secs;
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn parses_reset_formats() {
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(parse_reset("20", now), Some(Duration::from_secs(20)));
+        assert_eq!(parse_reset("6m0s", now), Some(Duration::from_secs(360)));
+        assert_eq!(
+            parse_reset("1h2m3.5s", now),
+            Some(Duration::from_secs_f64(3723.5))
+        );
+        assert_eq!(parse_reset("250ms", now), Some(Duration::from_millis(250)));
+        assert_eq!(
+            parse_reset("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_reset("soon", now), None);
+    }
+
+    #[test]
+    fn cooldown_uses_the_exhausted_limit() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-ratelimit-remaining-requests",
+            HeaderValue::from_static("0"),
+        );
+        headers.insert("x-ratelimit-reset-requests", HeaderValue::from_static("2s"));
+        headers.insert(
+            "x-ratelimit-remaining-tokens",
+            HeaderValue::from_static("1500"),
+        );
+        headers.insert("x-ratelimit-reset-tokens", HeaderValue::from_static("6m0s"));
+
+        assert_eq!(
+            cooldown_for_response(StatusCode::TOO_MANY_REQUESTS, &headers, None),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            cooldown_for_response(StatusCode::BAD_REQUEST, &headers, None),
+            None
+        );
+    }
+
+    #[test]
+    fn cooldown_falls_back_to_defaults() {
+        let headers = HeaderMap::new();
+        assert_eq!(
+            cooldown_for_response(StatusCode::TOO_MANY_REQUESTS, &headers, None),
+            Some(Duration::from_secs(KEY_COOLDOWN_DEFAULT_SECS))
+        );
+        assert_eq!(
+            cooldown_for_response(StatusCode::PAYMENT_REQUIRED, &headers, None),
+            Some(Duration::from_secs(KEY_QUOTA_COOLDOWN_SECS))
+        );
+        let body = br#"{"error":{"code":"insufficient_quota"}}"#;
+        assert_eq!(
+            cooldown_for_response(StatusCode::TOO_MANY_REQUESTS, &headers, Some(body)),
+            Some(Duration::from_secs(KEY_QUOTA_COOLDOWN_SECS))
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use axum::http::header;
+use reqwest::Client;
+use sqlx::PgPool;
+use tokio::time::{Instant, MissedTickBehavior};
+
+use crate::{
+    constants::{KEY_REVALIDATION_INTERVAL_SECS, KEY_REVALIDATION_TIMEOUT_SECS},
+    db::{
+        provider_keys::{self, DisabledKeyProbe},
+        types::{ApiType, KeyDisabledReason},
+    },
+    error::AppResult,
+    services::background::BackgroundTasks,
+};
+
+/// Endpoint types a disabled key can be probed through, preferred first.
+/// Keys of providers with none of them stay disabled until re-enabled by hand.
+const PROBE_API_TYPES: &[ApiType] = &[
+    ApiType::OpenAiModels,
+    ApiType::AnthropicMessages,
+    ApiType::GeminiGenerateContent,
+];
+
+/// Starts the periodic job that probes keys disabled after an upstream 401
+/// and re-enables the ones the provider accepts again.
+pub fn spawn_key_revalidation(
+    pool: PgPool,
+    http_client: Client,
+    background_tasks: &BackgroundTasks,
+) {
+    let shutdown_token = background_tasks.token();
+    background_tasks.spawn("provider_keys.revalidate", async move {
+        let period = Duration::from_secs(KEY_REVALIDATION_INTERVAL_SECS);
+        let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => return,
+                _ = interval.tick() => {}
+            }
+
+            if let Err(err) = revalidate_disabled_keys(&pool, &http_client).await {
+                tracing::error!(error = %err, "failed to revalidate disabled provider keys");
+            }
+        }
+    });
+}
+
+async fn revalidate_disabled_keys(pool: &PgPool, http_client: &Client) -> AppResult<()> {
+    let probes = provider_keys::fetch_disabled_key_probes(
+        pool,
+        KeyDisabledReason::Unauthorized,
+        PROBE_API_TYPES,
+    )
+    .await?;
+    tracing::debug!(keys = probes.len(), "revalidating disabled provider keys");
+
+    for probe in probes {
+        if !probe_key(http_client, &probe).await {
+            continue;
+        }
+        if provider_keys::reenable_key(pool, probe.id, KeyDisabledReason::Unauthorized).await? {
+            tracing::info!(
+                provider_key_id = %probe.id,
+                provider_id = %probe.provider_id,
+                "provider key accepted again, re-enabled"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists models with the key, authenticated the way the endpoint's provider
+/// expects. Anthropic models are listed next to the messages endpoint and
+/// Gemini endpoints are already the models URL.
+async fn probe_key(http_client: &Client, probe: &DisabledKeyProbe) -> bool {
+    let request = match probe.endpoint_api_type {
+        ApiType::AnthropicMessages => {
+            let Some(base) = probe
+                .endpoint_url
+                .trim_end_matches('/')
+                .strip_suffix("/messages")
+            else {
+                tracing::debug!(
+                    provider_key_id = %probe.id,
+                    url = %probe.endpoint_url,
+                    "cannot derive models url from messages endpoint, skipping probe"
+                );
+                return false;
+            };
+            http_client
+                .get(format!("{base}/models"))
+                .header("x-api-key", &probe.key)
+                .header("anthropic-version", "2023-06-01")
+        }
+        ApiType::GeminiGenerateContent => http_client
+            .get(&probe.endpoint_url)
+            .header("x-goog-api-key", &probe.key),
+        _ => http_client
+            .get(&probe.endpoint_url)
+            .header(header::AUTHORIZATION, format!("Bearer {}", probe.key)),
+    };
+    let response = request
+        .timeout(Duration::from_secs(KEY_REVALIDATION_TIMEOUT_SECS))
+        .send()
+        .await;
+
+    match response {
+        Ok(response) => {
+            tracing::debug!(
+                provider_key_id = %probe.id,
+                status = %response.status(),
+                "probed disabled provider key"
+            );
+            response.status().is_success()
+        }
+        Err(err) => {
+            tracing::debug!(provider_key_id = %probe.id, error = %err, "disabled provider key probe failed");
+            false
+        }
+    }
+}
@@ -1,11 +1,19 @@
+use std::time::Duration;
+
 use sqlx::PgPool;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
+use crate::db::budgets::ModelUsage;
 use crate::db::cache_log::{self, CacheLogContext};
 use crate::db::request_logs::{
     self, CachedResponse, RequestLog, RequestLogContext, RequestLogFilter, RequestLogSummary,
 };
 use crate::error::AppResult;
+use crate::services::{
+    budgets::BudgetSpend,
+    pricing::{self, TokenCounts},
+};
 
 pub async fn fetch_request_logs(
     pool: &PgPool,
@@ -25,13 +33,18 @@ pub async fn fetch_request_log_detail(
     request_logs::fetch_request_log_detail(pool, request_id).await
 }
 
-pub async fn record_request(pool: &PgPool, context: &RequestLogContext) -> AppResult<()> {
+pub async fn record_request(
+    pool: &PgPool,
+    budget_spend: &BudgetSpend,
+    context: &RequestLogContext,
+) -> AppResult<()> {
     let Some(api_type) = context.api_type else {
         return Ok(());
     };
 
     let db_context = request_logs::RequestLogContext {
         request_id: context.request_id,
+        parent_request_id: context.parent_request_id,
         gateway_key_id: context.gateway_key_id,
         api_type: Some(api_type),
         model: context.model.clone(),
@@ -49,16 +62,65 @@ pub async fn record_request(pool: &PgPool, context: &RequestLogContext) -> AppRe
         prompt_tokens: context.prompt_tokens,
         completion_tokens: context.completion_tokens,
         total_tokens: context.total_tokens,
+        cached_tokens: context.cached_tokens,
+        ttft_ms: context.ttft_ms,
+        stream: context.stream,
+        request_body_hash: context.request_body_hash,
+    };
+
+    // A failed price lookup must not lose the log itself.
+    let cost = match (&context.provider, &context.model) {
+        (Some(provider), Some(model)) => {
+            let tokens = TokenCounts {
+                prompt: context.prompt_tokens,
+                completion: context.completion_tokens,
+                cached: context.cached_tokens,
+            };
+            pricing::request_cost(pool, provider, model, tokens)
+                .await
+                .unwrap_or_else(|err| {
+                    tracing::error!(error = %err, "failed to price request");
+                    None
+                })
+        }
+        _ => None,
     };
+    // Counted before the log is written so budget checks see it right away.
+    if let Some(gateway_key_id) = context.gateway_key_id {
+        budget_spend.record(
+            gateway_key_id,
+            ModelUsage {
+                model: context.model.clone(),
+                alias: context.alias.clone(),
+                prompt_tokens: context.prompt_tokens.unwrap_or_default().into(),
+                completion_tokens: context.completion_tokens.unwrap_or_default().into(),
+                cost: cost.unwrap_or_default(),
+            },
+        );
+    }
 
-    request_logs::record_request(pool, &db_context).await
+    request_logs::record_request(pool, &db_context, cost).await
 }
 
 pub async fn find_cached_response(
     pool: &PgPool,
     request_body_hash: &str,
+    ttl: Duration,
+    max_body_bytes: usize,
+    stream: bool,
+    any_stream_mode: bool,
 ) -> AppResult<Option<CachedResponse>> {
-    request_logs::find_cached_response(pool, request_body_hash).await
+    let not_before = OffsetDateTime::now_utc() - ttl;
+    let max_body_bytes = i64::try_from(max_body_bytes).unwrap_or(i64::MAX);
+    request_logs::find_cached_response(
+        pool,
+        request_body_hash,
+        not_before,
+        max_body_bytes,
+        stream,
+        any_stream_mode,
+    )
+    .await
 }
 
 pub async fn record_cache_event(pool: &PgPool, context: &CacheLogContext) -> AppResult<()> {
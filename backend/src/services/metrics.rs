@@ -0,0 +1,384 @@
+//! In-process metrics in the Prometheus text exposition format. Series are
+//! kept per label set in concurrent maps and rendered on scrape.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        Arc,
+        atomic::{AtomicI64, Ordering},
+    },
+};
+
+use dashmap::DashMap;
+
+use crate::{
+    db::{request_logs::RequestLogContext, types::ApiType},
+    services::rate_limit::RateLimitKind,
+};
+
+/// Upper bounds in seconds of the request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct RouteLabels {
+    api_type: String,
+    alias: String,
+    provider: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct RequestLabels {
+    route: RouteLabels,
+    /// HTTP status of the upstream, or `error` when none was received.
+    status: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum TokenKind {
+    Prompt,
+    Completion,
+}
+
+impl TokenKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Prompt => "prompt",
+            Self::Completion => "completion",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    requests: DashMap<RequestLabels, Histogram>,
+    tokens: DashMap<(RouteLabels, TokenKind), u64>,
+    cache_lookups: DashMap<(&'static str, bool), u64>,
+    rate_limit_rejections: DashMap<RateLimitKind, u64>,
+    in_flight_streams: AtomicI64,
+}
+
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Registry>,
+}
+
+/// Counts a stream as in flight until dropped, which happens when the
+/// stream ends or the client goes away.
+pub struct StreamGuard {
+    registry: Arc<Registry>,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.registry
+            .in_flight_streams
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records one upstream attempt from its request log.
+    pub fn record_request(&self, context: &RequestLogContext) {
+        let Some(api_type) = context.api_type else {
+            return;
+        };
+        let route = route_labels(api_type, context);
+        let labels = RequestLabels {
+            route: route.clone(),
+            status: context
+                .status_code
+                .map_or_else(|| "error".to_string(), |status| status.to_string()),
+        };
+        let latency = f64::from(context.latency_ms.unwrap_or(0).max(0)) / 1000.0;
+        self.registry
+            .requests
+            .entry(labels)
+            .or_default()
+            .observe(latency);
+
+        for (kind, tokens) in [
+            (TokenKind::Prompt, context.prompt_tokens),
+            (TokenKind::Completion, context.completion_tokens),
+        ] {
+            if let Some(tokens) = tokens.filter(|tokens| *tokens > 0) {
+                *self
+                    .registry
+                    .tokens
+                    .entry((route.clone(), kind))
+                    .or_default() += tokens as u64;
+            }
+        }
+    }
+
+    pub fn record_cache_lookup(&self, layer: &'static str, hit: bool) {
+        *self.registry.cache_lookups.entry((layer, hit)).or_default() += 1;
+    }
+
+    pub fn record_rate_limit_rejection(&self, kind: RateLimitKind) {
+        *self.registry.rate_limit_rejections.entry(kind).or_default() += 1;
+    }
+
+    pub fn track_stream(&self) -> StreamGuard {
+        self.registry
+            .in_flight_streams
+            .fetch_add(1, Ordering::Relaxed);
+        StreamGuard {
+            registry: self.registry.clone(),
+        }
+    }
+
+    pub fn render(&self, background_tasks_pending: usize) -> String {
+        let mut out = String::new();
+
+        let requests: BTreeMap<_, _> = self
+            .registry
+            .requests
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        header(
+            &mut out,
+            "af_requests_total",
+            "counter",
+            "Upstream requests by route and status.",
+        );
+        for (labels, histogram) in &requests {
+            let labels = request_labels(labels);
+            let _ = writeln!(out, "af_requests_total{{{labels}}} {}", histogram.count);
+        }
+        header(
+            &mut out,
+            "af_request_duration_seconds",
+            "histogram",
+            "Upstream request latency in seconds.",
+        );
+        for (labels, histogram) in &requests {
+            let labels = request_labels(labels);
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "af_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {count}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "af_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "af_request_duration_seconds_sum{{{labels}}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "af_request_duration_seconds_count{{{labels}}} {}",
+                histogram.count
+            );
+        }
+
+        let tokens: BTreeMap<_, _> = self
+            .registry
+            .tokens
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        header(
+            &mut out,
+            "af_tokens_total",
+            "counter",
+            "Tokens reported by upstreams.",
+        );
+        for ((route, kind), count) in &tokens {
+            let _ = writeln!(
+                out,
+                "af_tokens_total{{{},kind=\"{}\"}} {count}",
+                route_label_pairs(route),
+                kind.as_str()
+            );
+        }
+
+        let cache_lookups: BTreeMap<_, _> = self
+            .registry
+            .cache_lookups
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect();
+        header(
+            &mut out,
+            "af_cache_lookups_total",
+            "counter",
+            "Response cache lookups by layer and result.",
+        );
+        for ((layer, hit), count) in &cache_lookups {
+            let result = if *hit { "hit" } else { "miss" };
+            let _ = writeln!(
+                out,
+                "af_cache_lookups_total{{layer=\"{layer}\",result=\"{result}\"}} {count}"
+            );
+        }
+
+        let rejections: BTreeMap<_, _> = self
+            .registry
+            .rate_limit_rejections
+            .iter()
+            .map(|entry| (entry.key().as_str(), *entry.value()))
+            .collect();
+        header(
+            &mut out,
+            "af_rate_limit_rejections_total",
+            "counter",
+            "Requests rejected by gateway key rate limits.",
+        );
+        for (kind, count) in &rejections {
+            let _ = writeln!(
+                out,
+                "af_rate_limit_rejections_total{{kind=\"{kind}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "af_in_flight_streams",
+            "gauge",
+            "Streaming responses currently being sent.",
+        );
+        let _ = writeln!(
+            out,
+            "af_in_flight_streams {}",
+            self.registry.in_flight_streams.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "af_background_tasks_pending",
+            "gauge",
+            "Background tasks not yet finished.",
+        );
+        let _ = writeln!(
+            out,
+            "af_background_tasks_pending {background_tasks_pending}"
+        );
+
+        out
+    }
+}
+
+fn route_labels(api_type: ApiType, context: &RequestLogContext) -> RouteLabels {
+    RouteLabels {
+        api_type: api_type.to_string(),
+        alias: context.alias.clone().unwrap_or_default(),
+        provider: context.provider.clone().unwrap_or_default(),
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn route_label_pairs(route: &RouteLabels) -> String {
+    format!(
+        "api_type=\"{}\",alias=\"{}\",provider=\"{}\"",
+        escape(&route.api_type),
+        escape(&route.alias),
+        escape(&route.provider)
+    )
+}
+
+fn request_labels(labels: &RequestLabels) -> String {
+    format!(
+        "{},status=\"{}\"",
+        route_label_pairs(&labels.route),
+        escape(&labels.status)
+    )
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn render_exposes_request_histogram_and_gauges() {
+        let metrics = Metrics::new();
+        metrics.record_request(&RequestLogContext {
+            request_id: Uuid::nil(),
+            parent_request_id: None,
+            gateway_key_id: None,
+            api_type: Some(ApiType::OpenAiChatCompletions),
+            model: Some("gpt-4o".to_string()),
+            alias: Some("fast \"one\"".to_string()),
+            provider: Some("openai".to_string()),
+            endpoint: None,
+            status_code: Some(200),
+            latency_ms: Some(300),
+            client_ip: None,
+            user_agent: None,
+            request_body: None,
+            response_body: None,
+            request_content_type: None,
+            response_content_type: None,
+            prompt_tokens: Some(12),
+            completion_tokens: Some(3),
+            total_tokens: Some(15),
+            cached_tokens: None,
+            ttft_ms: None,
+            stream: false,
+            request_body_hash: None,
+        });
+        metrics.record_cache_lookup("moka", false);
+        let stream = metrics.track_stream();
+
+        let out = metrics.render(2);
+        let labels = r#"api_type="openai_chat_completions",alias="fast \"one\"",provider="openai""#;
+        assert!(out.contains(&format!("af_requests_total{{{labels},status=\"200\"}} 1")));
+        assert!(out.contains(&format!(
+            "af_request_duration_seconds_bucket{{{labels},status=\"200\",le=\"0.25\"}} 0"
+        )));
+        assert!(out.contains(&format!(
+            "af_request_duration_seconds_bucket{{{labels},status=\"200\",le=\"0.5\"}} 1"
+        )));
+        assert!(out.contains(&format!("af_tokens_total{{{labels},kind=\"prompt\"}} 12")));
+        assert!(out.contains(r#"af_cache_lookups_total{layer="moka",result="miss"} 1"#));
+        assert!(out.contains("af_in_flight_streams 1"));
+        assert!(out.contains("af_background_tasks_pending 2"));
+
+        drop(stream);
+        assert!(metrics.render(0).contains("af_in_flight_streams 0"));
+    }
+}
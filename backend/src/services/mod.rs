@@ -1,12 +1,23 @@
 pub mod aliases;
 pub mod auth;
 pub mod background;
+pub mod budgets;
+pub mod cache_entries;
+pub mod cache_replay;
+pub mod circuit_breaker;
 pub mod gateway_keys;
+pub mod health;
+pub mod key_cooldown;
+pub mod key_revalidation;
 pub mod logging;
+pub mod metrics;
+pub mod models;
 pub mod openai;
+pub mod pricing;
 pub mod providers;
 pub mod rate_limit;
 pub mod response_cache;
 pub mod routing;
+pub mod semantic_cache;
 pub mod stats;
 pub mod users;
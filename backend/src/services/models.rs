@@ -0,0 +1,120 @@
+use axum::http::HeaderMap;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use time::format_description::well_known::Rfc3339;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        gateway_key_models,
+        models::{self, ModelEntry},
+    },
+    error::{AppError, AppResult},
+};
+
+/// `owned_by` reported for aliases, which belong to the gateway rather than
+/// to a single provider.
+const ALIAS_OWNER: &str = "gateway";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelListFormat {
+    OpenAi,
+    Anthropic,
+}
+
+impl ModelListFormat {
+    /// Anthropic SDKs always send `anthropic-version`; everything else gets
+    /// the OpenAI shape.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        if headers.contains_key("anthropic-version") {
+            Self::Anthropic
+        } else {
+            Self::OpenAi
+        }
+    }
+}
+
+/// Models the gateway key may use, honouring its whitelist.
+pub async fn list_models(pool: &PgPool, gateway_key_id: Uuid) -> AppResult<Vec<ModelEntry>> {
+    let whitelist = gateway_key_models::fetch_model_whitelist(pool, gateway_key_id).await?;
+    let entries = models::list_model_entries(pool).await?;
+
+    Ok(entries
+        .into_iter()
+        .filter(|entry| whitelist.is_empty() || whitelist.contains(&entry.id))
+        .collect())
+}
+
+pub async fn get_model(pool: &PgPool, gateway_key_id: Uuid, id: &str) -> AppResult<ModelEntry> {
+    list_models(pool, gateway_key_id)
+        .await?
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .ok_or(AppError::NotFound)
+}
+
+pub fn render_list(entries: &[ModelEntry], format: ModelListFormat) -> Value {
+    let data: Vec<Value> = entries
+        .iter()
+        .map(|entry| render_model(entry, format))
+        .collect();
+    match format {
+        ModelListFormat::OpenAi => json!({ "object": "list", "data": data }),
+        ModelListFormat::Anthropic => json!({
+            "data": data,
+            "has_more": false,
+            "first_id": entries.first().map(|entry| &entry.id),
+            "last_id": entries.last().map(|entry| &entry.id),
+        }),
+    }
+}
+
+pub fn render_model(entry: &ModelEntry, format: ModelListFormat) -> Value {
+    match format {
+        ModelListFormat::OpenAi => json!({
+            "id": entry.id,
+            "object": "model",
+            "created": entry.created_at.unix_timestamp(),
+            "owned_by": entry.provider_name.as_deref().unwrap_or(ALIAS_OWNER),
+        }),
+        ModelListFormat::Anthropic => json!({
+            "type": "model",
+            "id": entry.id,
+            "display_name": entry.id,
+            "created_at": entry.created_at.format(&Rfc3339).unwrap_or_default(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::*;
+
+    #[test]
+    fn renders_both_list_formats() {
+        let entries = [
+            ModelEntry {
+                id: "smart".to_string(),
+                provider_name: None,
+                created_at: OffsetDateTime::UNIX_EPOCH,
+            },
+            ModelEntry {
+                id: "oa:gpt-4o".to_string(),
+                provider_name: Some("OpenAI".to_string()),
+                created_at: OffsetDateTime::UNIX_EPOCH,
+            },
+        ];
+
+        let openai = render_list(&entries, ModelListFormat::OpenAi);
+        assert_eq!(openai["object"], "list");
+        assert_eq!(openai["data"][0]["owned_by"], ALIAS_OWNER);
+        assert_eq!(openai["data"][1]["owned_by"], "OpenAI");
+
+        let anthropic = render_list(&entries, ModelListFormat::Anthropic);
+        assert_eq!(anthropic["data"][1]["type"], "model");
+        assert_eq!(anthropic["data"][1]["created_at"], "1970-01-01T00:00:00Z");
+        assert_eq!(anthropic["last_id"], "oa:gpt-4o");
+    }
+}
@@ -21,6 +21,7 @@ impl OpenAiService {
             payload,
             client_info,
             ApiType::OpenAiChatCompletions,
+            None,
         )
         .await
     }
@@ -0,0 +1,71 @@
+use axum::{body::Body, http::Response};
+use serde_json::Value;
+
+use crate::{
+    db::types::ApiType,
+    error::{AppError, AppResult},
+    middleware::{auth::GatewayKeyId, request_log::ClientInfo},
+};
+
+use super::OpenAiService;
+
+/// Response formats a client may ask for with `alt`; streams are a JSON
+/// array unless it is `sse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum GeminiAlt {
+    Json,
+    Sse,
+}
+
+impl GeminiAlt {
+    fn parse(value: &str) -> AppResult<Self> {
+        match value {
+            "json" => Ok(Self::Json),
+            "sse" => Ok(Self::Sse),
+            _ => Err(AppError::BadRequest(format!("unsupported alt: {value}"))),
+        }
+    }
+
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Sse => "sse",
+        }
+    }
+}
+
+/// What Gemini carries in the URL rather than the body.
+pub(super) struct GeminiTarget {
+    pub model: String,
+    pub stream: bool,
+    /// The client's `alt`, forwarded upstream.
+    pub alt: Option<GeminiAlt>,
+}
+
+impl OpenAiService {
+    /// The body is passed on and logged as the client sent it; the model and
+    /// streaming mode travel next to it.
+    pub async fn gemini_generate_content(
+        &self,
+        gateway_key_id: GatewayKeyId,
+        model: &str,
+        stream: bool,
+        alt: Option<String>,
+        payload: Value,
+        client_info: ClientInfo,
+    ) -> AppResult<Response<Body>> {
+        let target = GeminiTarget {
+            model: model.to_string(),
+            stream,
+            alt: alt.as_deref().map(GeminiAlt::parse).transpose()?,
+        };
+        self.process_request(
+            gateway_key_id,
+            payload,
+            client_info,
+            ApiType::GeminiGenerateContent,
+            Some(target),
+        )
+        .await
+    }
+}
@@ -1,9 +1,14 @@
 mod chat;
 mod embeddings;
+mod gemini;
 mod responses;
+mod stored_responses;
 mod streaming;
+mod translate;
 mod utils;
 
+pub use stored_responses::spawn_stored_responses_purge;
+
 use axum::http::header;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -14,7 +19,10 @@ use uuid::Uuid;
 use crate::{
     db::types::ApiType,
     error::{AppError, AppResult},
-    services::{background::BackgroundTasks, providers},
+    services::{
+        background::BackgroundTasks, budgets::BudgetSpend, circuit_breaker::CircuitBreakers,
+        metrics::Metrics, providers, rate_limit::RateLimiter, routing::TargetSelector,
+    },
 };
 
 #[derive(Debug, Deserialize)]
@@ -39,14 +47,36 @@ pub struct OpenAiService {
     pool: PgPool,
     http_client: Client,
     background_tasks: BackgroundTasks,
+    target_selector: TargetSelector,
+    circuit_breakers: CircuitBreakers,
+    rate_limiter: RateLimiter,
+    budget_spend: BudgetSpend,
+    metrics: Metrics,
+    store_responses: bool,
 }
 
 impl OpenAiService {
-    pub fn new(pool: PgPool, http_client: Client, background_tasks: BackgroundTasks) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pool: PgPool,
+        http_client: Client,
+        background_tasks: BackgroundTasks,
+        circuit_breakers: CircuitBreakers,
+        rate_limiter: RateLimiter,
+        budget_spend: BudgetSpend,
+        metrics: Metrics,
+        store_responses: bool,
+    ) -> Self {
         Self {
             pool,
             http_client,
             background_tasks,
+            target_selector: TargetSelector::new(),
+            circuit_breakers,
+            rate_limiter,
+            budget_spend,
+            metrics,
+            store_responses,
         }
     }
 
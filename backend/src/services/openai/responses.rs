@@ -2,44 +2,46 @@ use std::time::Instant;
 
 use axum::{
     body::Body,
-    http::{Response, header},
+    http::{HeaderMap, Response, header},
 };
 use serde_json::Value;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use crate::{
     db::{
-        provider_keys::{self, UpdateKeyParams},
-        types::ApiType,
+        provider_keys,
+        request_logs::RequestLogContext,
+        types::{ApiType, KeyDisabledReason},
     },
     error::{AppError, AppResult},
     middleware::{auth::GatewayKeyId, request_log::ClientInfo},
-    services::{logging, providers, routing},
+    services::{
+        circuit_breaker::AttemptOutcome,
+        key_cooldown, logging, providers,
+        rate_limit::{self, TokenReservation},
+        routing::{self, Route},
+    },
     utils::extract_model_from_payload,
 };
 
 use super::{
-    OpenAiService, streaming,
-    utils::{RequestContext, extract_usage},
+    OpenAiService,
+    gemini::{GeminiAlt, GeminiTarget},
+    stored_responses, streaming,
+    translate::Translation,
+    utils::{RequestContext, Usage, extract_usage},
 };
 
-fn prepare_request_bodies(payload: Value, routed_model_id: &str, extra_fields: &Value) -> AppResult<(Vec<u8>, bool)> {
-    let mut upstream_payload = payload;
-    let payload_object = upstream_payload
-        .as_object_mut()
-        .ok_or_else(|| AppError::BadRequest("payload must be a JSON object".to_string()))?;
-    payload_object.insert(
-        "model".to_string(),
-        Value::String(routed_model_id.to_string()),
-    );
-    
-    if let Some(obj) = extra_fields.as_object() {
-        for (k, v) in obj {
-            payload_object.insert(k.clone(), v.clone());
-        }
-    }
-    
-    let stream = payload_object
+fn prepare_request_bodies(
+    payload: Value,
+    routed_model_id: &str,
+    extra_fields: &Value,
+) -> AppResult<(Vec<u8>, bool)> {
+    let mut upstream_payload = with_routed_model(payload, routed_model_id)?;
+    merge_extra_fields(&mut upstream_payload, extra_fields);
+
+    let stream = upstream_payload
         .get("stream")
         .and_then(Value::as_bool)
         .unwrap_or(false);
@@ -50,6 +52,94 @@ fn prepare_request_bodies(payload: Value, routed_model_id: &str, extra_fields: &
     Ok((upstream_request_body, stream))
 }
 
+/// Builds the upstream body for a target speaking a different API type.
+/// Extra fields are merged after translation since they are written in the
+/// upstream's format.
+fn prepare_translated_body(
+    payload: Value,
+    routed_model_id: &str,
+    extra_fields: &Value,
+    translation: Translation,
+) -> AppResult<Vec<u8>> {
+    let mut upstream_payload = translation.request(with_routed_model(payload, routed_model_id)?)?;
+    merge_extra_fields(&mut upstream_payload, extra_fields);
+
+    serde_json::to_vec(&upstream_payload).map_err(|err| AppError::Internal(err.into()))
+}
+
+/// Gemini rejects unknown fields, so the body gets no `model`; the model
+/// travels in the URL instead.
+fn prepare_gemini_body(payload: Value, extra_fields: &Value) -> AppResult<Vec<u8>> {
+    let mut upstream_payload = payload;
+    merge_extra_fields(&mut upstream_payload, extra_fields);
+
+    serde_json::to_vec(&upstream_payload).map_err(|err| AppError::Internal(err.into()))
+}
+
+/// Gemini endpoints are configured as the `.../models` base URL; the model,
+/// method and the client's `alt` are appended per request.
+fn upstream_url(route: &Route, stream: bool, alt: Option<GeminiAlt>) -> String {
+    if route.upstream_api_type != ApiType::GeminiGenerateContent {
+        return route.endpoint_url.clone();
+    }
+    let method = if stream {
+        "streamGenerateContent"
+    } else {
+        "generateContent"
+    };
+    let mut url = format!(
+        "{}/{}:{method}",
+        route.endpoint_url.trim_end_matches('/'),
+        route.model_id
+    );
+    if let Some(alt) = alt {
+        url.push_str("?alt=");
+        url.push_str(alt.as_str());
+    }
+    url
+}
+
+fn with_routed_model(mut payload: Value, routed_model_id: &str) -> AppResult<Value> {
+    payload
+        .as_object_mut()
+        .ok_or_else(|| AppError::BadRequest("payload must be a JSON object".to_string()))?
+        .insert(
+            "model".to_string(),
+            Value::String(routed_model_id.to_string()),
+        );
+    Ok(payload)
+}
+
+fn merge_extra_fields(payload: &mut Value, extra_fields: &Value) {
+    if let (Some(payload_object), Some(obj)) = (payload.as_object_mut(), extra_fields.as_object()) {
+        for (k, v) in obj {
+            payload_object.insert(k.clone(), v.clone());
+        }
+    }
+}
+
+/// Failed requests give their reserved tokens back; successful ones are
+/// charged what the upstream reported.
+fn settle_tokens(
+    token_reservation: Option<TokenReservation>,
+    status: reqwest::StatusCode,
+    usage: Usage,
+) {
+    let Some(token_reservation) = token_reservation else {
+        return;
+    };
+    if !status.is_success() {
+        return;
+    }
+    let reported = usage
+        .total_tokens
+        .or(match (usage.prompt_tokens, usage.completion_tokens) {
+            (None, None) => None,
+            (prompt, completion) => Some(prompt.unwrap_or(0) + completion.unwrap_or(0)),
+        });
+    token_reservation.settle(reported);
+}
+
 impl OpenAiService {
     pub async fn responses(
         &self,
@@ -62,6 +152,7 @@ impl OpenAiService {
             payload,
             client_info,
             ApiType::OpenAiResponses,
+            None,
         )
         .await
     }
@@ -77,6 +168,7 @@ impl OpenAiService {
             payload,
             client_info,
             ApiType::AnthropicMessages,
+            None,
         )
         .await
     }
@@ -87,6 +179,7 @@ impl OpenAiService {
         payload: Value,
         client_info: ClientInfo,
         api_type: ApiType,
+        gemini_target: Option<GeminiTarget>,
     ) -> AppResult<Response<Body>> {
         let request_id = Uuid::now_v7();
         let start = Instant::now();
@@ -94,72 +187,256 @@ impl OpenAiService {
         let ClientInfo {
             client_ip,
             user_agent,
+            request_body_hash,
         } = client_info;
 
         tracing::debug!(%request_id, %api_type, "received request");
 
-        let model = extract_model_from_payload(&payload)?;
+        let model = match &gemini_target {
+            Some(target) => target.model.clone(),
+            None => extract_model_from_payload(&payload)?,
+        };
 
         tracing::debug!(%model, "extracted model from payload");
 
-        let route = routing::resolve_route(&self.pool, &model, api_type).await?;
+        let routes = routing::resolve_routes(
+            &self.pool,
+            &self.target_selector,
+            &self.circuit_breakers,
+            &model,
+            api_type,
+        )
+        .await?;
+        let limits = rate_limit::fetch_limits(&self.pool, gateway_key_id.0).await?;
+        let mut token_reservation = self
+            .rate_limiter
+            .reserve_tokens(
+                gateway_key_id.0,
+                &limits,
+                rate_limit::estimate_prompt_tokens(&payload),
+            )
+            .await
+            .map_err(|limited| {
+                self.metrics.record_rate_limit_rejection(limited.kind);
+                AppError::TooManyRequests(limited)
+            })?;
+        let history = if api_type == ApiType::OpenAiResponses && self.store_responses {
+            stored_responses::load_history(&self.pool, gateway_key_id.0, &payload).await?
+        } else {
+            None
+        };
 
-        let pool = self.pool.clone();
-        let provider_id = route.provider_id;
-        let shutdown_token = self.background_tasks.token();
-        self.background_tasks
-            .spawn("providers.increment_usage_count", async move {
-                if shutdown_token.is_cancelled() {
-                    return;
+        let mut last_failure = None;
+        for (attempt, route) in routes.into_iter().enumerate() {
+            if !self
+                .circuit_breakers
+                .try_acquire(route.provider_endpoint_id, route.provider_key.id)
+            {
+                tracing::debug!(
+                    provider = %route.provider_name,
+                    attempt,
+                    "circuit breaker rejected attempt, skipping target"
+                );
+                continue;
+            }
+            self.spawn_usage_counters(&route);
+
+            let (request_body, stream) = match &gemini_target {
+                Some(target) => (
+                    serde_json::to_vec(&payload).map_err(|err| AppError::Internal(err.into()))?,
+                    target.stream,
+                ),
+                None => {
+                    prepare_request_bodies(payload.clone(), &route.model_id, &route.extra_fields)?
                 }
-                let _ = providers::increment_usage_count(&pool, provider_id).await;
-            });
+            };
+            let translation = Translation::between(api_type, route.upstream_api_type);
+            let mut stored_input = None;
+            let upstream_request_body = match translation {
+                Some(translation) => {
+                    let mut translated_payload = payload.clone();
+                    if translation == Translation::ResponsesViaChat {
+                        (translated_payload, stored_input) =
+                            stored_responses::prepare_emulated_request(
+                                translated_payload,
+                                history.as_deref(),
+                                self.store_responses,
+                            )?;
+                    }
+                    prepare_translated_body(
+                        translated_payload,
+                        &route.model_id,
+                        &route.extra_fields,
+                        translation,
+                    )?
+                }
+                None if route.upstream_api_type == ApiType::GeminiGenerateContent => {
+                    prepare_gemini_body(payload.clone(), &route.extra_fields)?
+                }
+                None => request_body.clone(),
+            };
+
+            tracing::debug!(stream, "processing stream option");
+
+            let alt = gemini_target.as_ref().and_then(|target| target.alt);
+            let url = upstream_url(&route, stream, alt);
+            tracing::debug!(%url, attempt, "target endpoint url");
+
+            let request_context = RequestContext {
+                request_id: Uuid::now_v7(),
+                parent_request_id: request_id,
+                gateway_key_id,
+                api_type,
+                model: route.model_id.clone(),
+                alias: route.alias_name.clone(),
+                provider: route.provider_name.clone(),
+                endpoint: url.clone(),
+                start,
+                client_ip: client_ip.clone(),
+                user_agent: user_agent.clone(),
+                request_body,
+                stream,
+                request_body_hash,
+            };
+
+            tracing::debug!("sending request to upstream provider");
+            let attempt_start = Instant::now();
+            let response = self
+                .send_upstream(&route, &url, upstream_request_body)
+                .await;
+
+            let response = match response {
+                Ok(response) => {
+                    tracing::debug!(status = ?response.status(), "received upstream response");
+
+                    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+                        self.spawn_disable_key(&route);
+                    }
 
-        let pool = self.pool.clone();
-        let key_id = route.provider_key.id;
-        let shutdown_token = self.background_tasks.token();
-        self.background_tasks
-            .spawn("provider_keys.increment_usage_count", async move {
-                if shutdown_token.is_cancelled() {
-                    return;
+                    response
                 }
-                let _ = provider_keys::increment_usage_count(&pool, key_id).await;
-            });
+                Err(err) => {
+                    tracing::debug!(error = %err, "upstream request failed");
+                    self.circuit_breakers.record(
+                        route.provider_endpoint_id,
+                        route.provider_key.id,
+                        AttemptOutcome::EndpointFailure,
+                    );
+                    let context = request_context.build_log_context(
+                        None,
+                        Some(err.to_string().into_bytes()),
+                        Some("text/plain".to_string()),
+                        Usage::default(),
+                    );
+                    self.spawn_request_log("request_log.record_failure", context);
+                    if let Some(alias_target_id) = route.alias_target_id {
+                        self.target_selector.record_failure(alias_target_id);
+                    }
+
+                    tracing::warn!(
+                        error = %err,
+                        provider = %route.provider_name,
+                        attempt,
+                        "upstream request failed, failing over to next target"
+                    );
+                    last_failure = Some(FailedAttempt::Error(err));
+                    continue;
+                }
+            };
 
-        let (upstream_request_body, stream) = prepare_request_bodies(payload, &route.model_id, &route.extra_fields)?;
+            let status = response.status();
+            self.circuit_breakers.record(
+                route.provider_endpoint_id,
+                route.provider_key.id,
+                attempt_outcome(status),
+            );
+            if is_retryable_status(status) {
+                let headers = response.headers().clone();
+                let response_content_type = headers
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|s| s.to_string());
+                let response_body = response.bytes().await.ok().map(|bytes| bytes.to_vec());
+                self.spawn_key_cooldown(&route, status, &headers, response_body.as_deref());
+                last_failure = Some(FailedAttempt::response(
+                    status,
+                    headers.get(header::CONTENT_TYPE).cloned(),
+                    response_body.as_deref().unwrap_or_default(),
+                    translation,
+                ));
+                let context = request_context.build_log_context(
+                    Some(status.as_u16() as i32),
+                    response_body,
+                    response_content_type,
+                    Usage::default(),
+                );
+                self.spawn_request_log("request_log.record_failover", context);
+                if let Some(alias_target_id) = route.alias_target_id {
+                    self.target_selector.record_failure(alias_target_id);
+                }
+
+                tracing::warn!(
+                    %status,
+                    provider = %route.provider_name,
+                    attempt,
+                    "upstream returned retryable status, failing over to next target"
+                );
+                continue;
+            }
 
-        tracing::debug!(stream, "processing stream option");
+            if status.is_success()
+                && let Some(alias_target_id) = route.alias_target_id
+            {
+                self.target_selector
+                    .record_latency(alias_target_id, attempt_start.elapsed());
+            }
 
-        let url = route.endpoint_url;
-        tracing::debug!(%url, "target endpoint url");
+            return self
+                .forward_response(
+                    response,
+                    stream,
+                    translation,
+                    stored_input,
+                    request_context,
+                    token_reservation.take(),
+                )
+                .await;
+        }
 
-        let request_context = RequestContext {
-            request_id,
-            gateway_key_id,
-            api_type,
-            model: route.model_id.clone(),
-            alias: route.alias_name.clone(),
-            provider: route.provider_name.clone(),
-            endpoint: url.clone(),
-            start,
-            client_ip: client_ip.clone(),
-            user_agent: user_agent.clone(),
-            request_body: upstream_request_body.clone(),
-        };
+        match last_failure {
+            Some(FailedAttempt::Response {
+                status,
+                content_type,
+                body,
+            }) => streaming::build_buffered_response(status, content_type, body),
+            Some(FailedAttempt::Error(err)) => Err(upstream_transport_error(&err)),
+            None => Err(AppError::ServiceUnavailable(
+                "no upstream route available".to_string(),
+            )),
+        }
+    }
 
-        tracing::debug!("sending request to upstream provider");
+    async fn send_upstream(
+        &self,
+        route: &Route,
+        url: &str,
+        upstream_request_body: Vec<u8>,
+    ) -> reqwest::Result<reqwest::Response> {
         let mut request_builder = self
             .http_client
-            .post(&url)
+            .post(url)
             .header(header::CONTENT_TYPE, "application/json")
-            .body(upstream_request_body.clone());
+            .body(upstream_request_body);
 
-        match api_type {
+        match route.upstream_api_type {
             ApiType::AnthropicMessages => {
                 request_builder = request_builder
                     .header("x-api-key", &route.provider_key.key)
                     .header("anthropic-version", "2023-06-01");
             }
+            ApiType::GeminiGenerateContent => {
+                request_builder = request_builder.header("x-goog-api-key", &route.provider_key.key);
+            }
             _ => {
                 request_builder = request_builder.header(
                     header::AUTHORIZATION,
@@ -168,103 +445,95 @@ impl OpenAiService {
             }
         }
 
-        let response = request_builder.send().await;
-
-        let response = match response {
-            Ok(response) => {
-                tracing::debug!(status = ?response.status(), "received upstream response");
-
-                if response.status() == reqwest::StatusCode::UNAUTHORIZED {
-                    tracing::warn!(
-                        provider_key_id = %route.provider_key.id,
-                        provider_id = %route.provider_id,
-                        "upstream returned 401 Unauthorized, disabling provider key"
-                    );
-                    let pool = self.pool.clone();
-                    let key_id = route.provider_key.id;
-                    let shutdown_token = self.background_tasks.token();
-                    self.background_tasks.spawn("provider_keys.disable_key", async move {
-                        if shutdown_token.is_cancelled() {
-                            return;
-                        }
-                        if let Err(e) = provider_keys::update_key(
-                            &pool,
-                            key_id,
-                            UpdateKeyParams {
-                                name: None,
-                                enabled: Some(false),
-                            },
-                        )
-                        .await
-                        {
-                            tracing::error!(error = %e, key_id = %key_id, "failed to disable provider key");
-                        }
-                    });
-                }
-
-                response
-            }
-            Err(err) => {
-                tracing::debug!(error = %err, "upstream request failed");
-                let context = request_context.build_log_context(
-                    None,
-                    Some(err.to_string().into_bytes()),
-                    Some("text/plain".to_string()),
-                    None,
-                    None,
-                    None,
-                );
-                let pool = self.pool.clone();
-                let shutdown_token = self.background_tasks.token();
-                self.background_tasks
-                    .spawn("request_log.record_failure", async move {
-                        if shutdown_token.is_cancelled() {
-                            return;
-                        }
-                        if let Err(log_err) = logging::record_request(&pool, &context).await {
-                            tracing::error!(error = %log_err, "failed to record request log");
-                        }
-                    });
-                return Err(AppError::Internal(err.into()));
-            }
-        };
+        request_builder.send().await
+    }
 
+    async fn forward_response(
+        &self,
+        response: reqwest::Response,
+        stream: bool,
+        translation: Option<Translation>,
+        stored_input: Option<Vec<Value>>,
+        request_context: RequestContext,
+        token_reservation: Option<TokenReservation>,
+    ) -> AppResult<Response<Body>> {
         let status = response.status();
-        let content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+        let stored_input = stored_input.filter(|_| status.is_success());
+        let mut content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+        if translation.is_some() {
+            content_type = Some(header::HeaderValue::from_static(
+                if stream && status.is_success() {
+                    "text/event-stream"
+                } else {
+                    "application/json"
+                },
+            ));
+        }
         let response_content_type = content_type
             .as_ref()
             .and_then(|v| v.to_str().ok())
             .map(|s| s.to_string());
 
-        let response = if stream {
-            let (response, response_body_rx) =
-                streaming::build_streaming_response(response, status, content_type.clone())?;
+        let response = if stream && (translation.is_none() || status.is_success()) {
+            let (response, response_body_rx) = match translation {
+                Some(translation) => streaming::build_translated_streaming_response(
+                    response,
+                    status,
+                    translation.stream(),
+                    self.metrics.track_stream(),
+                )?,
+                None => streaming::build_streaming_response(
+                    response,
+                    status,
+                    content_type.clone(),
+                    self.metrics.track_stream(),
+                )?,
+            };
 
-            let request_context = request_context.clone();
             let pool = self.pool.clone();
+            let metrics = self.metrics.clone();
+            let budget_spend = self.budget_spend.clone();
             let shutdown_token = self.background_tasks.token();
             self.background_tasks
                 .spawn("request_log.record_stream_response", async move {
                     if shutdown_token.is_cancelled() {
                         return;
                     }
-                    let response_body = response_body_rx.await.ok();
-                    let (prompt_tokens, completion_tokens, total_tokens) =
-                        if let Some(body) = &response_body {
-                            extract_usage(body, request_context.api_type)
-                        } else {
-                            (None, None, None)
-                        };
+                    let (response_body, first_chunk_at) = match response_body_rx.await {
+                        Ok(streamed) => (Some(streamed.body), streamed.first_chunk_at),
+                        Err(_) => (None, None),
+                    };
+                    let usage = response_body
+                        .as_deref()
+                        .map(|body| extract_usage(body, request_context.api_type))
+                        .unwrap_or_default();
+                    settle_tokens(token_reservation, status, usage);
+                    if let (Some(input), Some(body)) = (stored_input, &response_body)
+                        && let Err(err) = stored_responses::record(
+                            &pool,
+                            request_context.gateway_key_id.0,
+                            request_context.alias.clone(),
+                            input,
+                            body,
+                        )
+                        .await
+                    {
+                        tracing::error!(error = %err, "failed to store emulated response");
+                    }
 
-                    let context = request_context.build_log_context(
+                    let mut context = request_context.build_log_context(
                         Some(status.as_u16() as i32),
                         response_body,
                         response_content_type,
-                        prompt_tokens,
-                        completion_tokens,
-                        total_tokens,
+                        usage,
                     );
-                    if let Err(err) = logging::record_request(&pool, &context).await {
+                    context.ttft_ms = first_chunk_at.map(|at| {
+                        i32::try_from(at.duration_since(request_context.start).as_millis())
+                            .unwrap_or(i32::MAX)
+                    });
+                    metrics.record_request(&context);
+                    if let Err(err) = logging::record_request(&pool, &budget_spend, &context).await
+                    {
                         tracing::error!(error = %err, "failed to record request log");
                     }
                 });
@@ -275,9 +544,13 @@ impl OpenAiService {
                 .bytes()
                 .await
                 .map_err(|err| AppError::Internal(err.into()))?;
-            let response_body = bytes.to_vec();
-            let (prompt_tokens, completion_tokens, total_tokens) =
-                extract_usage(&response_body, request_context.api_type);
+            let response_body = match translation {
+                Some(translation) if status.is_success() => translation.response(&bytes)?,
+                Some(translation) => translation.error(status, &bytes),
+                None => bytes.to_vec(),
+            };
+            let usage = extract_usage(&response_body, request_context.api_type);
+            settle_tokens(token_reservation, status, usage);
 
             let response = streaming::build_buffered_response(
                 status,
@@ -285,38 +558,220 @@ impl OpenAiService {
                 response_body.clone(),
             )?;
 
+            if let Some(input) = stored_input {
+                self.spawn_store_response(&request_context, input, response_body.clone());
+            }
             let context = request_context.build_log_context(
                 Some(status.as_u16() as i32),
                 Some(response_body),
                 response_content_type,
-                prompt_tokens,
-                completion_tokens,
-                total_tokens,
+                usage,
             );
-            let pool = self.pool.clone();
-            let shutdown_token = self.background_tasks.token();
-            self.background_tasks
-                .spawn("request_log.record_response", async move {
-                    if shutdown_token.is_cancelled() {
-                        return;
-                    }
-                    if let Err(err) = logging::record_request(&pool, &context).await {
-                        tracing::error!(error = %err, "failed to record request log");
-                    }
-                });
+            self.spawn_request_log("request_log.record_response", context);
 
             response
         };
 
         Ok(response)
     }
+
+    fn spawn_request_log(&self, task_name: &'static str, context: RequestLogContext) {
+        self.metrics.record_request(&context);
+        let pool = self.pool.clone();
+        let budget_spend = self.budget_spend.clone();
+        let shutdown_token = self.background_tasks.token();
+        self.background_tasks.spawn(task_name, async move {
+            if shutdown_token.is_cancelled() {
+                return;
+            }
+            if let Err(err) = logging::record_request(&pool, &budget_spend, &context).await {
+                tracing::error!(error = %err, "failed to record request log");
+            }
+        });
+    }
+
+    fn spawn_store_response(
+        &self,
+        request_context: &RequestContext,
+        input: Vec<Value>,
+        response_body: Vec<u8>,
+    ) {
+        let pool = self.pool.clone();
+        let gateway_key_id = request_context.gateway_key_id.0;
+        let model = request_context.alias.clone();
+        let shutdown_token = self.background_tasks.token();
+        self.background_tasks
+            .spawn("stored_responses.record", async move {
+                if shutdown_token.is_cancelled() {
+                    return;
+                }
+                if let Err(err) =
+                    stored_responses::record(&pool, gateway_key_id, model, input, &response_body)
+                        .await
+                {
+                    tracing::error!(error = %err, "failed to store emulated response");
+                }
+            });
+    }
+
+    fn spawn_usage_counters(&self, route: &Route) {
+        let pool = self.pool.clone();
+        let provider_id = route.provider_id;
+        let shutdown_token = self.background_tasks.token();
+        self.background_tasks
+            .spawn("providers.increment_usage_count", async move {
+                if shutdown_token.is_cancelled() {
+                    return;
+                }
+                let _ = providers::increment_usage_count(&pool, provider_id).await;
+            });
+
+        let pool = self.pool.clone();
+        let key_id = route.provider_key.id;
+        let shutdown_token = self.background_tasks.token();
+        self.background_tasks
+            .spawn("provider_keys.increment_usage_count", async move {
+                if shutdown_token.is_cancelled() {
+                    return;
+                }
+                let _ = provider_keys::increment_usage_count(&pool, key_id).await;
+            });
+    }
+
+    fn spawn_key_cooldown(
+        &self,
+        route: &Route,
+        status: reqwest::StatusCode,
+        headers: &HeaderMap,
+        body: Option<&[u8]>,
+    ) {
+        let Some(cooldown) = key_cooldown::cooldown_for_response(status, headers, body) else {
+            return;
+        };
+        tracing::warn!(
+            provider_key_id = %route.provider_key.id,
+            provider_id = %route.provider_id,
+            %status,
+            cooldown_secs = cooldown.as_secs(),
+            "upstream rejected provider key, cooling it down"
+        );
+        let pool = self.pool.clone();
+        let key_id = route.provider_key.id;
+        let cooldown_until = OffsetDateTime::now_utc() + cooldown;
+        let shutdown_token = self.background_tasks.token();
+        self.background_tasks
+            .spawn("provider_keys.set_cooldown", async move {
+                if shutdown_token.is_cancelled() {
+                    return;
+                }
+                if let Err(e) = provider_keys::set_cooldown(&pool, key_id, cooldown_until).await {
+                    tracing::error!(error = %e, key_id = %key_id, "failed to set provider key cooldown");
+                }
+            });
+    }
+
+    fn spawn_disable_key(&self, route: &Route) {
+        tracing::warn!(
+            provider_key_id = %route.provider_key.id,
+            provider_id = %route.provider_id,
+            "upstream returned 401 Unauthorized, disabling provider key"
+        );
+        let pool = self.pool.clone();
+        let key_id = route.provider_key.id;
+        let shutdown_token = self.background_tasks.token();
+        self.background_tasks
+            .spawn("provider_keys.disable_key", async move {
+                if shutdown_token.is_cancelled() {
+                    return;
+                }
+                if let Err(e) =
+                    provider_keys::disable_key(&pool, key_id, KeyDisabledReason::Unauthorized).await
+                {
+                    tracing::error!(error = %e, key_id = %key_id, "failed to disable provider key");
+                }
+            });
+    }
+}
+
+/// Attributes an upstream status to the endpoint or the key for the circuit
+/// breakers. Client errors other than auth and rate limits are the caller's
+/// fault and count as a healthy upstream.
+fn attempt_outcome(status: reqwest::StatusCode) -> AttemptOutcome {
+    match status {
+        reqwest::StatusCode::TOO_MANY_REQUESTS
+        | reqwest::StatusCode::PAYMENT_REQUIRED
+        | reqwest::StatusCode::UNAUTHORIZED
+        | reqwest::StatusCode::FORBIDDEN => AttemptOutcome::KeyFailure,
+        reqwest::StatusCode::REQUEST_TIMEOUT => AttemptOutcome::EndpointFailure,
+        status if status.is_server_error() => AttemptOutcome::EndpointFailure,
+        _ => AttemptOutcome::Success,
+    }
+}
+
+/// The last attempt that failed over, answered to the client once no route
+/// is left to try.
+enum FailedAttempt {
+    Response {
+        status: reqwest::StatusCode,
+        content_type: Option<header::HeaderValue>,
+        body: Vec<u8>,
+    },
+    Error(reqwest::Error),
+}
+
+impl FailedAttempt {
+    /// Keeps an upstream error response in the format the client asked for.
+    fn response(
+        status: reqwest::StatusCode,
+        content_type: Option<header::HeaderValue>,
+        body: &[u8],
+        translation: Option<Translation>,
+    ) -> Self {
+        match translation {
+            Some(translation) => Self::Response {
+                status,
+                content_type: Some(header::HeaderValue::from_static("application/json")),
+                body: translation.error(status, body),
+            },
+            None => Self::Response {
+                status,
+                content_type,
+                body: body.to_vec(),
+            },
+        }
+    }
+}
+
+/// Transport failures are the upstream's, so the client gets a gateway
+/// status rather than an internal error.
+fn upstream_transport_error(err: &reqwest::Error) -> AppError {
+    if err.is_timeout() {
+        AppError::GatewayTimeout("upstream did not respond in time".to_string())
+    } else if err.is_connect() {
+        AppError::BadGateway("could not connect to upstream".to_string())
+    } else {
+        AppError::BadGateway("upstream request failed".to_string())
+    }
+}
+
+/// Statuses worth retrying on another target: the upstream is overloaded,
+/// rate limited, out of quota or broken, so a different provider or key may
+/// succeed.
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::PAYMENT_REQUIRED
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+        || status.is_server_error()
 }
 
 #[cfg(test)]
 mod tests {
     use serde_json::{Value, json};
 
-    use super::prepare_request_bodies;
+    use std::{net::TcpListener, time::Duration};
+
+    use super::{is_retryable_status, prepare_request_bodies, upstream_transport_error};
+    use crate::error::AppError;
 
     #[test]
     fn prepare_request_bodies_rewrites_model_for_upstream_body() {
@@ -327,7 +782,8 @@ mod tests {
         });
 
         let (upstream_request_body, stream) =
-            prepare_request_bodies(payload, "provider-model", &json!({})).expect("request body should build");
+            prepare_request_bodies(payload, "provider-model", &json!({}))
+                .expect("request body should build");
 
         let upstream_json: Value = serde_json::from_slice(&upstream_request_body)
             .expect("upstream body should be valid JSON");
@@ -352,7 +808,8 @@ mod tests {
         });
 
         let (upstream_request_body, _) =
-            prepare_request_bodies(payload, "provider-model", &extra_fields).expect("request body should build");
+            prepare_request_bodies(payload, "provider-model", &extra_fields)
+                .expect("request body should build");
 
         let upstream_json: Value = serde_json::from_slice(&upstream_request_body)
             .expect("upstream body should be valid JSON");
@@ -361,8 +818,16 @@ mod tests {
             upstream_json.get("model").and_then(Value::as_str),
             Some("provider-model")
         );
-        assert_eq!(upstream_json.get("enable_thinking").and_then(Value::as_bool), Some(true));
-        assert_eq!(upstream_json.get("temperature").and_then(Value::as_f64), Some(0.7));
+        assert_eq!(
+            upstream_json
+                .get("enable_thinking")
+                .and_then(Value::as_bool),
+            Some(true)
+        );
+        assert_eq!(
+            upstream_json.get("temperature").and_then(Value::as_f64),
+            Some(0.7)
+        );
     }
 
     #[test]
@@ -378,12 +843,59 @@ mod tests {
         });
 
         let (upstream_request_body, _) =
-            prepare_request_bodies(payload, "provider-model", &extra_fields).expect("request body should build");
+            prepare_request_bodies(payload, "provider-model", &extra_fields)
+                .expect("request body should build");
 
         let upstream_json: Value = serde_json::from_slice(&upstream_request_body)
             .expect("upstream body should be valid JSON");
 
-        assert_eq!(upstream_json.get("enable_thinking").and_then(Value::as_bool), Some(true));
-        assert_eq!(upstream_json.get("temperature").and_then(Value::as_f64), Some(0.9));
+        assert_eq!(
+            upstream_json
+                .get("enable_thinking")
+                .and_then(Value::as_bool),
+            Some(true)
+        );
+        assert_eq!(
+            upstream_json.get("temperature").and_then(Value::as_f64),
+            Some(0.9)
+        );
+    }
+
+    #[test]
+    fn is_retryable_status_covers_rate_limits_and_server_errors() {
+        assert!(is_retryable_status(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(reqwest::StatusCode::PAYMENT_REQUIRED));
+        assert!(is_retryable_status(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(
+            reqwest::StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert!(!is_retryable_status(reqwest::StatusCode::BAD_REQUEST));
+        assert!(!is_retryable_status(reqwest::StatusCode::OK));
+    }
+
+    #[tokio::test]
+    async fn exhausted_transport_failures_map_to_gateway_errors() {
+        let client = reqwest::Client::new();
+
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_url = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let err = client.get(&closed_url).send().await.unwrap_err();
+        assert!(matches!(
+            upstream_transport_error(&err),
+            AppError::BadGateway(_)
+        ));
+
+        let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+        let err = client
+            .get(format!("http://{}", silent.local_addr().unwrap()))
+            .timeout(Duration::from_millis(50))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            upstream_transport_error(&err),
+            AppError::GatewayTimeout(_)
+        ));
     }
 }
@@ -0,0 +1,200 @@
+//! Server-side conversation state for Responses requests emulated over chat
+//! completions, so clients can continue them with `previous_response_id`.
+//! Native Responses upstreams keep their own state and are not involved.
+
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use tokio::time::{Instant, MissedTickBehavior};
+use uuid::Uuid;
+
+use crate::{
+    constants::STORED_RESPONSES_PURGE_INTERVAL_SECS,
+    db::stored_responses::{self, NewStoredResponse},
+    error::{AppError, AppResult},
+    services::background::BackgroundTasks,
+    utils::sse::SseParser,
+};
+
+/// Starts the periodic job that deletes stored responses older than
+/// `retention`, whether or not storing is enabled.
+pub fn spawn_stored_responses_purge(
+    pool: PgPool,
+    retention: Duration,
+    background_tasks: &BackgroundTasks,
+) {
+    let shutdown_token = background_tasks.token();
+    background_tasks.spawn("stored_responses.purge", async move {
+        let period = Duration::from_secs(STORED_RESPONSES_PURGE_INTERVAL_SECS);
+        let mut interval = tokio::time::interval_at(Instant::now(), period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => return,
+                _ = interval.tick() => {}
+            }
+
+            match stored_responses::delete_stored_responses_older_than(
+                &pool,
+                retention.as_secs_f64(),
+            )
+            .await
+            {
+                Ok(0) => {}
+                Ok(deleted) => tracing::info!(deleted, "purged expired stored responses"),
+                Err(err) => tracing::error!(error = %err, "failed to purge stored responses"),
+            }
+        }
+    });
+}
+
+/// Input items a continued request starts from: the previous request's input
+/// followed by the previous response's output.
+pub(super) async fn load_history(
+    pool: &PgPool,
+    gateway_key_id: Uuid,
+    payload: &Value,
+) -> AppResult<Option<Vec<Value>>> {
+    let Some(previous_response_id) = payload.get("previous_response_id").and_then(Value::as_str)
+    else {
+        return Ok(None);
+    };
+    let Some(previous) =
+        stored_responses::fetch_stored_response(pool, previous_response_id, gateway_key_id).await?
+    else {
+        return Ok(None);
+    };
+
+    let mut history = into_items(previous.input);
+    history.extend(into_items(previous.output));
+    Ok(Some(history))
+}
+
+/// Rewrites a Responses request for emulation: a `previous_response_id` is
+/// replaced by the stored history. Returns the request together with the
+/// full input to store with the response, when it should be stored.
+pub(super) fn prepare_emulated_request(
+    mut payload: Value,
+    history: Option<&[Value]>,
+    store_enabled: bool,
+) -> AppResult<(Value, Option<Vec<Value>>)> {
+    let payload_object = payload
+        .as_object_mut()
+        .ok_or_else(|| AppError::BadRequest("payload must be a JSON object".to_string()))?;
+
+    let mut input = input_items(payload_object.get("input"));
+    if let Some(previous_response_id) = payload_object.remove("previous_response_id") {
+        let Some(history) = history else {
+            return Err(AppError::BadRequest(format!(
+                "previous response {previous_response_id} not found"
+            )));
+        };
+        input.splice(0..0, history.iter().cloned());
+        payload_object.insert("input".to_string(), Value::Array(input.clone()));
+    }
+
+    let store = store_enabled
+        && payload_object
+            .get("store")
+            .and_then(Value::as_bool)
+            .unwrap_or(true);
+
+    Ok((payload, store.then_some(input)))
+}
+
+pub(super) async fn record(
+    pool: &PgPool,
+    gateway_key_id: Uuid,
+    model: String,
+    input: Vec<Value>,
+    response_body: &[u8],
+) -> AppResult<()> {
+    let Some(response) = final_response(response_body) else {
+        tracing::warn!("emulated response has no final response object, not storing it");
+        return Ok(());
+    };
+    let Some(id) = response.get("id").and_then(Value::as_str) else {
+        return Ok(());
+    };
+
+    stored_responses::insert_stored_response(
+        pool,
+        &NewStoredResponse {
+            id: id.to_string(),
+            gateway_key_id,
+            model,
+            input: Value::Array(input),
+            output: response.get("output").cloned().unwrap_or_else(|| json!([])),
+        },
+    )
+    .await
+}
+
+fn input_items(input: Option<&Value>) -> Vec<Value> {
+    match input {
+        Some(Value::String(text)) => vec![json!({
+            "type": "message",
+            "role": "user",
+            "content": text,
+        })],
+        Some(Value::Array(items)) => items.clone(),
+        _ => Vec::new(),
+    }
+}
+
+fn into_items(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items,
+        _ => Vec::new(),
+    }
+}
+
+/// The response object from a buffered body, or from the terminal event of a
+/// streamed one.
+fn final_response(body: &[u8]) -> Option<Value> {
+    if let Ok(response) = serde_json::from_slice::<Value>(body) {
+        return Some(response);
+    }
+
+    let mut parser = SseParser::new();
+    let mut events = parser.push(body);
+    events.extend(parser.finish());
+    events.into_iter().rev().find_map(|event| {
+        let data: Value = serde_json::from_str(&event.data).ok()?;
+        match data.get("type").and_then(Value::as_str)? {
+            "response.completed" | "response.incomplete" => data.get("response").cloned(),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepare_emulated_request_prepends_history() {
+        let payload = json!({
+            "model": "alias",
+            "previous_response_id": "resp_1",
+            "input": "and tomorrow?"
+        });
+        let history = [
+            json!({ "type": "message", "role": "user", "content": "weather?" }),
+            json!({ "type": "message", "role": "assistant", "content": "sunny" }),
+        ];
+
+        let (payload, stored_input) =
+            prepare_emulated_request(payload, Some(&history), true).unwrap();
+
+        assert!(payload.get("previous_response_id").is_none());
+        assert_eq!(payload["input"].as_array().unwrap().len(), 3);
+        assert_eq!(payload["input"][2]["content"], "and tomorrow?");
+        assert_eq!(stored_input.unwrap().len(), 3);
+
+        let unknown = json!({ "previous_response_id": "resp_2", "input": "hi" });
+        assert!(prepare_emulated_request(unknown, None, true).is_err());
+    }
+}
@@ -1,24 +1,44 @@
 use axum::{
-    body::Body,
+    body::{Body, Bytes},
     http::{Response, header},
 };
+use std::time::Instant;
+
 use futures_util::StreamExt;
 use tokio::sync::oneshot;
 
-use crate::error::{AppError, AppResult};
+use super::translate::StreamTranslator;
+use crate::{
+    error::{AppError, AppResult},
+    services::metrics::StreamGuard,
+    utils::sse::SseParser,
+};
+
+/// What a client was sent over a stream, handed over once the stream ends.
+pub(super) struct StreamedBody {
+    pub body: Vec<u8>,
+    /// When the first non-empty chunk was passed on to the client.
+    pub first_chunk_at: Option<Instant>,
+}
 
 pub(super) fn build_streaming_response(
     upstream_response: reqwest::Response,
     status: reqwest::StatusCode,
     content_type: Option<header::HeaderValue>,
-) -> AppResult<(Response<Body>, oneshot::Receiver<Vec<u8>>)> {
-    let (response_body_tx, response_body_rx) = oneshot::channel::<Vec<u8>>();
+    stream_guard: StreamGuard,
+) -> AppResult<(Response<Body>, oneshot::Receiver<StreamedBody>)> {
+    let (response_body_tx, response_body_rx) = oneshot::channel::<StreamedBody>();
     let mut stream_body = upstream_response.bytes_stream();
     let body_stream = async_stream::stream! {
+        let _stream_guard = stream_guard;
         let mut captured = Vec::new();
+        let mut first_chunk_at = None;
         while let Some(next) = stream_body.next().await {
             match next {
                 Ok(chunk) => {
+                    if !chunk.is_empty() {
+                        first_chunk_at.get_or_insert_with(Instant::now);
+                    }
                     captured.extend_from_slice(&chunk);
                     yield Ok::<_, std::convert::Infallible>(chunk);
                 }
@@ -28,7 +48,10 @@ pub(super) fn build_streaming_response(
                 }
             }
         }
-        let _ = response_body_tx.send(captured);
+        let _ = response_body_tx.send(StreamedBody {
+            body: captured,
+            first_chunk_at,
+        });
     };
 
     let mut builder = Response::builder().status(status);
@@ -55,3 +78,64 @@ pub(super) fn build_buffered_response(
         .body(Body::from(body))
         .map_err(|err| AppError::Internal(err.into()))
 }
+
+/// Streams an upstream SSE response through a protocol translator. The
+/// captured body is the translated stream, as the client saw it.
+pub(super) fn build_translated_streaming_response(
+    upstream_response: reqwest::Response,
+    status: reqwest::StatusCode,
+    mut translator: Box<dyn StreamTranslator>,
+    stream_guard: StreamGuard,
+) -> AppResult<(Response<Body>, oneshot::Receiver<StreamedBody>)> {
+    let (response_body_tx, response_body_rx) = oneshot::channel::<StreamedBody>();
+    let mut stream_body = upstream_response.bytes_stream();
+    let body_stream = async_stream::stream! {
+        let _stream_guard = stream_guard;
+        let mut parser = SseParser::new();
+        let mut captured = Vec::new();
+        let mut first_chunk_at = None;
+        while let Some(next) = stream_body.next().await {
+            match next {
+                Ok(chunk) => {
+                    let translated: String = parser
+                        .push(&chunk)
+                        .iter()
+                        .map(|event| translator.translate_event(event))
+                        .collect();
+                    if translated.is_empty() {
+                        continue;
+                    }
+                    first_chunk_at.get_or_insert_with(Instant::now);
+                    captured.extend_from_slice(translated.as_bytes());
+                    yield Ok::<_, std::convert::Infallible>(Bytes::from(translated));
+                }
+                Err(err) => {
+                    tracing::error!(error = %err, "upstream stream error");
+                    break;
+                }
+            }
+        }
+        let mut translated = parser
+            .finish()
+            .map(|event| translator.translate_event(&event))
+            .unwrap_or_default();
+        translated.push_str(&translator.finish());
+        captured.extend_from_slice(translated.as_bytes());
+        if !translated.is_empty() {
+            first_chunk_at.get_or_insert_with(Instant::now);
+        }
+        yield Ok(Bytes::from(translated));
+        let _ = response_body_tx.send(StreamedBody {
+            body: captured,
+            first_chunk_at,
+        });
+    };
+
+    let response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .body(Body::from_stream(body_stream))
+        .map_err(|err| AppError::Internal(err.into()))?;
+
+    Ok((response, response_body_rx))
+}
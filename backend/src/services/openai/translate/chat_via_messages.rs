@@ -0,0 +1,616 @@
+//! OpenAI Chat Completions requests served by an Anthropic Messages upstream.
+
+use reqwest::StatusCode;
+use serde_json::{Map, Value, json};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{StreamTranslator, text_of};
+use crate::{
+    constants::ANTHROPIC_DEFAULT_MAX_TOKENS,
+    error::{AppError, AppResult},
+    utils::sse::{SseEvent, format_event},
+};
+
+const PASSTHROUGH_FIELDS: [&str; 4] = ["model", "temperature", "top_p", "stream"];
+
+pub(super) fn request(payload: Value) -> AppResult<Value> {
+    let Value::Object(mut input) = payload else {
+        return Err(AppError::BadRequest(
+            "payload must be a JSON object".to_string(),
+        ));
+    };
+    let Some(Value::Array(input_messages)) = input.remove("messages") else {
+        return Err(AppError::BadRequest(
+            "messages must be an array".to_string(),
+        ));
+    };
+
+    let mut system = Vec::new();
+    let mut messages: Vec<Value> = Vec::new();
+    for message in &input_messages {
+        let content = message.get("content").unwrap_or(&Value::Null);
+        let (role, blocks) = match message.get("role").and_then(Value::as_str) {
+            Some("system" | "developer") => {
+                system.push(text_of(content));
+                continue;
+            }
+            Some("assistant") => ("assistant", assistant_blocks(message)),
+            Some("tool") => ("user", vec![tool_result_block(message)]),
+            _ => ("user", user_blocks(content)),
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+        // Anthropic requires alternating roles, so consecutive turns from the
+        // same side (e.g. several tool results) are merged.
+        match messages.last_mut() {
+            Some(last) if last["role"] == role => {
+                if let Some(Value::Array(content)) = last.get_mut("content") {
+                    content.extend(blocks);
+                }
+            }
+            _ => messages.push(json!({ "role": role, "content": blocks })),
+        }
+    }
+
+    if let Some(instruction) = input.get("response_format").and_then(format_instruction) {
+        system.push(instruction);
+    }
+
+    let mut output = Map::new();
+    for field in PASSTHROUGH_FIELDS {
+        if let Some(value) = input.remove(field) {
+            output.insert(field.to_string(), value);
+        }
+    }
+    let max_tokens = input
+        .remove("max_completion_tokens")
+        .or_else(|| input.remove("max_tokens"))
+        .filter(|value| !value.is_null())
+        .unwrap_or_else(|| json!(ANTHROPIC_DEFAULT_MAX_TOKENS));
+    output.insert("max_tokens".to_string(), max_tokens);
+    output.insert("messages".to_string(), Value::Array(messages));
+
+    let system = system
+        .into_iter()
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !system.is_empty() {
+        output.insert("system".to_string(), Value::String(system));
+    }
+
+    match input.get("stop") {
+        Some(Value::String(stop)) => {
+            output.insert("stop_sequences".to_string(), json!([stop]));
+        }
+        Some(stop @ Value::Array(_)) => {
+            output.insert("stop_sequences".to_string(), stop.clone());
+        }
+        _ => {}
+    }
+
+    if let Some(user) = input.get("user") {
+        output.insert("metadata".to_string(), json!({ "user_id": user }));
+    }
+
+    if let Some(Value::Array(tools)) = input.get("tools") {
+        let tools: Vec<Value> = tools.iter().filter_map(tool).collect();
+        if !tools.is_empty() {
+            output.insert("tools".to_string(), Value::Array(tools));
+        }
+    }
+
+    let mut tool_choice = input.get("tool_choice").and_then(tool_choice);
+    if input.get("parallel_tool_calls").and_then(Value::as_bool) == Some(false) {
+        let choice = tool_choice.get_or_insert_with(|| json!({ "type": "auto" }));
+        choice["disable_parallel_tool_use"] = Value::Bool(true);
+    }
+    if let Some(tool_choice) = tool_choice {
+        output.insert("tool_choice".to_string(), tool_choice);
+    }
+
+    Ok(Value::Object(output))
+}
+
+fn user_blocks(content: &Value) -> Vec<Value> {
+    let Value::Array(parts) = content else {
+        let text = text_of(content);
+        if text.is_empty() {
+            return Vec::new();
+        }
+        return vec![json!({ "type": "text", "text": text })];
+    };
+
+    parts
+        .iter()
+        .filter_map(|part| match part.get("type").and_then(Value::as_str)? {
+            "text" => Some(json!({ "type": "text", "text": part.get("text")? })),
+            "image_url" => {
+                let url = part.pointer("/image_url/url").and_then(Value::as_str)?;
+                Some(json!({ "type": "image", "source": image_source(url) }))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn image_source(url: &str) -> Value {
+    if let Some((media_type, data)) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+    {
+        return json!({ "type": "base64", "media_type": media_type, "data": data });
+    }
+    json!({ "type": "url", "url": url })
+}
+
+fn assistant_blocks(message: &Value) -> Vec<Value> {
+    let mut blocks = Vec::new();
+    let text = text_of(message.get("content").unwrap_or(&Value::Null));
+    if !text.is_empty() {
+        blocks.push(json!({ "type": "text", "text": text }));
+    }
+    if let Some(Value::Array(tool_calls)) = message.get("tool_calls") {
+        for tool_call in tool_calls {
+            let function = tool_call.get("function").unwrap_or(&Value::Null);
+            let input = function
+                .get("arguments")
+                .and_then(Value::as_str)
+                .and_then(|arguments| serde_json::from_str::<Value>(arguments).ok())
+                .unwrap_or_else(|| json!({}));
+            blocks.push(json!({
+                "type": "tool_use",
+                "id": tool_call.get("id").cloned().unwrap_or_default(),
+                "name": function.get("name").cloned().unwrap_or_default(),
+                "input": input,
+            }));
+        }
+    }
+    blocks
+}
+
+fn tool_result_block(message: &Value) -> Value {
+    json!({
+        "type": "tool_result",
+        "tool_use_id": message.get("tool_call_id").cloned().unwrap_or_default(),
+        "content": text_of(message.get("content").unwrap_or(&Value::Null)),
+    })
+}
+
+/// Anthropic has no JSON mode, so the requested format is spelled out in the
+/// system prompt instead.
+fn format_instruction(response_format: &Value) -> Option<String> {
+    match response_format.get("type").and_then(Value::as_str)? {
+        "json_object" => Some("Respond only with a single valid JSON object.".to_string()),
+        "json_schema" => {
+            let schema = response_format.pointer("/json_schema/schema")?;
+            Some(format!(
+                "Respond only with a single valid JSON object matching this JSON schema:\n{schema}"
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn tool(tool: &Value) -> Option<Value> {
+    if tool.get("type").and_then(Value::as_str) != Some("function") {
+        return None;
+    }
+    let function = tool.get("function")?;
+    let mut output = json!({
+        "name": function.get("name")?,
+        "input_schema": function
+            .get("parameters")
+            .cloned()
+            .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+    });
+    if let Some(description) = function.get("description") {
+        output["description"] = description.clone();
+    }
+    Some(output)
+}
+
+fn tool_choice(choice: &Value) -> Option<Value> {
+    match choice {
+        Value::String(choice) => match choice.as_str() {
+            "auto" => Some(json!({ "type": "auto" })),
+            "required" => Some(json!({ "type": "any" })),
+            "none" => Some(json!({ "type": "none" })),
+            _ => None,
+        },
+        Value::Object(_) => Some(json!({
+            "type": "tool",
+            "name": choice.pointer("/function/name")?,
+        })),
+        _ => None,
+    }
+}
+
+pub(super) fn response(body: &[u8]) -> AppResult<Vec<u8>> {
+    let message: Value =
+        serde_json::from_slice(body).map_err(|err| AppError::Internal(err.into()))?;
+
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    if let Some(Value::Array(blocks)) = message.get("content") {
+        for block in blocks {
+            match block.get("type").and_then(Value::as_str) {
+                Some("text") => {
+                    text.push_str(block.get("text").and_then(Value::as_str).unwrap_or(""))
+                }
+                Some("tool_use") => tool_calls.push(json!({
+                    "id": block.get("id").cloned().unwrap_or_default(),
+                    "type": "function",
+                    "function": {
+                        "name": block.get("name").cloned().unwrap_or_default(),
+                        "arguments": block.get("input").cloned().unwrap_or_else(|| json!({})).to_string(),
+                    },
+                })),
+                _ => {}
+            }
+        }
+    }
+
+    let mut chat_message = json!({
+        "role": "assistant",
+        "content": if text.is_empty() && !tool_calls.is_empty() { Value::Null } else { Value::String(text) },
+    });
+    if !tool_calls.is_empty() {
+        chat_message["tool_calls"] = Value::Array(tool_calls);
+    }
+
+    let finish_reason = message
+        .get("stop_reason")
+        .and_then(Value::as_str)
+        .map(finish_reason)
+        .unwrap_or("stop");
+    let completion = json!({
+        "id": completion_id(&message),
+        "object": "chat.completion",
+        "created": OffsetDateTime::now_utc().unix_timestamp(),
+        "model": message.get("model").cloned().unwrap_or_default(),
+        "choices": [{
+            "index": 0,
+            "message": chat_message,
+            "finish_reason": finish_reason,
+        }],
+        "usage": usage(message.get("usage").unwrap_or(&Value::Null)),
+    });
+
+    serde_json::to_vec(&completion).map_err(|err| AppError::Internal(err.into()))
+}
+
+pub(super) fn error(status: StatusCode, body: &[u8]) -> Vec<u8> {
+    let parsed = serde_json::from_slice::<Value>(body).ok();
+    let error = parsed.as_ref().and_then(|value| value.get("error"));
+    let message = error
+        .and_then(|error| error.get("message"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| String::from_utf8_lossy(body).into_owned());
+    let error_type = error
+        .and_then(|error| error.get("type"))
+        .and_then(Value::as_str)
+        .unwrap_or(if status.is_client_error() {
+            "invalid_request_error"
+        } else {
+            "api_error"
+        });
+    json!({
+        "error": {
+            "message": message,
+            "type": error_type,
+            "param": null,
+            "code": null,
+        },
+    })
+    .to_string()
+    .into_bytes()
+}
+
+fn completion_id(message: &Value) -> String {
+    match message.get("id").and_then(Value::as_str) {
+        Some(id) => format!("chatcmpl-{}", id.trim_start_matches("msg_")),
+        None => format!("chatcmpl-{}", Uuid::now_v7().simple()),
+    }
+}
+
+fn finish_reason(stop_reason: &str) -> &'static str {
+    match stop_reason {
+        "max_tokens" => "length",
+        "tool_use" => "tool_calls",
+        "refusal" => "content_filter",
+        _ => "stop",
+    }
+}
+
+/// Anthropic reports cache reads and writes apart from `input_tokens`; OpenAI
+/// counts them all as prompt tokens.
+fn usage(usage: &Value) -> Value {
+    let tokens = |field: &str| usage.get(field).and_then(Value::as_i64).unwrap_or(0);
+    let cached_tokens = tokens("cache_read_input_tokens");
+    let prompt_tokens =
+        tokens("input_tokens") + cached_tokens + tokens("cache_creation_input_tokens");
+    let completion_tokens = tokens("output_tokens");
+
+    let mut value = json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+    });
+    if cached_tokens > 0 {
+        value["prompt_tokens_details"] = json!({ "cached_tokens": cached_tokens });
+    }
+    value
+}
+
+/// Turns Anthropic stream events into chat completion chunks, ending with a
+/// usage chunk and `[DONE]`.
+pub(super) struct MessagesToChatStream {
+    id: String,
+    model: Value,
+    created: i64,
+    /// Chat `tool_calls` index of each open Anthropic `tool_use` block,
+    /// keyed by content block index.
+    tool_indexes: Vec<(u64, usize)>,
+    usage: Map<String, Value>,
+    done: bool,
+}
+
+impl Default for MessagesToChatStream {
+    fn default() -> Self {
+        Self {
+            id: format!("chatcmpl-{}", Uuid::now_v7().simple()),
+            model: Value::Null,
+            created: OffsetDateTime::now_utc().unix_timestamp(),
+            tool_indexes: Vec::new(),
+            usage: Map::new(),
+            done: false,
+        }
+    }
+}
+
+impl MessagesToChatStream {
+    fn chunk(&self, delta: Value, finish_reason: Option<&str>) -> String {
+        let chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        });
+        format_event(None, &chunk.to_string())
+    }
+
+    fn tool_index(&self, block_index: u64) -> Option<usize> {
+        self.tool_indexes
+            .iter()
+            .find(|(index, _)| *index == block_index)
+            .map(|(_, tool_index)| *tool_index)
+    }
+
+    fn record_usage(&mut self, usage: Option<&Value>) {
+        if let Some(Value::Object(usage)) = usage {
+            for (field, value) in usage {
+                if !value.is_null() {
+                    self.usage.insert(field.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    fn done(&mut self) -> String {
+        if self.done {
+            return String::new();
+        }
+        self.done = true;
+        let chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [],
+            "usage": usage(&Value::Object(self.usage.clone())),
+        });
+        let mut out = format_event(None, &chunk.to_string());
+        out.push_str(&format_event(None, "[DONE]"));
+        out
+    }
+}
+
+impl StreamTranslator for MessagesToChatStream {
+    fn translate_event(&mut self, event: &SseEvent) -> String {
+        let Ok(data) = serde_json::from_str::<Value>(&event.data) else {
+            return String::new();
+        };
+        let block_index = data.get("index").and_then(Value::as_u64).unwrap_or(0);
+
+        match data.get("type").and_then(Value::as_str).unwrap_or("") {
+            "message_start" => {
+                let message = data.get("message").unwrap_or(&Value::Null);
+                if let Some(id) = message.get("id").and_then(Value::as_str) {
+                    self.id = format!("chatcmpl-{}", id.trim_start_matches("msg_"));
+                }
+                self.model = message.get("model").cloned().unwrap_or_default();
+                self.record_usage(message.get("usage"));
+                self.chunk(json!({ "role": "assistant", "content": "" }), None)
+            }
+            "content_block_start" => {
+                let block = data.get("content_block").unwrap_or(&Value::Null);
+                match block.get("type").and_then(Value::as_str) {
+                    Some("tool_use") => {
+                        let tool_index = self.tool_indexes.len();
+                        self.tool_indexes.push((block_index, tool_index));
+                        self.chunk(
+                            json!({ "tool_calls": [{
+                                "index": tool_index,
+                                "id": block.get("id").cloned().unwrap_or_default(),
+                                "type": "function",
+                                "function": {
+                                    "name": block.get("name").cloned().unwrap_or_default(),
+                                    "arguments": "",
+                                },
+                            }] }),
+                            None,
+                        )
+                    }
+                    Some("text") => match block.get("text").and_then(Value::as_str) {
+                        Some(text) if !text.is_empty() => {
+                            self.chunk(json!({ "content": text }), None)
+                        }
+                        _ => String::new(),
+                    },
+                    _ => String::new(),
+                }
+            }
+            "content_block_delta" => {
+                let delta = data.get("delta").unwrap_or(&Value::Null);
+                match delta.get("type").and_then(Value::as_str) {
+                    Some("text_delta") => self.chunk(
+                        json!({ "content": delta.get("text").cloned().unwrap_or_default() }),
+                        None,
+                    ),
+                    Some("input_json_delta") => match self.tool_index(block_index) {
+                        Some(tool_index) => self.chunk(
+                            json!({ "tool_calls": [{
+                                "index": tool_index,
+                                "function": {
+                                    "arguments": delta.get("partial_json").cloned().unwrap_or_default(),
+                                },
+                            }] }),
+                            None,
+                        ),
+                        None => String::new(),
+                    },
+                    _ => String::new(),
+                }
+            }
+            "message_delta" => {
+                self.record_usage(data.get("usage"));
+                let finish_reason = data
+                    .pointer("/delta/stop_reason")
+                    .and_then(Value::as_str)
+                    .map(finish_reason);
+                match finish_reason {
+                    Some(finish_reason) => self.chunk(json!({}), Some(finish_reason)),
+                    None => String::new(),
+                }
+            }
+            "message_stop" => self.done(),
+            "error" => {
+                let error = data.get("error").cloned().unwrap_or_default();
+                format_event(None, &json!({ "error": error }).to_string())
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn finish(&mut self) -> String {
+        self.done()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_maps_roles_tools_and_format() {
+        let payload = json!({
+            "model": "gpt",
+            "messages": [
+                { "role": "system", "content": "be brief" },
+                { "role": "user", "content": "weather?" },
+                { "role": "assistant", "content": null, "tool_calls": [
+                    { "id": "call_1", "type": "function", "function": { "name": "weather", "arguments": "{\"city\":\"Paris\"}" } },
+                    { "id": "call_2", "type": "function", "function": { "name": "weather", "arguments": "{\"city\":\"Rome\"}" } }
+                ]},
+                { "role": "tool", "tool_call_id": "call_1", "content": "sunny" },
+                { "role": "tool", "tool_call_id": "call_2", "content": "rainy" }
+            ],
+            "tools": [{ "type": "function", "function": { "name": "weather", "parameters": { "type": "object" } } }],
+            "tool_choice": "required",
+            "parallel_tool_calls": false,
+            "response_format": { "type": "json_object" },
+            "stop": "END"
+        });
+
+        let anthropic = request(payload).unwrap();
+        let messages = anthropic["messages"].as_array().unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][1]["input"]["city"], "Rome");
+        assert_eq!(messages[2]["content"][1]["tool_use_id"], "call_2");
+        assert!(
+            anthropic["system"]
+                .as_str()
+                .unwrap()
+                .starts_with("be brief\n\n")
+        );
+        assert_eq!(anthropic["max_tokens"], ANTHROPIC_DEFAULT_MAX_TOKENS);
+        assert_eq!(
+            anthropic["tool_choice"],
+            json!({ "type": "any", "disable_parallel_tool_use": true })
+        );
+        assert_eq!(anthropic["tools"][0]["input_schema"]["type"], "object");
+        assert_eq!(anthropic["stop_sequences"], json!(["END"]));
+    }
+
+    #[test]
+    fn response_maps_usage_for_extraction() {
+        let body = json!({
+            "id": "msg_1",
+            "model": "claude",
+            "content": [{ "type": "text", "text": "Hi" }],
+            "stop_reason": "max_tokens",
+            "usage": { "input_tokens": 10, "cache_read_input_tokens": 5, "output_tokens": 3 }
+        });
+
+        let completion: Value =
+            serde_json::from_slice(&response(body.to_string().as_bytes()).unwrap()).unwrap();
+
+        assert_eq!(completion["id"], "chatcmpl-1");
+        assert_eq!(completion["choices"][0]["message"]["content"], "Hi");
+        assert_eq!(completion["choices"][0]["finish_reason"], "length");
+        assert_eq!(completion["usage"]["prompt_tokens"], 15);
+        assert_eq!(completion["usage"]["total_tokens"], 18);
+        assert_eq!(
+            completion["usage"]["prompt_tokens_details"]["cached_tokens"],
+            5
+        );
+    }
+
+    #[test]
+    fn stream_emits_chunks_usage_and_done() {
+        let mut stream = MessagesToChatStream::default();
+        let events = [
+            json!({ "type": "message_start", "message": { "id": "msg_1", "model": "claude", "usage": { "input_tokens": 7, "output_tokens": 1 } } }),
+            json!({ "type": "content_block_start", "index": 0, "content_block": { "type": "tool_use", "id": "toolu_1", "name": "weather", "input": {} } }),
+            json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "input_json_delta", "partial_json": "{\"city\":" } }),
+            json!({ "type": "content_block_stop", "index": 0 }),
+            json!({ "type": "message_delta", "delta": { "stop_reason": "tool_use" }, "usage": { "output_tokens": 4 } }),
+            json!({ "type": "message_stop" }),
+        ];
+
+        let mut out = String::new();
+        for data in events {
+            out.push_str(&stream.translate_event(&SseEvent {
+                event: data["type"].as_str().map(str::to_string),
+                data: data.to_string(),
+            }));
+        }
+        out.push_str(&stream.finish());
+
+        let chunks: Vec<&str> = out
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .collect();
+        assert_eq!(chunks.len(), 6);
+        assert!(chunks[2].contains(r#""arguments":"{\"city\":""#));
+        assert!(chunks[3].contains(r#""finish_reason":"tool_calls""#));
+        assert!(chunks[4].contains(r#""total_tokens":11"#));
+        assert_eq!(chunks[5], "[DONE]");
+    }
+}
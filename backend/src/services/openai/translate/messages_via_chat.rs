@@ -0,0 +1,630 @@
+//! Anthropic Messages requests served by an OpenAI Chat Completions upstream.
+
+use reqwest::StatusCode;
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
+
+use super::{StreamTranslator, text_of};
+use crate::{
+    error::{AppError, AppResult},
+    utils::sse::{SseEvent, format_event},
+};
+
+/// Anthropic request fields that carry over to chat completions unchanged or
+/// under a new name.
+const PASSTHROUGH_FIELDS: [(&str, &str); 5] = [
+    ("model", "model"),
+    ("max_tokens", "max_tokens"),
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("stop_sequences", "stop"),
+];
+
+pub(super) fn request(payload: Value) -> AppResult<Value> {
+    let Value::Object(mut input) = payload else {
+        return Err(AppError::BadRequest(
+            "payload must be a JSON object".to_string(),
+        ));
+    };
+    let Some(Value::Array(input_messages)) = input.remove("messages") else {
+        return Err(AppError::BadRequest(
+            "messages must be an array".to_string(),
+        ));
+    };
+
+    let mut messages = Vec::new();
+    if let Some(system) = input.get("system") {
+        let text = text_of(system);
+        if !text.is_empty() {
+            messages.push(json!({ "role": "system", "content": text }));
+        }
+    }
+    for message in &input_messages {
+        let content = message.get("content").unwrap_or(&Value::Null);
+        match message.get("role").and_then(Value::as_str) {
+            Some("assistant") => messages.push(assistant_message(content)),
+            _ => messages.extend(user_messages(content)),
+        }
+    }
+
+    let mut output = Map::new();
+    for (from, to) in PASSTHROUGH_FIELDS {
+        if let Some(value) = input.remove(from) {
+            output.insert(to.to_string(), value);
+        }
+    }
+    output.insert("messages".to_string(), Value::Array(messages));
+
+    if input.get("stream").and_then(Value::as_bool) == Some(true) {
+        output.insert("stream".to_string(), Value::Bool(true));
+        output.insert(
+            "stream_options".to_string(),
+            json!({ "include_usage": true }),
+        );
+    }
+
+    if let Some(user_id) = input
+        .get("metadata")
+        .and_then(|metadata| metadata.get("user_id"))
+    {
+        output.insert("user".to_string(), user_id.clone());
+    }
+
+    if let Some(Value::Array(tools)) = input.get("tools") {
+        let tools: Vec<Value> = tools.iter().filter_map(tool).collect();
+        if !tools.is_empty() {
+            output.insert("tools".to_string(), Value::Array(tools));
+        }
+    }
+
+    if let Some(choice) = input.get("tool_choice") {
+        if let Some(tool_choice) = tool_choice(choice) {
+            output.insert("tool_choice".to_string(), tool_choice);
+        }
+        if choice
+            .get("disable_parallel_tool_use")
+            .and_then(Value::as_bool)
+            == Some(true)
+        {
+            output.insert("parallel_tool_calls".to_string(), Value::Bool(false));
+        }
+    }
+
+    Ok(Value::Object(output))
+}
+
+/// Splits a user turn into chat messages: tool results become `tool`
+/// messages, which must directly follow the assistant's tool calls, and the
+/// remaining blocks form a user message.
+fn user_messages(content: &Value) -> Vec<Value> {
+    let Value::Array(blocks) = content else {
+        return vec![json!({ "role": "user", "content": text_of(content) })];
+    };
+
+    let mut messages = Vec::new();
+    let mut parts = Vec::new();
+    for block in blocks {
+        match block.get("type").and_then(Value::as_str) {
+            Some("text") => parts.push(json!({
+                "type": "text",
+                "text": block.get("text").cloned().unwrap_or_default(),
+            })),
+            Some("image") => {
+                if let Some(url) = image_url(block.get("source").unwrap_or(&Value::Null)) {
+                    parts.push(json!({ "type": "image_url", "image_url": { "url": url } }));
+                }
+            }
+            Some("tool_result") => messages.push(json!({
+                "role": "tool",
+                "tool_call_id": block.get("tool_use_id").cloned().unwrap_or_default(),
+                "content": text_of(block.get("content").unwrap_or(&Value::Null)),
+            })),
+            _ => {}
+        }
+    }
+
+    if parts.is_empty() {
+        return messages;
+    }
+    let all_text = parts
+        .iter()
+        .all(|part| part.get("type").and_then(Value::as_str) == Some("text"));
+    let content = if all_text {
+        Value::String(text_of(&Value::Array(parts)))
+    } else {
+        Value::Array(parts)
+    };
+    messages.push(json!({ "role": "user", "content": content }));
+    messages
+}
+
+fn assistant_message(content: &Value) -> Value {
+    let Value::Array(blocks) = content else {
+        return json!({ "role": "assistant", "content": text_of(content) });
+    };
+
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for block in blocks {
+        match block.get("type").and_then(Value::as_str) {
+            Some("text") => text.push_str(block.get("text").and_then(Value::as_str).unwrap_or("")),
+            Some("tool_use") => {
+                let input = block.get("input").cloned().unwrap_or_else(|| json!({}));
+                tool_calls.push(json!({
+                    "id": block.get("id").cloned().unwrap_or_default(),
+                    "type": "function",
+                    "function": {
+                        "name": block.get("name").cloned().unwrap_or_default(),
+                        "arguments": input.to_string(),
+                    },
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    let mut message = json!({ "role": "assistant", "content": text });
+    if !tool_calls.is_empty() {
+        if text.is_empty() {
+            message["content"] = Value::Null;
+        }
+        message["tool_calls"] = Value::Array(tool_calls);
+    }
+    message
+}
+
+fn image_url(source: &Value) -> Option<String> {
+    match source.get("type").and_then(Value::as_str)? {
+        "base64" => Some(format!(
+            "data:{};base64,{}",
+            source.get("media_type").and_then(Value::as_str)?,
+            source.get("data").and_then(Value::as_str)?,
+        )),
+        "url" => source
+            .get("url")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Server tools (web search, code execution, ...) have no schema and no chat
+/// equivalent, so they are dropped.
+fn tool(tool: &Value) -> Option<Value> {
+    let parameters = tool.get("input_schema")?;
+    let mut function = json!({
+        "name": tool.get("name")?,
+        "parameters": parameters,
+    });
+    if let Some(description) = tool.get("description") {
+        function["description"] = description.clone();
+    }
+    Some(json!({ "type": "function", "function": function }))
+}
+
+fn tool_choice(choice: &Value) -> Option<Value> {
+    match choice.get("type").and_then(Value::as_str)? {
+        "auto" => Some(json!("auto")),
+        "any" => Some(json!("required")),
+        "none" => Some(json!("none")),
+        "tool" => Some(json!({
+            "type": "function",
+            "function": { "name": choice.get("name")? },
+        })),
+        _ => None,
+    }
+}
+
+pub(super) fn response(body: &[u8]) -> AppResult<Vec<u8>> {
+    let chat: Value = serde_json::from_slice(body).map_err(|err| AppError::Internal(err.into()))?;
+    let choice = chat
+        .get("choices")
+        .and_then(|choices| choices.get(0))
+        .unwrap_or(&Value::Null);
+    let message = choice.get("message").unwrap_or(&Value::Null);
+
+    let mut content = Vec::new();
+    if let Some(text) = message.get("content").and_then(Value::as_str)
+        && !text.is_empty()
+    {
+        content.push(json!({ "type": "text", "text": text }));
+    }
+    if let Some(Value::Array(tool_calls)) = message.get("tool_calls") {
+        for tool_call in tool_calls {
+            let function = tool_call.get("function").unwrap_or(&Value::Null);
+            let input = function
+                .get("arguments")
+                .and_then(Value::as_str)
+                .and_then(|arguments| serde_json::from_str::<Value>(arguments).ok())
+                .unwrap_or_else(|| json!({}));
+            content.push(json!({
+                "type": "tool_use",
+                "id": tool_call.get("id").cloned().unwrap_or_default(),
+                "name": function.get("name").cloned().unwrap_or_default(),
+                "input": input,
+            }));
+        }
+    }
+
+    let stop_reason = choice
+        .get("finish_reason")
+        .and_then(Value::as_str)
+        .map(stop_reason);
+    let message = json!({
+        "id": message_id(&chat),
+        "type": "message",
+        "role": "assistant",
+        "model": chat.get("model").cloned().unwrap_or_default(),
+        "content": content,
+        "stop_reason": stop_reason.unwrap_or("end_turn"),
+        "stop_sequence": null,
+        "usage": usage(chat.get("usage").unwrap_or(&Value::Null)),
+    });
+
+    serde_json::to_vec(&message).map_err(|err| AppError::Internal(err.into()))
+}
+
+pub(super) fn error(status: StatusCode, body: &[u8]) -> Vec<u8> {
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|value| {
+            value
+                .pointer("/error/message")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| String::from_utf8_lossy(body).into_owned());
+    let error_type = match status {
+        StatusCode::BAD_REQUEST => "invalid_request_error",
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::FORBIDDEN => "permission_error",
+        StatusCode::NOT_FOUND => "not_found_error",
+        StatusCode::PAYLOAD_TOO_LARGE => "request_too_large",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limit_error",
+        StatusCode::SERVICE_UNAVAILABLE => "overloaded_error",
+        _ => "api_error",
+    };
+    json!({
+        "type": "error",
+        "error": { "type": error_type, "message": message },
+    })
+    .to_string()
+    .into_bytes()
+}
+
+fn message_id(chat: &Value) -> String {
+    match chat.get("id").and_then(Value::as_str) {
+        Some(id) => format!("msg_{}", id.trim_start_matches("chatcmpl-")),
+        None => format!("msg_{}", Uuid::now_v7().simple()),
+    }
+}
+
+fn stop_reason(finish_reason: &str) -> &'static str {
+    match finish_reason {
+        "length" => "max_tokens",
+        "tool_calls" | "function_call" => "tool_use",
+        "content_filter" => "refusal",
+        _ => "end_turn",
+    }
+}
+
+/// Anthropic counts cache reads separately from `input_tokens`.
+fn usage(usage: &Value) -> Value {
+    let prompt_tokens = usage
+        .get("prompt_tokens")
+        .and_then(Value::as_i64)
+        .unwrap_or(0);
+    let completion_tokens = usage
+        .get("completion_tokens")
+        .and_then(Value::as_i64)
+        .unwrap_or(0);
+    let cached_tokens = usage
+        .pointer("/prompt_tokens_details/cached_tokens")
+        .and_then(Value::as_i64)
+        .unwrap_or(0);
+
+    let mut value = json!({
+        "input_tokens": prompt_tokens - cached_tokens,
+        "output_tokens": completion_tokens,
+    });
+    if cached_tokens > 0 {
+        value["cache_read_input_tokens"] = json!(cached_tokens);
+    }
+    value
+}
+
+enum OpenBlock {
+    Text,
+    ToolUse { call_index: u64 },
+}
+
+/// Turns chat completion chunks into Anthropic `message_*` and
+/// `content_block_*` events.
+#[derive(Default)]
+pub(super) struct ChatToMessagesStream {
+    started: bool,
+    block: Option<OpenBlock>,
+    next_index: usize,
+    stop_reason: Option<&'static str>,
+    usage: Value,
+}
+
+impl ChatToMessagesStream {
+    fn start(&mut self, chunk: &Value, out: &mut String) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        push_event(
+            out,
+            "message_start",
+            json!({
+                "type": "message_start",
+                "message": {
+                    "id": message_id(chunk),
+                    "type": "message",
+                    "role": "assistant",
+                    "model": chunk.get("model").cloned().unwrap_or_default(),
+                    "content": [],
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": { "input_tokens": 0, "output_tokens": 0 },
+                },
+            }),
+        );
+    }
+
+    fn open_block(&mut self, block: OpenBlock, content_block: Value, out: &mut String) {
+        self.close_block(out);
+        push_event(
+            out,
+            "content_block_start",
+            json!({
+                "type": "content_block_start",
+                "index": self.next_index,
+                "content_block": content_block,
+            }),
+        );
+        self.block = Some(block);
+    }
+
+    fn close_block(&mut self, out: &mut String) {
+        if self.block.take().is_some() {
+            push_event(
+                out,
+                "content_block_stop",
+                json!({ "type": "content_block_stop", "index": self.next_index }),
+            );
+            self.next_index += 1;
+        }
+    }
+
+    fn block_delta(&self, delta: Value, out: &mut String) {
+        push_event(
+            out,
+            "content_block_delta",
+            json!({
+                "type": "content_block_delta",
+                "index": self.next_index,
+                "delta": delta,
+            }),
+        );
+    }
+
+    fn text_delta(&mut self, text: &str, out: &mut String) {
+        if !matches!(self.block, Some(OpenBlock::Text)) {
+            self.open_block(OpenBlock::Text, json!({ "type": "text", "text": "" }), out);
+        }
+        self.block_delta(json!({ "type": "text_delta", "text": text }), out);
+    }
+
+    fn tool_call_delta(&mut self, tool_call: &Value, out: &mut String) {
+        let call_index = tool_call.get("index").and_then(Value::as_u64).unwrap_or(0);
+        let function = tool_call.get("function").unwrap_or(&Value::Null);
+        let continues_open_call = matches!(
+            self.block,
+            Some(OpenBlock::ToolUse { call_index: open }) if open == call_index
+        ) && tool_call.get("id").is_none();
+
+        if !continues_open_call {
+            let id = tool_call
+                .get("id")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("toolu_{}", Uuid::now_v7().simple()));
+            self.open_block(
+                OpenBlock::ToolUse { call_index },
+                json!({
+                    "type": "tool_use",
+                    "id": id,
+                    "name": function.get("name").cloned().unwrap_or_default(),
+                    "input": {},
+                }),
+                out,
+            );
+        }
+
+        if let Some(arguments) = function.get("arguments").and_then(Value::as_str)
+            && !arguments.is_empty()
+        {
+            self.block_delta(
+                json!({ "type": "input_json_delta", "partial_json": arguments }),
+                out,
+            );
+        }
+    }
+}
+
+impl StreamTranslator for ChatToMessagesStream {
+    fn translate_event(&mut self, event: &SseEvent) -> String {
+        let Ok(chunk) = serde_json::from_str::<Value>(&event.data) else {
+            return String::new();
+        };
+
+        let mut out = String::new();
+        self.start(&chunk, &mut out);
+
+        if let Some(usage) = chunk.get("usage")
+            && usage.is_object()
+        {
+            self.usage = usage.clone();
+        }
+
+        let choice = chunk
+            .get("choices")
+            .and_then(|choices| choices.get(0))
+            .unwrap_or(&Value::Null);
+        let delta = choice.get("delta").unwrap_or(&Value::Null);
+
+        if let Some(text) = delta.get("content").and_then(Value::as_str)
+            && !text.is_empty()
+        {
+            self.text_delta(text, &mut out);
+        }
+        if let Some(Value::Array(tool_calls)) = delta.get("tool_calls") {
+            for tool_call in tool_calls {
+                self.tool_call_delta(tool_call, &mut out);
+            }
+        }
+        if let Some(finish_reason) = choice.get("finish_reason").and_then(Value::as_str) {
+            self.stop_reason = Some(stop_reason(finish_reason));
+        }
+
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        let mut out = String::new();
+        self.start(&Value::Null, &mut out);
+        self.close_block(&mut out);
+        push_event(
+            &mut out,
+            "message_delta",
+            json!({
+                "type": "message_delta",
+                "delta": {
+                    "stop_reason": self.stop_reason.unwrap_or("end_turn"),
+                    "stop_sequence": null,
+                },
+                "usage": usage(&self.usage),
+            }),
+        );
+        push_event(&mut out, "message_stop", json!({ "type": "message_stop" }));
+        out
+    }
+}
+
+fn push_event(out: &mut String, event: &str, data: Value) {
+    out.push_str(&format_event(Some(event), &data.to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_maps_system_tools_and_tool_results() {
+        let payload = json!({
+            "model": "claude",
+            "max_tokens": 256,
+            "system": [{ "type": "text", "text": "be brief" }],
+            "messages": [
+                { "role": "user", "content": "weather?" },
+                { "role": "assistant", "content": [
+                    { "type": "tool_use", "id": "call_1", "name": "weather", "input": { "city": "Paris" } }
+                ]},
+                { "role": "user", "content": [
+                    { "type": "tool_result", "tool_use_id": "call_1", "content": "sunny" },
+                    { "type": "text", "text": "thanks" }
+                ]}
+            ],
+            "tools": [{ "name": "weather", "input_schema": { "type": "object" } }],
+            "tool_choice": { "type": "any" },
+            "stream": true
+        });
+
+        let chat = request(payload).unwrap();
+        let messages = chat["messages"].as_array().unwrap();
+
+        assert_eq!(
+            messages[0],
+            json!({ "role": "system", "content": "be brief" })
+        );
+        assert_eq!(
+            messages[2]["tool_calls"][0]["function"]["arguments"],
+            r#"{"city":"Paris"}"#
+        );
+        assert_eq!(
+            messages[3],
+            json!({ "role": "tool", "tool_call_id": "call_1", "content": "sunny" })
+        );
+        assert_eq!(messages[4], json!({ "role": "user", "content": "thanks" }));
+        assert_eq!(chat["tools"][0]["function"]["name"], "weather");
+        assert_eq!(chat["tool_choice"], "required");
+        assert_eq!(chat["stream_options"]["include_usage"], true);
+    }
+
+    #[test]
+    fn response_maps_tool_calls_and_usage() {
+        let body = json!({
+            "id": "chatcmpl-1",
+            "model": "gpt",
+            "choices": [{
+                "message": {
+                    "content": null,
+                    "tool_calls": [{ "id": "call_1", "function": { "name": "weather", "arguments": "{\"city\":\"Paris\"}" } }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": { "prompt_tokens": 10, "completion_tokens": 5 }
+        });
+
+        let message: Value =
+            serde_json::from_slice(&response(body.to_string().as_bytes()).unwrap()).unwrap();
+
+        assert_eq!(message["id"], "msg_1");
+        assert_eq!(message["stop_reason"], "tool_use");
+        assert_eq!(message["content"][0]["input"]["city"], "Paris");
+        assert_eq!(
+            message["usage"],
+            json!({ "input_tokens": 10, "output_tokens": 5 })
+        );
+    }
+
+    #[test]
+    fn stream_emits_anthropic_events() {
+        let mut stream = ChatToMessagesStream::default();
+        let chunks = [
+            json!({ "id": "chatcmpl-1", "model": "gpt", "choices": [{ "delta": { "content": "Hi" } }] }),
+            json!({ "id": "chatcmpl-1", "choices": [{ "delta": {}, "finish_reason": "stop" }] }),
+            json!({ "id": "chatcmpl-1", "choices": [], "usage": { "prompt_tokens": 3, "completion_tokens": 1 } }),
+        ];
+
+        let mut out = String::new();
+        for chunk in chunks {
+            out.push_str(&stream.translate_event(&SseEvent {
+                event: None,
+                data: chunk.to_string(),
+            }));
+        }
+        out.push_str(&stream.finish());
+
+        let events: Vec<&str> = out
+            .lines()
+            .filter_map(|line| line.strip_prefix("event: "))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop"
+            ]
+        );
+        assert!(out.contains(r#""stop_reason":"end_turn""#));
+        assert!(out.contains(r#""output_tokens":1"#));
+    }
+}
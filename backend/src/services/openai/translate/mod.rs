@@ -0,0 +1,97 @@
+//! Protocol translation between client-facing and upstream API formats.
+//!
+//! When an alias target only exposes a different API type than the one the
+//! client called, the request is rewritten into the upstream format and the
+//! response (buffered or streamed) is rewritten back.
+
+mod chat_via_messages;
+mod messages_via_chat;
+mod responses_via_chat;
+
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::{db::types::ApiType, error::AppResult, utils::sse::SseEvent};
+
+/// Rewrites an upstream SSE stream into the client's event format.
+pub(super) trait StreamTranslator: Send {
+    /// Returns the SSE text to send to the client for one upstream event.
+    fn translate_event(&mut self, event: &SseEvent) -> String;
+
+    /// Returns any closing events once the upstream stream has ended.
+    fn finish(&mut self) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Translation {
+    /// Anthropic Messages clients served by a Chat Completions upstream.
+    MessagesViaChat,
+    /// Chat Completions clients served by an Anthropic Messages upstream.
+    ChatViaMessages,
+    /// Responses clients emulated over a Chat Completions upstream.
+    ResponsesViaChat,
+}
+
+impl Translation {
+    pub(super) fn between(client: ApiType, upstream: ApiType) -> Option<Self> {
+        match (client, upstream) {
+            (ApiType::AnthropicMessages, ApiType::OpenAiChatCompletions) => {
+                Some(Self::MessagesViaChat)
+            }
+            (ApiType::OpenAiChatCompletions, ApiType::AnthropicMessages) => {
+                Some(Self::ChatViaMessages)
+            }
+            (ApiType::OpenAiResponses, ApiType::OpenAiChatCompletions) => {
+                Some(Self::ResponsesViaChat)
+            }
+            _ => None,
+        }
+    }
+
+    pub(super) fn request(self, payload: Value) -> AppResult<Value> {
+        match self {
+            Self::MessagesViaChat => messages_via_chat::request(payload),
+            Self::ChatViaMessages => chat_via_messages::request(payload),
+            Self::ResponsesViaChat => responses_via_chat::request(payload),
+        }
+    }
+
+    pub(super) fn response(self, body: &[u8]) -> AppResult<Vec<u8>> {
+        match self {
+            Self::MessagesViaChat => messages_via_chat::response(body),
+            Self::ChatViaMessages => chat_via_messages::response(body),
+            Self::ResponsesViaChat => responses_via_chat::response(body),
+        }
+    }
+
+    pub(super) fn error(self, status: StatusCode, body: &[u8]) -> Vec<u8> {
+        match self {
+            Self::MessagesViaChat => messages_via_chat::error(status, body),
+            Self::ChatViaMessages => chat_via_messages::error(status, body),
+            Self::ResponsesViaChat => responses_via_chat::error(body),
+        }
+    }
+
+    pub(super) fn stream(self) -> Box<dyn StreamTranslator> {
+        match self {
+            Self::MessagesViaChat => Box::new(messages_via_chat::ChatToMessagesStream::default()),
+            Self::ChatViaMessages => Box::new(chat_via_messages::MessagesToChatStream::default()),
+            Self::ResponsesViaChat => {
+                Box::new(responses_via_chat::ChatToResponsesStream::default())
+            }
+        }
+    }
+}
+
+/// Concatenates the text of a string or an array of `{"type":"text"}` blocks.
+fn text_of(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|block| block.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
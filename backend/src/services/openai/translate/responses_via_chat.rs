@@ -0,0 +1,838 @@
+//! OpenAI Responses requests emulated over an OpenAI Chat Completions upstream.
+
+use serde_json::{Map, Value, json};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{StreamTranslator, text_of};
+use crate::{
+    error::{AppError, AppResult},
+    utils::sse::{SseEvent, format_event},
+};
+
+const PASSTHROUGH_FIELDS: [(&str, &str); 6] = [
+    ("model", "model"),
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("user", "user"),
+    ("parallel_tool_calls", "parallel_tool_calls"),
+    ("max_output_tokens", "max_tokens"),
+];
+
+pub(super) fn request(payload: Value) -> AppResult<Value> {
+    let Value::Object(mut input) = payload else {
+        return Err(AppError::BadRequest(
+            "payload must be a JSON object".to_string(),
+        ));
+    };
+
+    let mut messages = Vec::new();
+    if let Some(instructions) = input.get("instructions").and_then(Value::as_str)
+        && !instructions.is_empty()
+    {
+        messages.push(json!({ "role": "system", "content": instructions }));
+    }
+    match input.remove("input") {
+        Some(Value::String(text)) => messages.push(json!({ "role": "user", "content": text })),
+        Some(Value::Array(items)) => {
+            for item in &items {
+                push_item(&mut messages, item);
+            }
+        }
+        None | Some(Value::Null) => {}
+        Some(_) => {
+            return Err(AppError::BadRequest(
+                "input must be a string or an array of items".to_string(),
+            ));
+        }
+    }
+
+    let mut output = Map::new();
+    for (from, to) in PASSTHROUGH_FIELDS {
+        if let Some(value) = input.remove(from) {
+            output.insert(to.to_string(), value);
+        }
+    }
+    output.insert("messages".to_string(), Value::Array(messages));
+
+    if input.get("stream").and_then(Value::as_bool) == Some(true) {
+        output.insert("stream".to_string(), Value::Bool(true));
+        output.insert(
+            "stream_options".to_string(),
+            json!({ "include_usage": true }),
+        );
+    }
+
+    if let Some(effort) = input
+        .get("reasoning")
+        .and_then(|reasoning| reasoning.get("effort"))
+    {
+        output.insert("reasoning_effort".to_string(), effort.clone());
+    }
+
+    if let Some(format) = input
+        .get("text")
+        .and_then(|text| text.get("format"))
+        .and_then(response_format)
+    {
+        output.insert("response_format".to_string(), format);
+    }
+
+    if let Some(Value::Array(tools)) = input.get("tools") {
+        let tools: Vec<Value> = tools.iter().filter_map(tool).collect();
+        if !tools.is_empty() {
+            output.insert("tools".to_string(), Value::Array(tools));
+        }
+    }
+
+    if let Some(tool_choice) = input.get("tool_choice").and_then(tool_choice) {
+        output.insert("tool_choice".to_string(), tool_choice);
+    }
+
+    Ok(Value::Object(output))
+}
+
+fn push_item(messages: &mut Vec<Value>, item: &Value) {
+    let item_type = item
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or("message");
+    match item_type {
+        "message" => {
+            let content = item.get("content").unwrap_or(&Value::Null);
+            match item.get("role").and_then(Value::as_str) {
+                Some("system" | "developer") => {
+                    messages.push(json!({ "role": "system", "content": text_of(content) }));
+                }
+                Some("assistant") => {
+                    messages.push(json!({ "role": "assistant", "content": text_of(content) }));
+                }
+                _ => messages.push(json!({ "role": "user", "content": user_content(content) })),
+            }
+        }
+        "function_call" => {
+            let tool_call = json!({
+                "id": item.get("call_id").cloned().unwrap_or_default(),
+                "type": "function",
+                "function": {
+                    "name": item.get("name").cloned().unwrap_or_default(),
+                    "arguments": item.get("arguments").cloned().unwrap_or_else(|| json!("{}")),
+                },
+            });
+            // Calls made in the same turn belong to one assistant message.
+            match messages.last_mut() {
+                Some(last) if last["role"] == "assistant" => {
+                    match last.get_mut("tool_calls") {
+                        Some(Value::Array(tool_calls)) => tool_calls.push(tool_call),
+                        _ => last["tool_calls"] = json!([tool_call]),
+                    }
+                    if last["content"] == "" {
+                        last["content"] = Value::Null;
+                    }
+                }
+                _ => messages.push(json!({
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [tool_call],
+                })),
+            }
+        }
+        "function_call_output" => {
+            let output = item.get("output").unwrap_or(&Value::Null);
+            messages.push(json!({
+                "role": "tool",
+                "tool_call_id": item.get("call_id").cloned().unwrap_or_default(),
+                "content": text_of(output),
+            }));
+        }
+        // Reasoning items and references have no chat equivalent.
+        _ => {}
+    }
+}
+
+fn user_content(content: &Value) -> Value {
+    let Value::Array(parts) = content else {
+        return Value::String(text_of(content));
+    };
+
+    let parts: Vec<Value> = parts
+        .iter()
+        .filter_map(|part| match part.get("type").and_then(Value::as_str)? {
+            "input_text" | "output_text" | "text" => {
+                Some(json!({ "type": "text", "text": part.get("text")? }))
+            }
+            "input_image" => Some(json!({
+                "type": "image_url",
+                "image_url": { "url": part.get("image_url")? },
+            })),
+            _ => None,
+        })
+        .collect();
+
+    if parts.iter().all(|part| part["type"] == "text") {
+        Value::String(text_of(&Value::Array(parts)))
+    } else {
+        Value::Array(parts)
+    }
+}
+
+fn response_format(format: &Value) -> Option<Value> {
+    match format.get("type").and_then(Value::as_str)? {
+        "json_object" => Some(json!({ "type": "json_object" })),
+        "json_schema" => {
+            let mut json_schema = json!({
+                "name": format.get("name").cloned().unwrap_or_else(|| json!("response")),
+                "schema": format.get("schema")?,
+            });
+            if let Some(strict) = format.get("strict") {
+                json_schema["strict"] = strict.clone();
+            }
+            Some(json!({ "type": "json_schema", "json_schema": json_schema }))
+        }
+        _ => None,
+    }
+}
+
+/// Built-in tools (web search, file search, ...) have no chat equivalent and
+/// are dropped.
+fn tool(tool: &Value) -> Option<Value> {
+    if tool.get("type").and_then(Value::as_str) != Some("function") {
+        return None;
+    }
+    let mut function = json!({
+        "name": tool.get("name")?,
+        "parameters": tool
+            .get("parameters")
+            .cloned()
+            .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+    });
+    for field in ["description", "strict"] {
+        if let Some(value) = tool.get(field) {
+            function[field] = value.clone();
+        }
+    }
+    Some(json!({ "type": "function", "function": function }))
+}
+
+fn tool_choice(choice: &Value) -> Option<Value> {
+    match choice {
+        Value::String(_) => Some(choice.clone()),
+        Value::Object(_) if choice.get("type").and_then(Value::as_str) == Some("function") => {
+            Some(json!({
+                "type": "function",
+                "function": { "name": choice.get("name")? },
+            }))
+        }
+        _ => None,
+    }
+}
+
+pub(super) fn response(body: &[u8]) -> AppResult<Vec<u8>> {
+    let chat: Value = serde_json::from_slice(body).map_err(|err| AppError::Internal(err.into()))?;
+    let choice = chat
+        .get("choices")
+        .and_then(|choices| choices.get(0))
+        .unwrap_or(&Value::Null);
+    let message = choice.get("message").unwrap_or(&Value::Null);
+
+    let mut output = Vec::new();
+    if let Some(reasoning) = reasoning_of(message)
+        && !reasoning.is_empty()
+    {
+        output.push(reasoning_item(&item_id("rs"), reasoning));
+    }
+    if let Some(text) = message.get("content").and_then(Value::as_str)
+        && !text.is_empty()
+    {
+        output.push(message_item(&item_id("msg"), text));
+    }
+    if let Some(Value::Array(tool_calls)) = message.get("tool_calls") {
+        for tool_call in tool_calls {
+            let function = tool_call.get("function").unwrap_or(&Value::Null);
+            output.push(function_call_item(
+                &item_id("fc"),
+                tool_call.get("id").and_then(Value::as_str).unwrap_or(""),
+                function.get("name").and_then(Value::as_str).unwrap_or(""),
+                function
+                    .get("arguments")
+                    .and_then(Value::as_str)
+                    .unwrap_or("{}"),
+            ));
+        }
+    }
+
+    let response = response_object(
+        &item_id("resp"),
+        OffsetDateTime::now_utc().unix_timestamp(),
+        chat.get("model").cloned().unwrap_or_default(),
+        choice.get("finish_reason").and_then(Value::as_str),
+        output,
+        usage(chat.get("usage").unwrap_or(&Value::Null)),
+    );
+
+    serde_json::to_vec(&response).map_err(|err| AppError::Internal(err.into()))
+}
+
+/// Chat completion errors already use the `{"error": {...}}` shape the
+/// Responses API returns.
+pub(super) fn error(body: &[u8]) -> Vec<u8> {
+    body.to_vec()
+}
+
+fn item_id(prefix: &str) -> String {
+    format!("{prefix}_{}", Uuid::now_v7().simple())
+}
+
+/// Reasoning text as returned by the chat servers that expose it.
+fn reasoning_of(message: &Value) -> Option<&str> {
+    message
+        .get("reasoning_content")
+        .or_else(|| message.get("reasoning"))
+        .and_then(Value::as_str)
+}
+
+fn reasoning_item(id: &str, text: &str) -> Value {
+    json!({
+        "type": "reasoning",
+        "id": id,
+        "summary": [{ "type": "summary_text", "text": text }],
+    })
+}
+
+fn message_item(id: &str, text: &str) -> Value {
+    json!({
+        "type": "message",
+        "id": id,
+        "status": "completed",
+        "role": "assistant",
+        "content": [{ "type": "output_text", "text": text, "annotations": [] }],
+    })
+}
+
+fn function_call_item(id: &str, call_id: &str, name: &str, arguments: &str) -> Value {
+    json!({
+        "type": "function_call",
+        "id": id,
+        "call_id": call_id,
+        "name": name,
+        "arguments": arguments,
+        "status": "completed",
+    })
+}
+
+fn response_object(
+    id: &str,
+    created_at: i64,
+    model: Value,
+    finish_reason: Option<&str>,
+    output: Vec<Value>,
+    usage: Value,
+) -> Value {
+    let (status, incomplete_details) = match finish_reason {
+        Some("length") => ("incomplete", json!({ "reason": "max_output_tokens" })),
+        Some("content_filter") => ("incomplete", json!({ "reason": "content_filter" })),
+        _ => ("completed", Value::Null),
+    };
+    json!({
+        "id": id,
+        "object": "response",
+        "created_at": created_at,
+        "status": status,
+        "error": null,
+        "incomplete_details": incomplete_details,
+        "model": model,
+        "output": output,
+        "usage": usage,
+    })
+}
+
+fn usage(usage: &Value) -> Value {
+    let tokens = |pointer: &str| usage.pointer(pointer).and_then(Value::as_i64).unwrap_or(0);
+    let input_tokens = tokens("/prompt_tokens");
+    let output_tokens = tokens("/completion_tokens");
+    json!({
+        "input_tokens": input_tokens,
+        "input_tokens_details": { "cached_tokens": tokens("/prompt_tokens_details/cached_tokens") },
+        "output_tokens": output_tokens,
+        "output_tokens_details": {
+            "reasoning_tokens": tokens("/completion_tokens_details/reasoning_tokens"),
+        },
+        "total_tokens": input_tokens + output_tokens,
+    })
+}
+
+enum OpenItem {
+    Reasoning {
+        id: String,
+        text: String,
+    },
+    Message {
+        id: String,
+        text: String,
+    },
+    FunctionCall {
+        index: u64,
+        id: String,
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+}
+
+/// Turns chat completion chunks into `response.*` events, one output item at
+/// a time.
+pub(super) struct ChatToResponsesStream {
+    id: String,
+    created_at: i64,
+    model: Value,
+    sequence_number: u64,
+    started: bool,
+    output: Vec<Value>,
+    open: Option<OpenItem>,
+    finish_reason: Option<String>,
+    usage: Value,
+}
+
+impl Default for ChatToResponsesStream {
+    fn default() -> Self {
+        Self {
+            id: item_id("resp"),
+            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+            model: Value::Null,
+            sequence_number: 0,
+            started: false,
+            output: Vec::new(),
+            open: None,
+            finish_reason: None,
+            usage: Value::Null,
+        }
+    }
+}
+
+impl ChatToResponsesStream {
+    fn emit(&mut self, out: &mut String, event: &str, mut data: Value) {
+        data["type"] = json!(event);
+        data["sequence_number"] = json!(self.sequence_number);
+        self.sequence_number += 1;
+        out.push_str(&format_event(Some(event), &data.to_string()));
+    }
+
+    fn snapshot(&self, finish_reason: Option<&str>, usage: Value) -> Value {
+        response_object(
+            &self.id,
+            self.created_at,
+            self.model.clone(),
+            finish_reason,
+            self.output.clone(),
+            usage,
+        )
+    }
+
+    fn start(&mut self, chunk: &Value, out: &mut String) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        if let Some(model) = chunk.get("model") {
+            self.model = model.clone();
+        }
+        let mut response = self.snapshot(None, Value::Null);
+        response["status"] = json!("in_progress");
+        self.emit(out, "response.created", json!({ "response": response }));
+        self.emit(out, "response.in_progress", json!({ "response": response }));
+    }
+
+    fn open(&mut self, item: OpenItem, out: &mut String) {
+        self.close(out);
+        let output_index = self.output.len();
+        match &item {
+            OpenItem::Reasoning { id, .. } => {
+                self.emit(
+                    out,
+                    "response.output_item.added",
+                    json!({
+                        "output_index": output_index,
+                        "item": { "type": "reasoning", "id": id, "summary": [] },
+                    }),
+                );
+                self.emit(
+                    out,
+                    "response.reasoning_summary_part.added",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "summary_index": 0,
+                        "part": { "type": "summary_text", "text": "" },
+                    }),
+                );
+            }
+            OpenItem::Message { id, .. } => {
+                self.emit(
+                    out,
+                    "response.output_item.added",
+                    json!({
+                        "output_index": output_index,
+                        "item": {
+                            "type": "message",
+                            "id": id,
+                            "status": "in_progress",
+                            "role": "assistant",
+                            "content": [],
+                        },
+                    }),
+                );
+                self.emit(
+                    out,
+                    "response.content_part.added",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "content_index": 0,
+                        "part": { "type": "output_text", "text": "", "annotations": [] },
+                    }),
+                );
+            }
+            OpenItem::FunctionCall {
+                id, call_id, name, ..
+            } => {
+                self.emit(
+                    out,
+                    "response.output_item.added",
+                    json!({
+                        "output_index": output_index,
+                        "item": {
+                            "type": "function_call",
+                            "id": id,
+                            "call_id": call_id,
+                            "name": name,
+                            "arguments": "",
+                            "status": "in_progress",
+                        },
+                    }),
+                );
+            }
+        }
+        self.open = Some(item);
+    }
+
+    fn close(&mut self, out: &mut String) {
+        let Some(item) = self.open.take() else {
+            return;
+        };
+        let output_index = self.output.len();
+        let done = match item {
+            OpenItem::Reasoning { id, text } => {
+                self.emit(
+                    out,
+                    "response.reasoning_summary_text.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "summary_index": 0,
+                        "text": text,
+                    }),
+                );
+                self.emit(
+                    out,
+                    "response.reasoning_summary_part.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "summary_index": 0,
+                        "part": { "type": "summary_text", "text": text },
+                    }),
+                );
+                reasoning_item(&id, &text)
+            }
+            OpenItem::Message { id, text } => {
+                self.emit(
+                    out,
+                    "response.output_text.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "content_index": 0,
+                        "text": text,
+                    }),
+                );
+                self.emit(
+                    out,
+                    "response.content_part.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "content_index": 0,
+                        "part": { "type": "output_text", "text": text, "annotations": [] },
+                    }),
+                );
+                message_item(&id, &text)
+            }
+            OpenItem::FunctionCall {
+                id,
+                call_id,
+                name,
+                arguments,
+                ..
+            } => {
+                self.emit(
+                    out,
+                    "response.function_call_arguments.done",
+                    json!({
+                        "item_id": id,
+                        "output_index": output_index,
+                        "arguments": arguments,
+                    }),
+                );
+                function_call_item(&id, &call_id, &name, &arguments)
+            }
+        };
+        self.emit(
+            out,
+            "response.output_item.done",
+            json!({ "output_index": output_index, "item": done }),
+        );
+        self.output.push(done);
+    }
+
+    fn reasoning_delta(&mut self, delta: &str, out: &mut String) {
+        if !matches!(self.open, Some(OpenItem::Reasoning { .. })) {
+            let item = OpenItem::Reasoning {
+                id: item_id("rs"),
+                text: String::new(),
+            };
+            self.open(item, out);
+        }
+        let output_index = self.output.len();
+        if let Some(OpenItem::Reasoning { id, text }) = &mut self.open {
+            text.push_str(delta);
+            let data = json!({
+                "item_id": id,
+                "output_index": output_index,
+                "summary_index": 0,
+                "delta": delta,
+            });
+            self.emit(out, "response.reasoning_summary_text.delta", data);
+        }
+    }
+
+    fn text_delta(&mut self, delta: &str, out: &mut String) {
+        if !matches!(self.open, Some(OpenItem::Message { .. })) {
+            let item = OpenItem::Message {
+                id: item_id("msg"),
+                text: String::new(),
+            };
+            self.open(item, out);
+        }
+        let output_index = self.output.len();
+        if let Some(OpenItem::Message { id, text }) = &mut self.open {
+            text.push_str(delta);
+            let data = json!({
+                "item_id": id,
+                "output_index": output_index,
+                "content_index": 0,
+                "delta": delta,
+            });
+            self.emit(out, "response.output_text.delta", data);
+        }
+    }
+
+    fn tool_call_delta(&mut self, tool_call: &Value, out: &mut String) {
+        let call_index = tool_call.get("index").and_then(Value::as_u64).unwrap_or(0);
+        let function = tool_call.get("function").unwrap_or(&Value::Null);
+        let continues_open_call = matches!(
+            self.open,
+            Some(OpenItem::FunctionCall { index, .. }) if index == call_index
+        ) && tool_call.get("id").is_none();
+
+        if !continues_open_call {
+            let item = OpenItem::FunctionCall {
+                index: call_index,
+                id: item_id("fc"),
+                call_id: tool_call
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| item_id("call")),
+                name: function
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or("")
+                    .to_string(),
+                arguments: String::new(),
+            };
+            self.open(item, out);
+        }
+
+        let Some(delta) = function.get("arguments").and_then(Value::as_str) else {
+            return;
+        };
+        if delta.is_empty() {
+            return;
+        }
+        let output_index = self.output.len();
+        if let Some(OpenItem::FunctionCall { id, arguments, .. }) = &mut self.open {
+            arguments.push_str(delta);
+            let data = json!({
+                "item_id": id,
+                "output_index": output_index,
+                "delta": delta,
+            });
+            self.emit(out, "response.function_call_arguments.delta", data);
+        }
+    }
+}
+
+impl StreamTranslator for ChatToResponsesStream {
+    fn translate_event(&mut self, event: &SseEvent) -> String {
+        let Ok(chunk) = serde_json::from_str::<Value>(&event.data) else {
+            return String::new();
+        };
+
+        let mut out = String::new();
+        self.start(&chunk, &mut out);
+
+        if let Some(usage) = chunk.get("usage")
+            && usage.is_object()
+        {
+            self.usage = usage.clone();
+        }
+
+        let choice = chunk
+            .get("choices")
+            .and_then(|choices| choices.get(0))
+            .unwrap_or(&Value::Null);
+        let delta = choice.get("delta").unwrap_or(&Value::Null);
+
+        if let Some(reasoning) = reasoning_of(delta)
+            && !reasoning.is_empty()
+        {
+            self.reasoning_delta(reasoning, &mut out);
+        }
+        if let Some(text) = delta.get("content").and_then(Value::as_str)
+            && !text.is_empty()
+        {
+            self.text_delta(text, &mut out);
+        }
+        if let Some(Value::Array(tool_calls)) = delta.get("tool_calls") {
+            for tool_call in tool_calls {
+                self.tool_call_delta(tool_call, &mut out);
+            }
+        }
+        if let Some(finish_reason) = choice.get("finish_reason").and_then(Value::as_str) {
+            self.finish_reason = Some(finish_reason.to_string());
+        }
+
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        let mut out = String::new();
+        self.start(&Value::Null, &mut out);
+        self.close(&mut out);
+
+        let finish_reason = self.finish_reason.clone();
+        let response = self.snapshot(finish_reason.as_deref(), usage(&self.usage));
+        let event = if response["status"] == "incomplete" {
+            "response.incomplete"
+        } else {
+            "response.completed"
+        };
+        self.emit(&mut out, event, json!({ "response": response }));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_maps_items_tools_and_format() {
+        let payload = json!({
+            "model": "gpt",
+            "instructions": "be brief",
+            "input": [
+                { "role": "user", "content": [{ "type": "input_text", "text": "weather?" }] },
+                { "type": "function_call", "call_id": "call_1", "name": "weather", "arguments": "{}" },
+                { "type": "function_call", "call_id": "call_2", "name": "time", "arguments": "{}" },
+                { "type": "function_call_output", "call_id": "call_1", "output": "sunny" }
+            ],
+            "tools": [
+                { "type": "function", "name": "weather", "parameters": { "type": "object" } },
+                { "type": "web_search" }
+            ],
+            "tool_choice": { "type": "function", "name": "weather" },
+            "text": { "format": { "type": "json_schema", "name": "answer", "schema": { "type": "object" } } },
+            "max_output_tokens": 64
+        });
+
+        let chat = request(payload).unwrap();
+        let messages = chat["messages"].as_array().unwrap();
+
+        assert_eq!(
+            messages[0],
+            json!({ "role": "system", "content": "be brief" })
+        );
+        assert_eq!(
+            messages[1],
+            json!({ "role": "user", "content": "weather?" })
+        );
+        assert_eq!(messages[2]["tool_calls"].as_array().unwrap().len(), 2);
+        assert_eq!(messages[3]["tool_call_id"], "call_1");
+        assert_eq!(chat["tools"].as_array().unwrap().len(), 1);
+        assert_eq!(chat["tool_choice"]["function"]["name"], "weather");
+        assert_eq!(chat["response_format"]["json_schema"]["name"], "answer");
+        assert_eq!(chat["max_tokens"], 64);
+    }
+
+    #[test]
+    fn stream_emits_response_events() {
+        let mut stream = ChatToResponsesStream::default();
+        let chunks = [
+            json!({ "model": "gpt", "choices": [{ "delta": { "reasoning_content": "hmm" } }] }),
+            json!({ "choices": [{ "delta": { "content": "Hi" } }] }),
+            json!({ "choices": [{ "delta": {}, "finish_reason": "stop" }] }),
+            json!({ "choices": [], "usage": { "prompt_tokens": 3, "completion_tokens": 2 } }),
+        ];
+
+        let mut out = String::new();
+        for chunk in chunks {
+            out.push_str(&stream.translate_event(&SseEvent {
+                event: None,
+                data: chunk.to_string(),
+            }));
+        }
+        out.push_str(&stream.finish());
+
+        let events: Vec<&str> = out
+            .lines()
+            .filter_map(|line| line.strip_prefix("event: "))
+            .collect();
+        assert_eq!(events.first(), Some(&"response.created"));
+        assert_eq!(events.last(), Some(&"response.completed"));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| **event == "response.output_item.done")
+                .count(),
+            2
+        );
+
+        let completed: Value = serde_json::from_str(
+            out.lines()
+                .filter_map(|line| line.strip_prefix("data: "))
+                .next_back()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            completed["response"]["output"][0]["summary"][0]["text"],
+            "hmm"
+        );
+        assert_eq!(
+            completed["response"]["output"][1]["content"][0]["text"],
+            "Hi"
+        );
+        assert_eq!(completed["response"]["usage"]["total_tokens"], 5);
+    }
+}
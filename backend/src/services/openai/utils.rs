@@ -6,11 +6,13 @@ use uuid::Uuid;
 use crate::{
     db::{request_logs::RequestLogContext, types::ApiType},
     middleware::auth::GatewayKeyId,
+    utils::request_body_hash::RequestBodyHash,
 };
 
 #[derive(Clone)]
 pub(super) struct RequestContext {
     pub request_id: Uuid,
+    pub parent_request_id: Uuid,
     pub gateway_key_id: GatewayKeyId,
     pub api_type: ApiType,
     pub model: String,
@@ -21,6 +23,8 @@ pub(super) struct RequestContext {
     pub client_ip: Option<String>,
     pub user_agent: Option<String>,
     pub request_body: Vec<u8>,
+    pub stream: bool,
+    pub request_body_hash: Option<RequestBodyHash>,
 }
 
 impl RequestContext {
@@ -29,12 +33,11 @@ impl RequestContext {
         status_code: Option<i32>,
         response_body: Option<Vec<u8>>,
         response_content_type: Option<String>,
-        prompt_tokens: Option<i32>,
-        completion_tokens: Option<i32>,
-        total_tokens: Option<i32>,
+        usage: Usage,
     ) -> RequestLogContext {
         RequestLogContext {
             request_id: self.request_id,
+            parent_request_id: Some(self.parent_request_id),
             gateway_key_id: Some(self.gateway_key_id.0),
             api_type: Some(self.api_type),
             model: Some(self.model.clone()),
@@ -49,9 +52,13 @@ impl RequestContext {
             response_body,
             request_content_type: Some("application/json".to_string()),
             response_content_type,
-            prompt_tokens,
-            completion_tokens,
-            total_tokens,
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+            cached_tokens: usage.cached_tokens,
+            ttft_ms: None,
+            stream: self.stream,
+            request_body_hash: self.request_body_hash,
         }
     }
 }
@@ -60,51 +67,43 @@ pub(super) fn elapsed_ms(start: Instant) -> i32 {
     i32::try_from(start.elapsed().as_millis()).unwrap_or(i32::MAX)
 }
 
-pub(super) fn extract_usage(
-    body: &[u8],
-    api_type: ApiType,
-) -> (Option<i32>, Option<i32>, Option<i32>) {
+/// Token counts reported by the upstream. `cached_tokens` is the part of
+/// `prompt_tokens` served from the provider's prompt cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct Usage {
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+    pub total_tokens: Option<i32>,
+    pub cached_tokens: Option<i32>,
+}
+
+impl Usage {
+    fn is_empty(&self) -> bool {
+        self.prompt_tokens.is_none()
+            && self.completion_tokens.is_none()
+            && self.total_tokens.is_none()
+    }
+}
+
+pub(super) fn extract_usage(body: &[u8], api_type: ApiType) -> Usage {
     // Try to parse as JSON first (non-streaming)
     if let Ok(json) = serde_json::from_slice::<Value>(body) {
-        match api_type {
+        let usage = match api_type {
             ApiType::OpenAiChatCompletions
             | ApiType::OpenAiEmbeddings
-            | ApiType::OpenAiResponses => {
-                if let Some(usage) = json.get("usage") {
-                    let prompt = usage
-                        .get("prompt_tokens")
-                        .and_then(Value::as_i64)
-                        .map(|v| v as i32);
-                    let completion = usage
-                        .get("completion_tokens")
-                        .and_then(Value::as_i64)
-                        .map(|v| v as i32);
-                    let total = usage
-                        .get("total_tokens")
-                        .and_then(Value::as_i64)
-                        .map(|v| v as i32);
-                    return (prompt, completion, total);
-                }
-            }
-            ApiType::AnthropicMessages => {
-                if let Some(usage) = json.get("usage") {
-                    let input = usage
-                        .get("input_tokens")
-                        .and_then(Value::as_i64)
-                        .map(|v| v as i32);
-                    let output = usage
-                        .get("output_tokens")
-                        .and_then(Value::as_i64)
-                        .map(|v| v as i32);
-                    let total = if let (Some(i), Some(o)) = (input, output) {
-                        Some(i + o)
-                    } else {
-                        None
-                    };
-                    return (input, output, total);
-                }
-            }
-            ApiType::OpenAiModels => {}
+            | ApiType::OpenAiResponses
+            | ApiType::AnthropicMessages => json.get("usage"),
+            // Streams without `alt=sse` are a JSON array of chunks.
+            ApiType::GeminiGenerateContent => json.get("usageMetadata").or_else(|| {
+                json.as_array()?
+                    .iter()
+                    .rev()
+                    .find_map(|chunk| chunk.get("usageMetadata"))
+            }),
+            ApiType::OpenAiModels => None,
+        };
+        if let Some(usage) = usage {
+            return usage_fields(usage, api_type);
         }
     }
 
@@ -112,6 +111,9 @@ pub(super) fn extract_usage(
     // We look for the "usage" field in the last few lines
     // This is a rough heuristic.
     let body_str = String::from_utf8_lossy(body);
+    if api_type == ApiType::AnthropicMessages {
+        return anthropic_stream_usage(&body_str);
+    }
     // OpenAi usage in stream: data: {"...": ..., "usage": {...}}
     // It might be one of the last data chunks.
     for line in body_str.lines().rev() {
@@ -120,49 +122,155 @@ pub(super) fn extract_usage(
                 continue;
             }
             if let Ok(json) = serde_json::from_str::<Value>(json_str)
-                && let Some(usage) = json.get("usage")
+                && let Some(usage) = json
+                    .get("usage")
+                    .or_else(|| json.pointer("/response/usage"))
+                    .or_else(|| json.get("usageMetadata"))
             {
-                // Same extraction logic as above
-                match api_type {
-                    ApiType::OpenAiChatCompletions
-                    | ApiType::OpenAiEmbeddings
-                    | ApiType::OpenAiResponses => {
-                        let prompt = usage
-                            .get("prompt_tokens")
-                            .and_then(Value::as_i64)
-                            .map(|v| v as i32);
-                        let completion = usage
-                            .get("completion_tokens")
-                            .and_then(Value::as_i64)
-                            .map(|v| v as i32);
-                        let total = usage
-                            .get("total_tokens")
-                            .and_then(Value::as_i64)
-                            .map(|v| v as i32);
-                        if prompt.is_some() || completion.is_some() || total.is_some() {
-                            return (prompt, completion, total);
-                        }
-                    }
-                    ApiType::AnthropicMessages => {
-                        // Anthropic streaming usage is different (message_delta), but for now let's see if usage is present
-                        if let Some(input) = usage
-                            .get("input_tokens")
-                            .and_then(Value::as_i64)
-                            .map(|v| v as i32)
-                        {
-                            let output = usage
-                                .get("output_tokens")
-                                .and_then(Value::as_i64)
-                                .map(|v| v as i32);
-                            let total = output.map(|o| input + o);
-                            return (Some(input), output, total);
-                        }
-                    }
-                    ApiType::OpenAiModels => {}
+                let usage = usage_fields(usage, api_type);
+                if !usage.is_empty() {
+                    return usage;
+                }
+            }
+        }
+    }
+
+    Usage::default()
+}
+
+fn usage_fields(usage: &Value, api_type: ApiType) -> Usage {
+    let count = |field: &str| usage.get(field).and_then(Value::as_i64).map(|v| v as i32);
+    match api_type {
+        ApiType::OpenAiChatCompletions | ApiType::OpenAiEmbeddings | ApiType::OpenAiResponses => {
+            Usage {
+                prompt_tokens: count("prompt_tokens").or_else(|| count("input_tokens")),
+                completion_tokens: count("completion_tokens").or_else(|| count("output_tokens")),
+                total_tokens: count("total_tokens"),
+                cached_tokens: usage
+                    .pointer("/prompt_tokens_details/cached_tokens")
+                    .or_else(|| usage.pointer("/input_tokens_details/cached_tokens"))
+                    .and_then(Value::as_i64)
+                    .map(|v| v as i32),
+            }
+        }
+        ApiType::AnthropicMessages => anthropic_usage(usage),
+        ApiType::GeminiGenerateContent => gemini_usage(usage),
+        ApiType::OpenAiModels => Usage::default(),
+    }
+}
+
+/// Anthropic reports cache reads and writes apart from `input_tokens`; they
+/// are all prompt tokens here, as in OpenAI usage.
+fn anthropic_usage(usage: &Value) -> Usage {
+    let count = |field: &str| usage.get(field).and_then(Value::as_i64).map(|v| v as i32);
+    let cached_tokens = count("cache_read_input_tokens");
+    let prompt_tokens = count("input_tokens").map(|input| {
+        input + cached_tokens.unwrap_or(0) + count("cache_creation_input_tokens").unwrap_or(0)
+    });
+    let completion_tokens = count("output_tokens");
+    let total_tokens = match (prompt_tokens, completion_tokens) {
+        (Some(prompt), Some(completion)) => Some(prompt + completion),
+        _ => None,
+    };
+    Usage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens,
+        cached_tokens,
+    }
+}
+
+/// Anthropic streams send the input tokens in `message_start` and running
+/// counts in each `message_delta`, so later fields override earlier ones.
+fn anthropic_stream_usage(body: &str) -> Usage {
+    let mut merged = serde_json::Map::new();
+    for json_str in body.lines().filter_map(|line| line.strip_prefix("data: ")) {
+        let Ok(json) = serde_json::from_str::<Value>(json_str) else {
+            continue;
+        };
+        let usage = json.pointer("/message/usage").or_else(|| json.get("usage"));
+        if let Some(Value::Object(usage)) = usage {
+            for (field, value) in usage {
+                if !value.is_null() {
+                    merged.insert(field.clone(), value.clone());
                 }
             }
         }
     }
+    anthropic_usage(&Value::Object(merged))
+}
+
+/// Gemini counts thinking tokens apart from candidate tokens; both are
+/// billed as output.
+fn gemini_usage(usage: &Value) -> Usage {
+    let count = |field: &str| usage.get(field).and_then(Value::as_i64).map(|v| v as i32);
+    let completion_tokens = match (count("candidatesTokenCount"), count("thoughtsTokenCount")) {
+        (None, None) => None,
+        (candidates, thoughts) => Some(candidates.unwrap_or(0) + thoughts.unwrap_or(0)),
+    };
+    Usage {
+        prompt_tokens: count("promptTokenCount"),
+        completion_tokens,
+        total_tokens: count("totalTokenCount"),
+        cached_tokens: count("cachedContentTokenCount"),
+    }
+}
 
-    (None, None, None)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_usage_reads_gemini_usage_metadata() {
+        let body = br#"{"candidates":[],"usageMetadata":{"promptTokenCount":7,"candidatesTokenCount":3,"thoughtsTokenCount":2,"totalTokenCount":12}}"#;
+        assert_eq!(
+            extract_usage(body, ApiType::GeminiGenerateContent),
+            Usage {
+                prompt_tokens: Some(7),
+                completion_tokens: Some(5),
+                total_tokens: Some(12),
+                cached_tokens: None,
+            }
+        );
+
+        let stream = b"data: {\"usageMetadata\":{\"promptTokenCount\":7}}\r\n\r\ndata: {\"usageMetadata\":{\"promptTokenCount\":7,\"candidatesTokenCount\":4,\"totalTokenCount\":11}}\r\n\r\n";
+        assert_eq!(
+            extract_usage(stream, ApiType::GeminiGenerateContent),
+            Usage {
+                prompt_tokens: Some(7),
+                completion_tokens: Some(4),
+                total_tokens: Some(11),
+                cached_tokens: None,
+            }
+        );
+
+        let json_stream = br#"[{"candidates":[]},{"usageMetadata":{"promptTokenCount":7,"candidatesTokenCount":4,"totalTokenCount":11}}]"#;
+        assert_eq!(
+            extract_usage(json_stream, ApiType::GeminiGenerateContent),
+            extract_usage(stream, ApiType::GeminiGenerateContent)
+        );
+    }
+
+    #[test]
+    fn extract_usage_merges_anthropic_stream_events() {
+        let stream = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":10,\"cache_read_input_tokens\":5,\"output_tokens\":1}}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":15}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+        assert_eq!(
+            extract_usage(stream.as_bytes(), ApiType::AnthropicMessages),
+            Usage {
+                prompt_tokens: Some(15),
+                completion_tokens: Some(15),
+                total_tokens: Some(30),
+                cached_tokens: Some(5),
+            }
+        );
+    }
 }
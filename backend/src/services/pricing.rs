@@ -0,0 +1,171 @@
+//! Token prices per upstream model. Each request's cost is computed once from
+//! the price in effect when it is logged; later price changes do not touch
+//! existing logs.
+
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        pricing::{self, ModelPricing, ModelPricingParams},
+        providers,
+    },
+    error::{AppError, AppResult},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ModelPricingInput {
+    pub provider_id: Uuid,
+    pub model_id: String,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    #[serde(default)]
+    pub cached_input_per_million: Option<f64>,
+}
+
+impl ModelPricingInput {
+    fn params(&self) -> ModelPricingParams<'_> {
+        ModelPricingParams {
+            provider_id: self.provider_id,
+            model_id: &self.model_id,
+            input_per_million: self.input_per_million,
+            output_per_million: self.output_per_million,
+            cached_input_per_million: self.cached_input_per_million,
+        }
+    }
+}
+
+pub async fn list_pricing(pool: &PgPool) -> AppResult<Vec<ModelPricing>> {
+    pricing::list_pricing(pool).await
+}
+
+pub async fn get_pricing(pool: &PgPool, id: Uuid) -> AppResult<Option<ModelPricing>> {
+    pricing::fetch_pricing_by_id(pool, id).await
+}
+
+pub async fn create_pricing(pool: &PgPool, input: &ModelPricingInput) -> AppResult<ModelPricing> {
+    validate(pool, None, input).await?;
+    let id = pricing::insert_pricing(pool, &input.params()).await?;
+    pricing::fetch_pricing_by_id(pool, id)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+pub async fn update_pricing(
+    pool: &PgPool,
+    id: Uuid,
+    input: &ModelPricingInput,
+) -> AppResult<Option<ModelPricing>> {
+    validate(pool, Some(id), input).await?;
+    if !pricing::update_pricing(pool, id, &input.params()).await? {
+        return Ok(None);
+    }
+    pricing::fetch_pricing_by_id(pool, id).await
+}
+
+pub async fn delete_pricing(pool: &PgPool, id: Uuid) -> AppResult<bool> {
+    pricing::delete_pricing(pool, id).await
+}
+
+/// Cost of a request to `model_id` at the provider, or `None` when the
+/// model is unpriced or the upstream reported no usage.
+pub async fn request_cost(
+    pool: &PgPool,
+    provider_name: &str,
+    model_id: &str,
+    tokens: TokenCounts,
+) -> AppResult<Option<f64>> {
+    if tokens.prompt.is_none() && tokens.completion.is_none() {
+        return Ok(None);
+    }
+    let pricing = pricing::fetch_pricing_for_model(pool, provider_name, model_id).await?;
+    Ok(pricing.map(|pricing| cost(&pricing, tokens)))
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokenCounts {
+    pub prompt: Option<i32>,
+    pub completion: Option<i32>,
+    /// Part of `prompt` read from the provider's prompt cache.
+    pub cached: Option<i32>,
+}
+
+fn cost(pricing: &ModelPricing, tokens: TokenCounts) -> f64 {
+    let prompt = f64::from(tokens.prompt.unwrap_or(0).max(0));
+    let cached = f64::from(tokens.cached.unwrap_or(0).max(0)).min(prompt);
+    let completion = f64::from(tokens.completion.unwrap_or(0).max(0));
+    let cached_price = pricing
+        .cached_input_per_million
+        .unwrap_or(pricing.input_per_million);
+
+    ((prompt - cached) * pricing.input_per_million
+        + cached * cached_price
+        + completion * pricing.output_per_million)
+        / 1_000_000.0
+}
+
+async fn validate(pool: &PgPool, id: Option<Uuid>, input: &ModelPricingInput) -> AppResult<()> {
+    if input.model_id.trim().is_empty() {
+        return Err(AppError::BadRequest(
+            "model_id must not be empty".to_string(),
+        ));
+    }
+    let prices = [input.input_per_million, input.output_per_million]
+        .into_iter()
+        .chain(input.cached_input_per_million);
+    for price in prices {
+        if !price.is_finite() || price < 0.0 {
+            return Err(AppError::BadRequest(
+                "prices must be non-negative numbers".to_string(),
+            ));
+        }
+    }
+    if providers::fetch_provider_by_id(pool, input.provider_id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::BadRequest("provider not found".to_string()));
+    }
+    if let Some(existing) =
+        pricing::fetch_pricing_id(pool, input.provider_id, &input.model_id).await?
+        && Some(existing) != id
+    {
+        return Err(AppError::BadRequest(format!(
+            "model {} already has a price at this provider",
+            input.model_id
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::*;
+
+    #[test]
+    fn cost_charges_cached_prompt_tokens_at_the_cached_price() {
+        let mut pricing = ModelPricing {
+            id: Uuid::nil(),
+            provider_id: Uuid::nil(),
+            provider_name: "openai".to_string(),
+            model_id: "gpt-4o".to_string(),
+            input_per_million: 2.0,
+            output_per_million: 8.0,
+            cached_input_per_million: Some(0.5),
+            created_at: OffsetDateTime::UNIX_EPOCH,
+            updated_at: OffsetDateTime::UNIX_EPOCH,
+        };
+        let tokens = TokenCounts {
+            prompt: Some(1_000_000),
+            completion: Some(250_000),
+            cached: Some(400_000),
+        };
+        assert_eq!(cost(&pricing, tokens), 3.4);
+
+        pricing.cached_input_per_million = None;
+        assert_eq!(cost(&pricing, tokens), 4.0);
+    }
+}
@@ -5,6 +5,8 @@ use crate::db::{
     providers::{self, CreateProviderParams, Provider, UpdateProviderParams},
 };
 use crate::error::AppResult;
+use crate::services::circuit_breaker::{BreakerSnapshot, CircuitBreakers};
+use serde::Serialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -143,3 +145,71 @@ pub async fn update_key(
 pub async fn delete_key(pool: &PgPool, id: Uuid) -> AppResult<bool> {
     provider_keys::delete_key(pool, id).await
 }
+
+// Health
+
+#[derive(Debug, Serialize)]
+pub struct ProviderHealth {
+    pub provider_id: Uuid,
+    pub endpoints: Vec<EndpointHealth>,
+    pub keys: Vec<KeyHealth>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EndpointHealth {
+    pub endpoint_id: Uuid,
+    pub api_type: ApiType,
+    pub url: String,
+    pub enabled: bool,
+    pub breaker: BreakerSnapshot,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyHealth {
+    pub key_id: Uuid,
+    pub name: Option<String>,
+    pub enabled: bool,
+    pub breaker: BreakerSnapshot,
+}
+
+pub async fn get_provider_health(
+    pool: &PgPool,
+    breakers: &CircuitBreakers,
+    provider_id: Uuid,
+) -> AppResult<Option<ProviderHealth>> {
+    if providers::fetch_provider_by_id(pool, provider_id)
+        .await?
+        .is_none()
+    {
+        return Ok(None);
+    }
+
+    let endpoints = provider_endpoints::list_endpoints_by_provider(pool, provider_id)
+        .await?
+        .into_iter()
+        .map(|endpoint| EndpointHealth {
+            breaker: breakers.endpoint_snapshot(endpoint.id),
+            endpoint_id: endpoint.id,
+            api_type: endpoint.api_type,
+            url: endpoint.url,
+            enabled: endpoint.enabled,
+        })
+        .collect();
+
+    let keys = provider_keys::list_keys_by_provider(pool, provider_id)
+        .await?
+        .into_iter()
+        .map(|key| KeyHealth {
+            breaker: breakers.key_snapshot(key.id),
+            key_id: key.id,
+            name: key.name,
+            enabled: key.enabled,
+        })
+        .collect();
+
+    Ok(Some(ProviderHealth {
+        provider_id,
+        endpoints,
+        keys,
+    }))
+}
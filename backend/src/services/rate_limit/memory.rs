@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Instant,
+};
+
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use super::{Bucket, LimitState, RateLimitKind, RateLimited};
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn full(bucket: &Bucket, now: Instant) -> Self {
+        Self {
+            tokens: bucket.capacity,
+            last_refill: now,
+        }
+    }
+
+    /// Also clamps to the current capacity, which shrinks when a key's limit
+    /// is lowered.
+    fn refill(&mut self, bucket: &Bucket, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        if elapsed > 0.0 {
+            self.tokens += elapsed * bucket.refill_per_sec;
+            self.last_refill = now;
+        }
+        self.tokens = self.tokens.min(bucket.capacity);
+    }
+
+    fn can_consume(&self, amount: f64) -> bool {
+        self.tokens >= amount
+    }
+
+    fn consume(&mut self, amount: f64) {
+        self.tokens = (self.tokens - amount).max(0.0);
+    }
+
+    /// Applies a correction after the fact. Unlike `consume` this may leave
+    /// the bucket in debt, which later requests wait out.
+    fn adjust(&mut self, bucket: &Bucket, amount: f64) {
+        self.tokens = (self.tokens - amount).clamp(-bucket.capacity, bucket.capacity);
+    }
+}
+
+/// Buckets are grouped per key so that checking several limits of one key
+/// takes a single lock.
+#[derive(Debug, Clone, Default)]
+pub(super) struct MemoryStore {
+    buckets: Arc<DashMap<Uuid, HashMap<RateLimitKind, TokenBucket>>>,
+    in_flight: Arc<DashMap<Uuid, Arc<AtomicU32>>>,
+}
+
+impl MemoryStore {
+    pub(super) fn consume(
+        &self,
+        gateway_key_id: Uuid,
+        buckets: &[Bucket],
+        amount: f64,
+    ) -> Result<(), RateLimited> {
+        let now = Instant::now();
+        let mut entry = self.buckets.entry(gateway_key_id).or_default();
+
+        for bucket in buckets {
+            let state = entry
+                .entry(bucket.kind)
+                .or_insert_with(|| TokenBucket::full(bucket, now));
+            state.refill(bucket, now);
+            if !state.can_consume(bucket.reservable(amount)) {
+                return Err(bucket.rejection(state.tokens, amount));
+            }
+        }
+
+        for bucket in buckets {
+            if let Some(state) = entry.get_mut(&bucket.kind) {
+                state.consume(bucket.reservable(amount));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn adjust(&self, gateway_key_id: Uuid, buckets: &[Bucket], amount: f64) {
+        let now = Instant::now();
+        if let Some(mut entry) = self.buckets.get_mut(&gateway_key_id) {
+            for bucket in buckets {
+                if let Some(state) = entry.get_mut(&bucket.kind) {
+                    state.refill(bucket, now);
+                    state.adjust(bucket, amount);
+                }
+            }
+        }
+    }
+
+    pub(super) fn states(&self, gateway_key_id: Uuid, buckets: &[Bucket]) -> Vec<LimitState> {
+        let now = Instant::now();
+        let mut entry = self.buckets.entry(gateway_key_id).or_default();
+        buckets
+            .iter()
+            .map(|bucket| {
+                let state = entry
+                    .entry(bucket.kind)
+                    .or_insert_with(|| TokenBucket::full(bucket, now));
+                state.refill(bucket, now);
+                bucket.state(state.tokens)
+            })
+            .collect()
+    }
+
+    pub(super) fn acquire_in_flight(
+        &self,
+        gateway_key_id: Uuid,
+        limit: u32,
+    ) -> Option<Arc<AtomicU32>> {
+        let counter = self.in_flight.entry(gateway_key_id).or_default().clone();
+        counter
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < limit).then_some(count + 1)
+            })
+            .ok()?;
+        Some(counter)
+    }
+}
+
+pub(super) fn release_in_flight(counter: &AtomicU32) {
+    counter.fetch_sub(1, Ordering::AcqRel);
+}
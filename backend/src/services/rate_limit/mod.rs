@@ -0,0 +1,581 @@
+//! Per-key request, token and concurrency limits. State lives in process
+//! memory by default, or in Postgres so that several gateway replicas share
+//! the same limits.
+
+mod memory;
+mod postgres;
+
+use std::{
+    fmt,
+    sync::{Arc, atomic::AtomicU32},
+    time::Duration,
+};
+
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::constants::{ESTIMATED_CHARS_PER_TOKEN, SECONDS_PER_DAY};
+use crate::db::gateway_keys::{self, KeyLimits};
+use crate::error::AppResult;
+use crate::services::background::BackgroundTasks;
+
+use memory::MemoryStore;
+use postgres::PostgresStore;
+
+/// The limit a request was rejected by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitKind {
+    RequestsPerSecond,
+    RequestsPerMinute,
+    TokensPerMinute,
+    TokensPerDay,
+    ConcurrentRequests,
+}
+
+const REQUEST_LIMITS: [RateLimitKind; 2] = [
+    RateLimitKind::RequestsPerSecond,
+    RateLimitKind::RequestsPerMinute,
+];
+const TOKEN_LIMITS: [RateLimitKind; 2] =
+    [RateLimitKind::TokensPerMinute, RateLimitKind::TokensPerDay];
+
+impl RateLimitKind {
+    /// Name of the bucket in the shared store, also used as a metric label.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::RequestsPerSecond => "rps",
+            Self::RequestsPerMinute => "rpm",
+            Self::TokensPerMinute => "tpm",
+            Self::TokensPerDay => "tpd",
+            Self::ConcurrentRequests => "concurrent",
+        }
+    }
+
+    fn window_secs(self) -> f64 {
+        match self {
+            Self::RequestsPerSecond => 1.0,
+            Self::RequestsPerMinute | Self::TokensPerMinute => 60.0,
+            Self::TokensPerDay => SECONDS_PER_DAY as f64,
+            Self::ConcurrentRequests => 0.0,
+        }
+    }
+
+    fn limit(self, limits: &KeyLimits) -> Option<i32> {
+        let limit = match self {
+            Self::RequestsPerSecond => limits.rate_limit_rps,
+            Self::RequestsPerMinute => limits.rate_limit_rpm,
+            Self::TokensPerMinute => limits.rate_limit_tpm,
+            Self::TokensPerDay => limits.rate_limit_tpd,
+            Self::ConcurrentRequests => limits.max_concurrent_requests,
+        };
+        sanitize_limit(limit)
+    }
+}
+
+impl fmt::Display for RateLimitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::RequestsPerSecond => "requests per second",
+            Self::RequestsPerMinute => "requests per minute",
+            Self::TokensPerMinute => "tokens per minute",
+            Self::TokensPerDay => "tokens per day",
+            Self::ConcurrentRequests => "concurrent requests",
+        })
+    }
+}
+
+/// A request turned away by one of a key's limits, with how long until the
+/// limit would let it through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    pub kind: RateLimitKind,
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} limit exceeded", self.kind)
+    }
+}
+
+/// Where one limit of a key stands, as reported in `x-ratelimit-*` headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitState {
+    pub limit: u64,
+    pub remaining: u64,
+    /// Until the limit is fully replenished.
+    pub reset: Duration,
+}
+
+/// The most restrictive request limit and token limit of a key.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimitSnapshot {
+    pub requests: Option<LimitState>,
+    pub tokens: Option<LimitState>,
+}
+
+/// A configured token bucket: `capacity` tokens, refilled evenly over the
+/// limit's window.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    kind: RateLimitKind,
+    capacity: f64,
+    refill_per_sec: f64,
+}
+
+impl Bucket {
+    fn configured(kinds: [RateLimitKind; 2], limits: &KeyLimits) -> Vec<Bucket> {
+        kinds
+            .into_iter()
+            .filter_map(|kind| {
+                let capacity = f64::from(kind.limit(limits)?);
+                Some(Bucket {
+                    kind,
+                    capacity,
+                    refill_per_sec: capacity / kind.window_secs(),
+                })
+            })
+            .collect()
+    }
+
+    /// Time until `amount` could be taken from a bucket holding `tokens`.
+    /// Amounts above capacity wait for a full bucket, which is the most they
+    /// can ever get.
+    fn wait_for(&self, tokens: f64, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - tokens;
+        Duration::from_secs_f64((missing / self.refill_per_sec).max(0.0))
+    }
+
+    /// What `amount` takes from the bucket up front. Amounts above capacity
+    /// could never be covered, so they take a full bucket instead.
+    fn reservable(&self, amount: f64) -> f64 {
+        amount.min(self.capacity)
+    }
+
+    fn rejection(&self, tokens: f64, amount: f64) -> RateLimited {
+        RateLimited {
+            kind: self.kind,
+            retry_after: self.wait_for(tokens, amount),
+        }
+    }
+
+    fn state(&self, tokens: f64) -> LimitState {
+        LimitState {
+            limit: self.capacity as u64,
+            remaining: tokens.max(0.0).floor() as u64,
+            reset: self.wait_for(tokens, self.capacity),
+        }
+    }
+}
+
+/// The state with the fewest requests or tokens left; ties go to the one
+/// that takes longer to recover.
+fn tightest(states: Vec<LimitState>) -> Option<LimitState> {
+    states
+        .into_iter()
+        .min_by(|a, b| a.remaining.cmp(&b.remaining).then(b.reset.cmp(&a.reset)))
+}
+
+#[derive(Clone)]
+enum Store {
+    Memory(MemoryStore),
+    Postgres(PostgresStore),
+}
+
+impl Store {
+    /// Takes `amount`, capped at capacity, from every bucket, or from none if
+    /// any cannot cover it.
+    async fn consume(
+        &self,
+        gateway_key_id: Uuid,
+        buckets: &[Bucket],
+        amount: f64,
+    ) -> Result<(), RateLimited> {
+        match self {
+            Self::Memory(store) => store.consume(gateway_key_id, buckets, amount),
+            Self::Postgres(store) => store.consume(gateway_key_id, buckets, amount).await,
+        }
+    }
+
+    /// Corrects earlier consumption; may leave buckets in debt.
+    fn adjust(&self, gateway_key_id: Uuid, buckets: Vec<Bucket>, amount: f64) {
+        match self {
+            Self::Memory(store) => store.adjust(gateway_key_id, &buckets, amount),
+            Self::Postgres(store) => store.spawn_adjust(gateway_key_id, buckets, amount),
+        }
+    }
+
+    async fn states(&self, gateway_key_id: Uuid, buckets: &[Bucket]) -> Vec<LimitState> {
+        match self {
+            Self::Memory(store) => store.states(gateway_key_id, buckets),
+            Self::Postgres(store) => store.states(gateway_key_id, buckets).await,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Store,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimiter {
+    /// Limits kept in this process only.
+    pub fn new() -> Self {
+        Self {
+            store: Store::Memory(MemoryStore::default()),
+        }
+    }
+
+    /// Limits shared through Postgres by every replica using the database.
+    pub fn postgres(pool: PgPool, background_tasks: BackgroundTasks) -> Self {
+        Self {
+            store: Store::Postgres(PostgresStore::new(pool, background_tasks)),
+        }
+    }
+
+    /// Admits one more in-flight request for the key, if it is below its
+    /// concurrency limit. The request counts until the permit is dropped.
+    pub async fn acquire_in_flight(
+        &self,
+        gateway_key_id: Uuid,
+        limits: &KeyLimits,
+    ) -> Result<Option<InFlightPermit>, RateLimited> {
+        let Some(limit) = RateLimitKind::ConcurrentRequests.limit(limits) else {
+            return Ok(None);
+        };
+
+        let permit = match &self.store {
+            Store::Memory(store) => store
+                .acquire_in_flight(gateway_key_id, limit as u32)
+                .map(Permit::Memory),
+            Store::Postgres(store) => store
+                .acquire_in_flight(gateway_key_id, i64::from(limit))
+                .await
+                .map(|id| Permit::Postgres(store.clone(), id)),
+        };
+
+        match permit {
+            Some(permit) => Ok(Some(InFlightPermit(permit))),
+            None => Err(RateLimited {
+                kind: RateLimitKind::ConcurrentRequests,
+                retry_after: Duration::from_secs(1),
+            }),
+        }
+    }
+
+    pub async fn check_and_consume(
+        &self,
+        gateway_key_id: Uuid,
+        limits: &KeyLimits,
+    ) -> Result<(), RateLimited> {
+        let buckets = Bucket::configured(REQUEST_LIMITS, limits);
+        if buckets.is_empty() {
+            return Ok(());
+        }
+
+        self.store.consume(gateway_key_id, &buckets, 1.0).await
+    }
+
+    /// Reserves the estimated prompt tokens of a request against the key's
+    /// token limits. The reservation is corrected to the real usage once the
+    /// response is known; see [`TokenReservation`].
+    pub async fn reserve_tokens(
+        &self,
+        gateway_key_id: Uuid,
+        limits: &KeyLimits,
+        estimated_tokens: u32,
+    ) -> Result<Option<TokenReservation>, RateLimited> {
+        let buckets = Bucket::configured(TOKEN_LIMITS, limits);
+        if buckets.is_empty() {
+            return Ok(None);
+        }
+
+        let amount = f64::from(estimated_tokens);
+        self.store.consume(gateway_key_id, &buckets, amount).await?;
+        // Prompts larger than a bucket get in once it is full and leave the
+        // rest as debt, so settling and refunds stay relative to the estimate.
+        for bucket in &buckets {
+            let overflow = amount - bucket.reservable(amount);
+            if overflow > 0.0 {
+                self.store.adjust(gateway_key_id, vec![*bucket], overflow);
+            }
+        }
+
+        Ok(Some(TokenReservation {
+            store: self.store.clone(),
+            gateway_key_id,
+            buckets,
+            estimated_tokens,
+            settled: false,
+        }))
+    }
+
+    pub async fn snapshot(&self, gateway_key_id: Uuid, limits: &KeyLimits) -> RateLimitSnapshot {
+        let requests = Bucket::configured(REQUEST_LIMITS, limits);
+        let tokens = Bucket::configured(TOKEN_LIMITS, limits);
+        RateLimitSnapshot {
+            requests: tightest(self.store.states(gateway_key_id, &requests).await),
+            tokens: tightest(self.store.states(gateway_key_id, &tokens).await),
+        }
+    }
+}
+
+enum Permit {
+    Memory(Arc<AtomicU32>),
+    Postgres(PostgresStore, Uuid),
+}
+
+/// One in-flight request of a key; see [`RateLimiter::acquire_in_flight`].
+pub struct InFlightPermit(Permit);
+
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        match &self.0 {
+            Permit::Memory(counter) => memory::release_in_flight(counter),
+            Permit::Postgres(store, id) => store.spawn_release_in_flight(*id),
+        }
+    }
+}
+
+/// Estimated tokens taken from a key's token buckets before forwarding.
+/// Settling it replaces the estimate with the usage the upstream reported;
+/// dropping it unsettled, as failed requests do, gives the estimate back.
+pub struct TokenReservation {
+    store: Store,
+    gateway_key_id: Uuid,
+    buckets: Vec<Bucket>,
+    estimated_tokens: u32,
+    settled: bool,
+}
+
+impl TokenReservation {
+    /// Without reported usage the estimate stands.
+    pub fn settle(mut self, total_tokens: Option<i32>) {
+        self.settled = true;
+        if let Some(total_tokens) = total_tokens {
+            self.adjust(f64::from(total_tokens.max(0)) - f64::from(self.estimated_tokens));
+        }
+    }
+
+    fn adjust(&mut self, amount: f64) {
+        if amount != 0.0 {
+            let buckets = std::mem::take(&mut self.buckets);
+            self.store.adjust(self.gateway_key_id, buckets, amount);
+        }
+    }
+}
+
+impl Drop for TokenReservation {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.adjust(-f64::from(self.estimated_tokens));
+        }
+    }
+}
+
+/// Rough prompt size of a request: the text it carries, at about four
+/// characters per token. Inline base64 data is left out since providers
+/// bill images and audio by other means.
+pub fn estimate_prompt_tokens(payload: &Value) -> u32 {
+    fn count_chars(value: &Value) -> usize {
+        match value {
+            Value::String(text) if text.starts_with("data:") => 0,
+            Value::String(text) => text.chars().count(),
+            Value::Array(items) => items.iter().map(count_chars).sum(),
+            Value::Object(fields) => fields
+                .iter()
+                .filter(|(key, _)| key.as_str() != "model")
+                .map(|(_, value)| count_chars(value))
+                .sum(),
+            _ => 0,
+        }
+    }
+
+    let tokens = count_chars(payload).div_ceil(ESTIMATED_CHARS_PER_TOKEN);
+    u32::try_from(tokens).unwrap_or(u32::MAX)
+}
+
+fn sanitize_limit(limit: Option<i32>) -> Option<i32> {
+    match limit {
+        Some(value) if value > 0 => Some(value),
+        _ => None,
+    }
+}
+
+pub async fn fetch_limits(pool: &PgPool, gateway_key_id: Uuid) -> AppResult<KeyLimits> {
+    gateway_keys::fetch_limits(pool, gateway_key_id).await
+}
+
+/// Formats a duration like `1h2m3s`, `1.5s` or `250ms`.
+pub fn format_reset(reset: Duration) -> String {
+    let millis = reset.as_millis();
+    if millis < 1000 {
+        return format!("{millis}ms");
+    }
+
+    let total_secs = millis / 1000;
+    let (hours, minutes, secs) = (total_secs / 3600, total_secs % 3600 / 60, total_secs % 60);
+    let fraction = millis % 1000;
+    let secs = if fraction == 0 {
+        secs.to_string()
+    } else {
+        format!("{secs}.{fraction:03}")
+            .trim_end_matches('0')
+            .to_string()
+    };
+
+    if hours > 0 {
+        format!("{hours}h{minutes}m{secs}s")
+    } else if minutes > 0 {
+        format!("{minutes}m{secs}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn token_reservations_settle_to_reported_usage() {
+        let limiter = RateLimiter::new();
+        let key = Uuid::now_v7();
+        let limits = KeyLimits {
+            rate_limit_tpm: Some(1000),
+            ..KeyLimits::default()
+        };
+
+        let reservation = limiter
+            .reserve_tokens(key, &limits, 400)
+            .await
+            .unwrap()
+            .unwrap();
+        reservation.settle(Some(900));
+        assert_eq!(
+            limiter
+                .reserve_tokens(key, &limits, 400)
+                .await
+                .err()
+                .map(|limited| limited.kind),
+            Some(RateLimitKind::TokensPerMinute)
+        );
+
+        let limiter = RateLimiter::new();
+        drop(limiter.reserve_tokens(key, &limits, 800).await.unwrap());
+        assert!(
+            limiter
+                .reserve_tokens(key, &limits, 800)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        let payload =
+            json!({ "model": "alias", "messages": [{ "role": "user", "content": "12345678" }] });
+        assert_eq!(estimate_prompt_tokens(&payload), 3);
+    }
+
+    async fn assert_oversized_prompt_is_admitted_into_debt(
+        limiter: &RateLimiter,
+        background_tasks: &BackgroundTasks,
+        key: Uuid,
+    ) {
+        let limits = KeyLimits {
+            rate_limit_tpm: Some(1000),
+            ..KeyLimits::default()
+        };
+
+        let reservation = limiter
+            .reserve_tokens(key, &limits, 1500)
+            .await
+            .unwrap()
+            .unwrap();
+        reservation.settle(None);
+        while background_tasks.pending_count() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // 500 tokens of debt take 30s to pay off at 1000 per minute.
+        let limited = limiter.reserve_tokens(key, &limits, 1).await.err().unwrap();
+        assert_eq!(limited.kind, RateLimitKind::TokensPerMinute);
+        assert!(limited.retry_after > Duration::from_secs(29));
+    }
+
+    #[tokio::test]
+    async fn oversized_prompts_are_admitted_into_debt_in_memory() {
+        assert_oversized_prompt_is_admitted_into_debt(
+            &RateLimiter::new(),
+            &BackgroundTasks::new(),
+            Uuid::now_v7(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL pointing at a migrated database"]
+    async fn oversized_prompts_are_admitted_into_debt_in_postgres() {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+        let key = gateway_keys::create_gateway_key(
+            &pool,
+            gateway_keys::CreateGatewayKeyParams {
+                name: None,
+                key: format!("test-{}", Uuid::now_v7()),
+                limits: KeyLimits::default(),
+                cache: Default::default(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let background_tasks = BackgroundTasks::new();
+        let limiter = RateLimiter::postgres(pool.clone(), background_tasks.clone());
+        assert_oversized_prompt_is_admitted_into_debt(&limiter, &background_tasks, key.id).await;
+
+        gateway_keys::delete_gateway_key(&pool, key.id)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn in_flight_permits_are_released_on_drop() {
+        let limiter = RateLimiter::new();
+        let key = Uuid::now_v7();
+        let limits = KeyLimits {
+            max_concurrent_requests: Some(1),
+            ..KeyLimits::default()
+        };
+
+        let permit = limiter.acquire_in_flight(key, &limits).await.unwrap();
+        assert!(permit.is_some());
+        assert_eq!(
+            limiter
+                .acquire_in_flight(key, &limits)
+                .await
+                .err()
+                .map(|limited| limited.kind),
+            Some(RateLimitKind::ConcurrentRequests)
+        );
+        drop(permit);
+        assert!(limiter.acquire_in_flight(key, &limits).await.is_ok());
+    }
+
+    #[test]
+    fn format_reset_matches_openai_durations() {
+        assert_eq!(format_reset(Duration::from_millis(250)), "250ms");
+        assert_eq!(format_reset(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(format_reset(Duration::from_secs(360)), "6m0s");
+        assert_eq!(format_reset(Duration::from_secs(3723)), "1h2m3s");
+    }
+}
@@ -0,0 +1,136 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{Bucket, LimitState, RateLimited};
+use crate::{
+    constants::IN_FLIGHT_LEASE_SECS,
+    db::rate_limits::{self, BucketLimit, ConsumeOutcome},
+    services::background::BackgroundTasks,
+};
+
+/// Shares limit state between replicas through Postgres. Errors fail open:
+/// they are logged and the request is let through, since the request path
+/// needs the database anyway.
+#[derive(Clone)]
+pub(super) struct PostgresStore {
+    pool: PgPool,
+    background_tasks: BackgroundTasks,
+}
+
+fn bucket_limits(buckets: &[Bucket]) -> Vec<BucketLimit<'static>> {
+    buckets
+        .iter()
+        .map(|bucket| BucketLimit {
+            name: bucket.kind.as_str(),
+            capacity: bucket.capacity,
+            refill_per_sec: bucket.refill_per_sec,
+        })
+        .collect()
+}
+
+impl PostgresStore {
+    pub(super) fn new(pool: PgPool, background_tasks: BackgroundTasks) -> Self {
+        Self {
+            pool,
+            background_tasks,
+        }
+    }
+
+    pub(super) async fn consume(
+        &self,
+        gateway_key_id: Uuid,
+        buckets: &[Bucket],
+        amount: f64,
+    ) -> Result<(), RateLimited> {
+        match rate_limits::consume_buckets(
+            &self.pool,
+            gateway_key_id,
+            &bucket_limits(buckets),
+            amount,
+        )
+        .await
+        {
+            Ok(ConsumeOutcome::Consumed) => Ok(()),
+            Ok(ConsumeOutcome::Rejected { index, tokens }) => {
+                Err(buckets[index].rejection(tokens, amount))
+            }
+            Err(err) => {
+                tracing::error!(error = %err, "failed to consume shared rate limit buckets");
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) fn spawn_adjust(&self, gateway_key_id: Uuid, buckets: Vec<Bucket>, amount: f64) {
+        let pool = self.pool.clone();
+        let shutdown_token = self.background_tasks.token();
+        self.background_tasks
+            .spawn("rate_limits.adjust_buckets", async move {
+                if shutdown_token.is_cancelled() {
+                    return;
+                }
+                if let Err(err) = rate_limits::adjust_buckets(
+                    &pool,
+                    gateway_key_id,
+                    &bucket_limits(&buckets),
+                    amount,
+                )
+                .await
+                {
+                    tracing::error!(error = %err, "failed to adjust shared rate limit buckets");
+                }
+            });
+    }
+
+    pub(super) async fn states(&self, gateway_key_id: Uuid, buckets: &[Bucket]) -> Vec<LimitState> {
+        if buckets.is_empty() {
+            return Vec::new();
+        }
+
+        match rate_limits::fetch_bucket_tokens(&self.pool, gateway_key_id, &bucket_limits(buckets))
+            .await
+        {
+            Ok(tokens) => buckets
+                .iter()
+                .zip(tokens)
+                .map(|(bucket, tokens)| bucket.state(tokens))
+                .collect(),
+            Err(err) => {
+                tracing::error!(error = %err, "failed to fetch shared rate limit buckets");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Returns the id of the recorded request, or `None` when the key is at
+    /// its limit.
+    pub(super) async fn acquire_in_flight(&self, gateway_key_id: Uuid, limit: i64) -> Option<Uuid> {
+        let id = Uuid::now_v7();
+        match rate_limits::acquire_in_flight(
+            &self.pool,
+            id,
+            gateway_key_id,
+            limit,
+            IN_FLIGHT_LEASE_SECS as f64,
+        )
+        .await
+        {
+            Ok(true) => Some(id),
+            Ok(false) => None,
+            Err(err) => {
+                tracing::error!(error = %err, "failed to record shared in-flight request");
+                Some(id)
+            }
+        }
+    }
+
+    pub(super) fn spawn_release_in_flight(&self, id: Uuid) {
+        let pool = self.pool.clone();
+        self.background_tasks
+            .spawn("rate_limits.release_in_flight", async move {
+                if let Err(err) = rate_limits::release_in_flight(&pool, id).await {
+                    tracing::error!(error = %err, "failed to release shared in-flight request");
+                }
+            });
+    }
+}
@@ -1,40 +1,499 @@
-use crate::db::request_logs::CachedResponse;
-use moka::sync::Cache;
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, header};
+use moka::{Expiry, sync::Cache};
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    config::ResponseCacheConfig,
+    constants::CACHE_TTL_HEADER,
+    db::{
+        aliases, gateway_keys,
+        request_logs::CachedResponse,
+        types::{ApiType, CachePolicy, CacheSettings},
+    },
+    error::{AppError, AppResult},
+    utils::extract_model_from_payload,
+};
 
 pub use crate::utils::request_body_hash::{
-    RequestBodyHash, hash_request_body, request_body_hash_hex,
+    RequestBodyHash, hash_scoped_request_body, parse_request_body_hash_hex, request_body_hash_hex,
 };
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct ResponseCacheKey {
     pub request_body_hash: RequestBodyHash,
+    pub stream: bool,
 }
 
 impl ResponseCacheKey {
-    pub fn new(request_body: &[u8]) -> Self {
+    /// `scope` is the gateway key for per-key caching, `None` when shared.
+    pub fn new(request_body: &[u8], scope: Option<Uuid>, stream: bool) -> Self {
+        Self {
+            request_body_hash: hash_scoped_request_body(request_body, scope),
+            stream,
+        }
+    }
+
+    /// The same request in the given stream mode.
+    pub fn with_stream(&self, stream: bool) -> Self {
         Self {
-            request_body_hash: hash_request_body(request_body),
+            request_body_hash: self.request_body_hash,
+            stream,
         }
     }
 }
 
+#[derive(Clone)]
+struct CacheEntry {
+    response: CachedResponse,
+    expires_in: Duration,
+}
+
+/// Evicts each entry once the TTL it was inserted with has run out.
+struct EntryExpiry;
+
+impl Expiry<ResponseCacheKey, CacheEntry> for EntryExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &ResponseCacheKey,
+        entry: &CacheEntry,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(entry.expires_in)
+    }
+}
+
 #[derive(Clone)]
 pub struct ResponseCache {
-    inner: Cache<ResponseCacheKey, CachedResponse>,
+    inner: Cache<ResponseCacheKey, CacheEntry>,
+    default_ttl: Duration,
+    max_body_bytes: usize,
+    stream_replay_interval: Duration,
+    stream_synthesis: bool,
+    semantic_embeddings_alias: Option<String>,
 }
 
 impl ResponseCache {
-    pub fn new() -> Self {
-        let inner = Cache::builder().max_capacity(1000).build();
+    pub fn new(config: &ResponseCacheConfig) -> Self {
+        let inner = Cache::builder()
+            .max_capacity(config.max_entries)
+            .expire_after(EntryExpiry)
+            .build();
+
+        Self {
+            inner,
+            default_ttl: Duration::from_secs(config.ttl_secs),
+            max_body_bytes: config.max_body_bytes,
+            stream_replay_interval: Duration::from_millis(config.stream_replay_interval_ms),
+            stream_synthesis: config.stream_synthesis,
+            semantic_embeddings_alias: config.semantic_embeddings_alias.clone(),
+        }
+    }
+
+    pub fn default_ttl(&self) -> Duration {
+        self.default_ttl
+    }
+
+    pub fn max_body_bytes(&self) -> usize {
+        self.max_body_bytes
+    }
+
+    pub fn stream_replay_interval(&self) -> Duration {
+        self.stream_replay_interval
+    }
 
-        Self { inner }
+    pub fn stream_synthesis(&self) -> bool {
+        self.stream_synthesis
     }
 
-    pub fn get(&self, key: &ResponseCacheKey) -> Option<CachedResponse> {
-        self.inner.get(key)
+    pub fn semantic_embeddings_alias(&self) -> Option<&str> {
+        self.semantic_embeddings_alias.as_deref()
+    }
+
+    /// Returns the cached response if it is no older than `ttl`. Entries are
+    /// shared, so one inserted under a longer TTL may still be too old here.
+    pub fn get(&self, key: &ResponseCacheKey, ttl: Duration) -> Option<CachedResponse> {
+        self.inner
+            .get(key)
+            .map(|entry| entry.response)
+            .filter(|response| response.age() <= ttl)
+    }
+
+    pub fn insert(&self, key: ResponseCacheKey, value: CachedResponse, ttl: Duration) {
+        let expires_in = ttl.saturating_sub(value.age());
+        if expires_in.is_zero() || value.response_body.len() > self.max_body_bytes {
+            return;
+        }
+        self.inner.insert(
+            key,
+            CacheEntry {
+                response: value,
+                expires_in,
+            },
+        );
+    }
+
+    /// Drops the entries for this hash in both stream modes.
+    pub fn invalidate(&self, request_body_hash: RequestBodyHash) {
+        for stream in [false, true] {
+            self.inner.invalidate(&ResponseCacheKey {
+                request_body_hash,
+                stream,
+            });
+        }
+    }
+
+    /// Drops every in-memory entry and returns how many there were.
+    pub fn flush(&self) -> u64 {
+        self.inner.run_pending_tasks();
+        let count = self.inner.entry_count();
+        self.inner.invalidate_all();
+        count
+    }
+}
+
+/// Why a request skipped the cache lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BypassReason {
+    PolicyDisabled,
+    NonDeterministic,
+    NoStore,
+    NoCache,
+    ZeroTtl,
+}
+
+impl BypassReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PolicyDisabled => "policy_disabled",
+            Self::NonDeterministic => "non_deterministic",
+            Self::NoStore => "no_store",
+            Self::NoCache => "no_cache",
+            Self::ZeroTtl => "zero_ttl",
+        }
+    }
+}
+
+/// What the response cache does with one request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePlan {
+    pub policy: CachePolicy,
+    pub ttl: Duration,
+    /// Set when the lookup is skipped.
+    pub bypass: Option<BypassReason>,
+    /// Policy the upstream response is stored under.
+    pub store_policy: CachePolicy,
+}
+
+impl CachePlan {
+    /// The most restrictive policy set on the alias or key applies, per-key
+    /// when neither sets one. Sampled requests bypass the cache unless the
+    /// key, or else the alias, opts in.
+    pub fn new(
+        default_ttl: Duration,
+        alias: &CacheSettings,
+        key: &CacheSettings,
+        directives: &CacheDirectives,
+        non_deterministic: bool,
+    ) -> Self {
+        let policy = [alias.cache_policy, key.cache_policy]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(CachePolicy::PerKey);
+        let cache_non_deterministic = key
+            .cache_non_deterministic
+            .or(alias.cache_non_deterministic)
+            .unwrap_or(false);
+        let ttl = directives.resolve_ttl(default_ttl, alias.cache_ttl_secs, key.cache_ttl_secs);
+
+        let bypass = if policy == CachePolicy::Disabled {
+            Some(BypassReason::PolicyDisabled)
+        } else if non_deterministic && !cache_non_deterministic {
+            Some(BypassReason::NonDeterministic)
+        } else if directives.no_store {
+            Some(BypassReason::NoStore)
+        } else if directives.no_cache {
+            Some(BypassReason::NoCache)
+        } else if ttl.is_zero() {
+            Some(BypassReason::ZeroTtl)
+        } else {
+            None
+        };
+        let store_policy = match bypass {
+            Some(
+                BypassReason::PolicyDisabled
+                | BypassReason::NonDeterministic
+                | BypassReason::NoStore,
+            ) => CachePolicy::Disabled,
+            _ => policy,
+        };
+
+        Self {
+            policy,
+            ttl,
+            bypass,
+            store_policy,
+        }
+    }
+
+    /// Cache scope for lookups: the key itself when per-key, else shared.
+    pub fn scope(&self, gateway_key_id: Uuid) -> Option<Uuid> {
+        (self.policy == CachePolicy::PerKey).then_some(gateway_key_id)
+    }
+}
+
+/// Plans the cache handling of a request from the settings of its gateway
+/// key and of the alias it names.
+pub async fn plan_request(
+    pool: &PgPool,
+    default_ttl: Duration,
+    gateway_key_id: Uuid,
+    api_type: ApiType,
+    payload: &Value,
+    directives: &CacheDirectives,
+) -> AppResult<CachePlan> {
+    let key = gateway_keys::fetch_cache_settings(pool, gateway_key_id).await?;
+    let alias = match extract_model_from_payload(payload) {
+        Ok(alias) => aliases::fetch_cache_settings(pool, &alias).await?,
+        Err(_) => CacheSettings::default(),
+    };
+    Ok(CachePlan::new(
+        default_ttl,
+        &alias,
+        &key,
+        directives,
+        is_non_deterministic(api_type, payload),
+    ))
+}
+
+/// Whether the response is sampled. Generation APIs default to temperature
+/// 1, so only an explicit zero temperature or a fixed `seed` counts as
+/// deterministic; embeddings always are.
+pub fn is_non_deterministic(api_type: ApiType, payload: &Value) -> bool {
+    if api_type == ApiType::OpenAiEmbeddings {
+        return false;
+    }
+    let greedy = payload
+        .get("temperature")
+        .and_then(Value::as_f64)
+        .is_some_and(|temperature| temperature == 0.0);
+    let seeded = payload.get("seed").is_some_and(|seed| !seed.is_null());
+    !(greedy || seeded)
+}
+
+pub fn validate_settings(settings: &CacheSettings) -> AppResult<()> {
+    validate_ttl(settings.cache_ttl_secs)
+}
+
+/// Rejects negative TTLs before they reach the database check.
+pub fn validate_ttl(ttl_secs: Option<i32>) -> AppResult<()> {
+    match ttl_secs {
+        Some(secs) if secs < 0 => Err(AppError::BadRequest(
+            "cache_ttl_secs must not be negative".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn validate_semantic_threshold(threshold: Option<f32>) -> AppResult<()> {
+    match threshold {
+        Some(threshold) if !(threshold > 0.0 && threshold <= 1.0) => Err(AppError::BadRequest(
+            "semantic_cache_threshold must be in (0, 1]".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Caching directives sent by the client with a request.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheDirectives {
+    /// Skip the lookup; the response may still be cached.
+    pub no_cache: bool,
+    /// Skip the lookup and keep the response out of the cache.
+    pub no_store: bool,
+    /// Oldest cached response the client accepts, from `Cache-Control:
+    /// max-age` or `x-af-cache-ttl`.
+    pub max_age: Option<Duration>,
+}
+
+impl CacheDirectives {
+    pub fn from_headers(headers: &HeaderMap) -> AppResult<Self> {
+        let mut directives = Self::default();
+
+        for value in headers.get_all(header::CACHE_CONTROL) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            for directive in value.split(',') {
+                let directive = directive.trim().to_ascii_lowercase();
+                match directive.split_once('=') {
+                    None if directive == "no-cache" => directives.no_cache = true,
+                    None if directive == "no-store" => directives.no_store = true,
+                    // Malformed max-age values are ignored, as HTTP caches do.
+                    Some(("max-age", secs)) => {
+                        if let Ok(secs) = secs.trim_matches('"').parse::<u64>() {
+                            directives.limit_max_age(Duration::from_secs(secs));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(value) = headers.get(CACHE_TTL_HEADER) {
+            let secs = value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!("{CACHE_TTL_HEADER} must be a number of seconds"))
+                })?;
+            directives.limit_max_age(Duration::from_secs(secs));
+        }
+
+        Ok(directives)
+    }
+
+    fn limit_max_age(&mut self, max_age: Duration) {
+        self.max_age = Some(self.max_age.map_or(max_age, |current| current.min(max_age)));
+    }
+
+    /// TTL for a lookup: the gateway key's TTL wins over the alias's, which
+    /// wins over the global default. Clients can only shorten it.
+    pub fn resolve_ttl(
+        &self,
+        default_ttl: Duration,
+        alias_ttl_secs: Option<i32>,
+        key_ttl_secs: Option<i32>,
+    ) -> Duration {
+        let ttl = key_ttl_secs
+            .or(alias_ttl_secs)
+            .map(|secs| Duration::from_secs(u64::try_from(secs).unwrap_or(0)))
+            .unwrap_or(default_ttl);
+        self.max_age.map_or(ttl, |max_age| ttl.min(max_age))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn directives_parse_and_only_shorten_ttl() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("No-Cache, max-age=600"),
+        );
+        headers.insert(CACHE_TTL_HEADER, HeaderValue::from_static("120"));
+
+        let directives = CacheDirectives::from_headers(&headers).ok();
+        assert_eq!(
+            directives,
+            Some(CacheDirectives {
+                no_cache: true,
+                no_store: false,
+                max_age: Some(Duration::from_secs(120)),
+            })
+        );
+
+        let directives = directives.unwrap_or_default();
+        let default_ttl = Duration::from_secs(3600);
+        assert_eq!(
+            directives.resolve_ttl(default_ttl, Some(30), None),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            directives.resolve_ttl(default_ttl, Some(30), Some(900)),
+            Duration::from_secs(120)
+        );
+        assert_eq!(
+            CacheDirectives::default().resolve_ttl(default_ttl, None, None),
+            default_ttl
+        );
+
+        headers.insert(CACHE_TTL_HEADER, HeaderValue::from_static("soon"));
+        assert!(CacheDirectives::from_headers(&headers).is_err());
+    }
+
+    #[test]
+    fn plan_applies_most_restrictive_policy_and_sampling_bypass() {
+        let default_ttl = Duration::from_secs(3600);
+        let directives = CacheDirectives::default();
+        let shared = CacheSettings {
+            cache_policy: Some(CachePolicy::Shared),
+            ..CacheSettings::default()
+        };
+        let per_key = CacheSettings {
+            cache_policy: Some(CachePolicy::PerKey),
+            ..CacheSettings::default()
+        };
+
+        let plan = CachePlan::new(default_ttl, &shared, &per_key, &directives, false);
+        assert_eq!(plan.policy, CachePolicy::PerKey);
+        assert_eq!(plan.bypass, None);
+        assert_eq!(plan.scope(Uuid::nil()), Some(Uuid::nil()));
+
+        let plan = CachePlan::new(
+            default_ttl,
+            &shared,
+            &CacheSettings::default(),
+            &directives,
+            false,
+        );
+        assert_eq!(plan.policy, CachePolicy::Shared);
+        assert_eq!(plan.scope(Uuid::nil()), None);
+
+        let plan = CachePlan::new(default_ttl, &shared, &per_key, &directives, true);
+        assert_eq!(plan.bypass, Some(BypassReason::NonDeterministic));
+        assert_eq!(plan.store_policy, CachePolicy::Disabled);
+
+        let opted_in = CacheSettings {
+            cache_non_deterministic: Some(true),
+            ..per_key
+        };
+        let plan = CachePlan::new(default_ttl, &shared, &opted_in, &directives, true);
+        assert_eq!(plan.bypass, None);
+
+        let no_cache = CacheDirectives {
+            no_cache: true,
+            ..directives
+        };
+        let plan = CachePlan::new(default_ttl, &shared, &per_key, &no_cache, false);
+        assert_eq!(plan.bypass, Some(BypassReason::NoCache));
+        assert_eq!(plan.store_policy, CachePolicy::PerKey);
     }
 
-    pub fn insert(&self, key: ResponseCacheKey, value: CachedResponse) {
-        self.inner.insert(key, value);
+    #[test]
+    fn missing_temperature_counts_as_sampled() {
+        let chat = ApiType::OpenAiChatCompletions;
+        let messages = json!([{ "role": "user", "content": "hi" }]);
+        assert!(is_non_deterministic(
+            chat,
+            &json!({ "model": "m", "messages": messages })
+        ));
+        assert!(is_non_deterministic(
+            chat,
+            &json!({ "model": "m", "messages": messages, "temperature": 0.7 })
+        ));
+        assert!(!is_non_deterministic(
+            chat,
+            &json!({ "model": "m", "messages": messages, "temperature": 0 })
+        ));
+        assert!(!is_non_deterministic(
+            chat,
+            &json!({ "model": "m", "messages": messages, "seed": 42 })
+        ));
+        assert!(!is_non_deterministic(
+            ApiType::OpenAiEmbeddings,
+            &json!({ "model": "m", "input": "hi" })
+        ));
     }
 }
@@ -1,26 +1,183 @@
+use std::collections::{HashMap, hash_map::Entry};
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use rand::RngExt;
 use serde_json::Value;
 use sqlx::PgPool;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use crate::constants::MAX_UPSTREAM_ATTEMPTS;
 use crate::db::alias_targets::{self, AliasTargetDetail};
 use crate::db::provider_keys::{self, ProviderKey};
-use crate::db::types::ApiType;
+use crate::db::types::{ApiType, LbStrategy};
 use crate::error::{AppError, AppResult};
-use crate::services::providers;
+use crate::services::{circuit_breaker::CircuitBreakers, providers};
 
 #[derive(Debug, Clone)]
 pub struct Route {
     pub provider_id: Uuid,
     pub provider_name: String,
+    pub provider_endpoint_id: Uuid,
     pub endpoint_url: String,
+    pub upstream_api_type: ApiType,
     pub model_id: String,
     pub provider_key: ProviderKey,
     pub alias_name: String,
+    pub alias_target_id: Option<Uuid>,
     pub extra_fields: Value,
 }
 
-pub async fn resolve_route(pool: &PgPool, model: &str, api_type: ApiType) -> AppResult<Route> {
+/// Smoothing factor for the per-target latency moving average.
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+
+/// Latency charged for a failed attempt, so a target that never answers is
+/// not tried first forever.
+const LATENCY_FAILURE_PENALTY: Duration = Duration::from_secs(30);
+
+/// Orders alias targets according to the alias load balancing strategy.
+///
+/// All state lives in memory so selecting a target never writes to the
+/// database on the request path.
+#[derive(Clone, Default)]
+pub struct TargetSelector {
+    round_robin: Arc<DashMap<Uuid, AtomicUsize>>,
+    latencies: Arc<DashMap<Uuid, f64>>,
+}
+
+impl TargetSelector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the time an alias target took to answer, feeding `least_latency`.
+    pub fn record_latency(&self, alias_target_id: Uuid, latency: Duration) {
+        let sample = latency.as_secs_f64() * 1000.0;
+        self.latencies
+            .entry(alias_target_id)
+            .and_modify(|avg| *avg += LATENCY_EWMA_ALPHA * (sample - *avg))
+            .or_insert(sample);
+    }
+
+    /// Records a failed attempt on an alias target as a slow answer.
+    pub fn record_failure(&self, alias_target_id: Uuid) {
+        self.record_latency(alias_target_id, LATENCY_FAILURE_PENALTY);
+    }
+
+    fn order_targets(&self, mut targets: Vec<AliasTargetDetail>) -> Vec<AliasTargetDetail> {
+        let Some(first) = targets.first() else {
+            return targets;
+        };
+        let alias_id = first.alias_id;
+
+        match first.strategy {
+            LbStrategy::RoundRobin => {
+                let offset = self
+                    .round_robin
+                    .entry(alias_id)
+                    .or_default()
+                    .fetch_add(1, Ordering::Relaxed);
+                let len = targets.len();
+                targets.rotate_left(offset % len);
+                targets
+            }
+            LbStrategy::WeightedRandom => weighted_shuffle(targets),
+            LbStrategy::PriorityWithFallback => {
+                targets.sort_by_key(|target| target.priority);
+                let mut ordered = Vec::with_capacity(targets.len());
+                let mut rest = targets.into_iter().peekable();
+                while let Some(target) = rest.next() {
+                    let priority = target.priority;
+                    let mut group = vec![target];
+                    while let Some(next) = rest.next_if(|next| next.priority == priority) {
+                        group.push(next);
+                    }
+                    ordered.extend(weighted_shuffle(group));
+                }
+                ordered
+            }
+            LbStrategy::LeastLatency => {
+                // Targets without samples go first so they get measured.
+                targets.sort_by(|a, b| {
+                    let a = self.latencies.get(&a.alias_target_id).map(|v| *v);
+                    let b = self.latencies.get(&b.alias_target_id).map(|v| *v);
+                    a.unwrap_or(0.0).total_cmp(&b.unwrap_or(0.0))
+                });
+                targets
+            }
+        }
+    }
+}
+
+/// Shuffles targets so that heavier ones are more likely to come first.
+fn weighted_shuffle(mut targets: Vec<AliasTargetDetail>) -> Vec<AliasTargetDetail> {
+    let mut rng = rand::rng();
+    let mut ordered = Vec::with_capacity(targets.len());
+    while !targets.is_empty() {
+        let total: u64 = targets.iter().map(|t| t.weight.max(1) as u64).sum();
+        let mut pick = rng.random_range(0..total);
+        let index = targets
+            .iter()
+            .position(|t| {
+                let weight = t.weight.max(1) as u64;
+                if pick < weight {
+                    true
+                } else {
+                    pick -= weight;
+                    false
+                }
+            })
+            .unwrap_or(0);
+        ordered.push(targets.remove(index));
+    }
+    ordered
+}
+
+/// Splits `max` slots between groups as evenly as their sizes allow, keeping
+/// the order of the groups and of the items within each group.
+fn fair_share<T>(groups: Vec<Vec<T>>, max: usize) -> Vec<T> {
+    let mut quotas = vec![0; groups.len()];
+    let mut remaining = max;
+    while remaining > 0 {
+        let mut granted = false;
+        for (quota, group) in quotas.iter_mut().zip(&groups) {
+            if remaining > 0 && *quota < group.len() {
+                *quota += 1;
+                remaining -= 1;
+                granted = true;
+            }
+        }
+        if !granted {
+            break;
+        }
+    }
+    groups
+        .into_iter()
+        .zip(quotas)
+        .flat_map(|(group, quota)| group.into_iter().take(quota))
+        .collect()
+}
+
+/// Builds the ordered list of upstream attempts for a request.
+///
+/// Every enabled target is paired with every enabled key of its provider, so a
+/// failing attempt can fall through to another key first and then to the next
+/// target. Targets of an alias are ordered by its load balancing strategy and
+/// endpoints or keys whose circuit breaker is open are left out. The plan is
+/// capped at `MAX_UPSTREAM_ATTEMPTS` entries, shared between targets so a
+/// provider with many keys still leaves room to fail over.
+pub async fn resolve_routes(
+    pool: &PgPool,
+    selector: &TargetSelector,
+    breakers: &CircuitBreakers,
+    model: &str,
+    api_type: ApiType,
+) -> AppResult<Vec<Route>> {
     // Resolve target
     tracing::debug!("resolving target");
     let mut targets = Vec::new();
@@ -42,9 +199,11 @@ pub async fn resolve_route(pool: &PgPool, model: &str, api_type: ApiType) -> App
                 .await
                 .unwrap_or_default();
 
+            let candidates = upstream_api_types(api_type);
             if let Some(endpoint) = endpoints
                 .into_iter()
-                .find(|e| e.api_type == api_type && e.enabled)
+                .filter(|e| e.enabled && candidates.contains(&e.api_type))
+                .min_by_key(|e| candidates.iter().position(|t| *t == e.api_type))
             {
                 tracing::debug!(endpoint_id = %endpoint.id, url = %endpoint.url, "found suitable endpoint");
                 targets.push(AliasTargetDetail {
@@ -59,7 +218,11 @@ pub async fn resolve_route(pool: &PgPool, model: &str, api_type: ApiType) -> App
                     usage_count: 0,
                     provider_endpoint_id: Some(endpoint.id),
                     endpoint_url: Some(endpoint.url),
+                    endpoint_api_type: Some(endpoint.api_type),
                     model_id: real_model.to_string(),
+                    weight: 1,
+                    priority: 0,
+                    strategy: LbStrategy::RoundRobin,
                     enabled: true,
                     created_at: OffsetDateTime::now_utc(),
                 });
@@ -77,46 +240,79 @@ pub async fn resolve_route(pool: &PgPool, model: &str, api_type: ApiType) -> App
         is_alias_match = true;
     }
 
-    let target = targets
-        .first()
-        .ok_or_else(|| AppError::BadRequest(format!("unknown model alias: {model}")))?;
+    if targets.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "unknown model alias: {model}"
+        )));
+    }
+
+    if is_alias_match {
+        targets = selector.order_targets(targets);
+    }
+
+    let mut keys_by_provider: HashMap<Uuid, Vec<ProviderKey>> = HashMap::new();
+    let mut routes_by_target = Vec::with_capacity(targets.len());
+    let mut skipped_open = 0;
+    for target in &targets {
+        let (Some(provider_endpoint_id), Some(endpoint_url)) =
+            (target.provider_endpoint_id, target.endpoint_url.clone())
+        else {
+            tracing::debug!(alias_target_id = %target.alias_target_id, "target has no endpoint url, skipping");
+            continue;
+        };
+
+        let provider_keys = match keys_by_provider.entry(target.provider_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                tracing::debug!(provider_id = %target.provider_id, "fetching provider keys");
+                entry.insert(fetch_provider_keys(pool, target.provider_id).await?)
+            }
+        };
+
+        let mut routes = Vec::new();
+        for provider_key in provider_keys.iter() {
+            if !breakers.allows(provider_endpoint_id, provider_key.id) {
+                skipped_open += 1;
+                continue;
+            }
+            routes.push(Route {
+                provider_id: target.provider_id,
+                provider_name: target.provider_name.clone(),
+                provider_endpoint_id,
+                endpoint_url: endpoint_url.clone(),
+                upstream_api_type: target.endpoint_api_type.unwrap_or(api_type),
+                model_id: target.model_id.clone(),
+                provider_key: provider_key.clone(),
+                alias_name: model.to_string(),
+                alias_target_id: is_alias_match.then_some(target.alias_target_id),
+                extra_fields: target.extra_fields.clone(),
+            });
+        }
+        routes_by_target.push(routes);
+    }
+
+    let routes = fair_share(routes_by_target, MAX_UPSTREAM_ATTEMPTS);
+
+    if routes.is_empty() && skipped_open > 0 {
+        return Err(AppError::ServiceUnavailable(
+            "all upstream targets are unavailable: circuit breaker open".to_string(),
+        ));
+    }
+
+    if routes.is_empty() {
+        return Err(AppError::ServiceUnavailable(
+            "no provider keys available".to_string(),
+        ));
+    }
 
     tracing::debug!(
-        provider_id = %target.provider_id,
-        provider_name = %target.provider_name,
-        target_model_id = %target.model_id,
-        "resolved alias target"
+        attempts = routes.len(),
+        first_provider = %routes[0].provider_name,
+        first_model_id = %routes[0].model_id,
+        "resolved upstream attempt plan"
     );
 
-    if is_alias_match {
-        tracing::debug!(alias_target_id = %target.alias_target_id, "incrementing alias target usage count");
-        alias_targets::increment_usage_count(pool, target.alias_target_id).await?;
-    }
-
-    // 3. Fetch provider keys
-    tracing::debug!("fetching provider keys");
-    let provider_keys = fetch_provider_keys(pool, target.provider_id).await?;
-
-    let provider_key = provider_keys
-        .first()
-        .ok_or_else(|| AppError::Internal(anyhow::anyhow!("no provider keys available")))?
-        .clone();
-
-    tracing::debug!(provider_key_id = %provider_key.id, "selected provider key");
-
-    let endpoint_url = target.endpoint_url.clone().ok_or_else(|| {
-        AppError::Internal(anyhow::anyhow!("no endpoint url found for target provider"))
-    })?;
-
-    Ok(Route {
-        provider_id: target.provider_id,
-        provider_name: target.provider_name.clone(),
-        endpoint_url,
-        model_id: target.model_id.clone(),
-        provider_key,
-        alias_name: model.to_string(),
-        extra_fields: target.extra_fields.clone(),
-    })
+    Ok(routes)
 }
 
 fn parse_provider_real_model(model: &str) -> Option<(&str, &str)> {
@@ -129,12 +325,29 @@ fn parse_provider_real_model(model: &str) -> Option<(&str, &str)> {
     Some((brief, real_model))
 }
 
+/// Endpoint types that can serve a request of `api_type`, native first. The
+/// others are reached through protocol translation.
+pub fn upstream_api_types(api_type: ApiType) -> &'static [ApiType] {
+    match api_type {
+        ApiType::AnthropicMessages => &[ApiType::AnthropicMessages, ApiType::OpenAiChatCompletions],
+        ApiType::OpenAiChatCompletions => {
+            &[ApiType::OpenAiChatCompletions, ApiType::AnthropicMessages]
+        }
+        ApiType::OpenAiEmbeddings => &[ApiType::OpenAiEmbeddings],
+        ApiType::OpenAiResponses => &[ApiType::OpenAiResponses, ApiType::OpenAiChatCompletions],
+        ApiType::OpenAiModels => &[ApiType::OpenAiModels],
+        ApiType::GeminiGenerateContent => &[ApiType::GeminiGenerateContent],
+    }
+}
+
 pub async fn fetch_alias_target_details(
     pool: &PgPool,
     alias_name: &str,
     api_type: ApiType,
 ) -> AppResult<Vec<AliasTargetDetail>> {
-    let rows = alias_targets::fetch_alias_target_details(pool, alias_name, api_type).await?;
+    let rows =
+        alias_targets::fetch_alias_target_details(pool, alias_name, upstream_api_types(api_type))
+            .await?;
 
     Ok(rows)
 }
@@ -144,3 +357,100 @@ pub async fn fetch_provider_keys(pool: &PgPool, provider_id: Uuid) -> AppResult<
 
     Ok(rows)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(strategy: LbStrategy, weight: i32, priority: i32) -> AliasTargetDetail {
+        AliasTargetDetail {
+            id: Uuid::now_v7(),
+            alias_id: Uuid::nil(),
+            alias_name: "alias".to_string(),
+            alias_target_id: Uuid::now_v7(),
+            extra_fields: Value::Object(serde_json::Map::new()),
+            provider_id: Uuid::now_v7(),
+            provider_name: "provider".to_string(),
+            provider_usage_count: 0,
+            usage_count: 0,
+            provider_endpoint_id: None,
+            endpoint_url: None,
+            endpoint_api_type: None,
+            model_id: "model".to_string(),
+            weight,
+            priority,
+            strategy,
+            enabled: true,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[test]
+    fn fair_share_leaves_room_for_every_target() {
+        let groups = vec![(0..10).collect(), vec![10, 11], vec![20]];
+        assert_eq!(fair_share(groups, 8), vec![0, 1, 2, 3, 4, 10, 11, 20]);
+        assert_eq!(fair_share(vec![vec![1, 2], vec![3]], 8), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn priority_with_fallback_tries_lower_priority_first() {
+        let selector = TargetSelector::new();
+        let strategy = LbStrategy::PriorityWithFallback;
+        let targets = vec![
+            target(strategy, 1, 2),
+            target(strategy, 5, 0),
+            target(strategy, 1, 1),
+            target(strategy, 1, 0),
+        ];
+
+        let priorities: Vec<i32> = selector
+            .order_targets(targets)
+            .iter()
+            .map(|t| t.priority)
+            .collect();
+
+        assert_eq!(priorities, vec![0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn round_robin_rotates_first_target() {
+        let selector = TargetSelector::new();
+        let targets = vec![
+            target(LbStrategy::RoundRobin, 1, 0),
+            target(LbStrategy::RoundRobin, 1, 0),
+        ];
+        let first = targets[0].alias_target_id;
+        let second = targets[1].alias_target_id;
+
+        let ordered = selector.order_targets(targets.clone());
+        assert_eq!(ordered[0].alias_target_id, first);
+        let ordered = selector.order_targets(targets);
+        assert_eq!(ordered[0].alias_target_id, second);
+    }
+
+    #[test]
+    fn least_latency_prefers_faster_targets() {
+        let selector = TargetSelector::new();
+        let slow = target(LbStrategy::LeastLatency, 1, 0);
+        let fast = target(LbStrategy::LeastLatency, 1, 0);
+        selector.record_latency(slow.alias_target_id, Duration::from_millis(900));
+        selector.record_latency(fast.alias_target_id, Duration::from_millis(100));
+        let fast_id = fast.alias_target_id;
+
+        let ordered = selector.order_targets(vec![slow, fast]);
+        assert_eq!(ordered[0].alias_target_id, fast_id);
+    }
+
+    #[test]
+    fn least_latency_tries_failing_targets_last() {
+        let selector = TargetSelector::new();
+        let failing = target(LbStrategy::LeastLatency, 1, 0);
+        let slow = target(LbStrategy::LeastLatency, 1, 0);
+        selector.record_failure(failing.alias_target_id);
+        selector.record_latency(slow.alias_target_id, Duration::from_secs(5));
+        let failing_id = failing.alias_target_id;
+
+        let ordered = selector.order_targets(vec![failing, slow]);
+        assert_eq!(ordered[1].alias_target_id, failing_id);
+    }
+}
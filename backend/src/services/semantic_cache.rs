@@ -0,0 +1,241 @@
+//! Semantic response cache. A request whose last user message is close
+//! enough, by cosine similarity of its embedding, to that of a cached request
+//! is served the cached response. The rest of the request, its alias and its
+//! cache scope must still match exactly.
+
+use std::time::Duration;
+
+use axum::body;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    constants::{MAX_REQUEST_BODY_BYTES, SEMANTIC_CACHE_MAX_CANDIDATES},
+    db::{aliases, cache_embeddings, types::ApiType},
+    error::{AppError, AppResult},
+    middleware::{auth::GatewayKeyId, request_log::ClientInfo},
+    services::openai::OpenAiService,
+    utils::request_body_hash::hash_scoped_request_body_hex,
+};
+
+/// The part of a request compared by meaning rather than byte for byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticRequest {
+    /// Text of the last user message.
+    pub text: String,
+    /// Hash of the request without that text, in its cache scope.
+    pub context_hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SemanticQuery {
+    pub context_hash: String,
+    pub embedding: Vec<f32>,
+    pub threshold: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SemanticMatch {
+    /// Exact cache key of the matching request.
+    pub request_body_hash: String,
+    pub similarity: f32,
+}
+
+/// Splits the last user message out of a chat completions or Anthropic
+/// messages request. Messages with anything but text are not eligible.
+pub fn semantic_request(
+    api_type: ApiType,
+    request_body: &[u8],
+    scope: Option<Uuid>,
+) -> Option<SemanticRequest> {
+    if !matches!(
+        api_type,
+        ApiType::OpenAiChatCompletions | ApiType::AnthropicMessages
+    ) {
+        return None;
+    }
+    let mut payload = serde_json::from_slice::<Value>(request_body).ok()?;
+    let message = payload
+        .get_mut("messages")?
+        .as_array_mut()?
+        .iter_mut()
+        .rev()
+        .find(|message| message.get("role").and_then(Value::as_str) == Some("user"))?;
+    let content = message.get_mut("content")?;
+    let text = match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => {
+            let texts: Option<Vec<&str>> = parts
+                .iter()
+                .map(|part| match part.get("type").and_then(Value::as_str) {
+                    Some("text") => part.get("text").and_then(Value::as_str),
+                    _ => None,
+                })
+                .collect();
+            texts?.join("\n")
+        }
+        _ => return None,
+    };
+    if text.trim().is_empty() {
+        return None;
+    }
+    *content = Value::Null;
+
+    let context = serde_json::to_vec(&payload).ok()?;
+    Some(SemanticRequest {
+        text,
+        context_hash: hash_scoped_request_body_hex(&context, scope),
+    })
+}
+
+/// Embeds the request's last user message through the embeddings alias,
+/// when its own alias has the semantic cache enabled. The embedding request
+/// is routed, logged and billed like any other request of the gateway key.
+pub async fn prepare_query(
+    pool: &PgPool,
+    openai: &OpenAiService,
+    embeddings_alias: &str,
+    alias: &str,
+    request: SemanticRequest,
+    gateway_key_id: GatewayKeyId,
+    client_info: ClientInfo,
+) -> AppResult<Option<SemanticQuery>> {
+    let Some(threshold) = aliases::fetch_semantic_cache_threshold(pool, alias).await? else {
+        return Ok(None);
+    };
+
+    let payload = json!({ "model": embeddings_alias, "input": request.text });
+    let response = openai
+        .embeddings(gateway_key_id, payload, client_info)
+        .await?;
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body(), MAX_REQUEST_BODY_BYTES)
+        .await
+        .map_err(|err| AppError::Internal(err.into()))?;
+    if !status.is_success() {
+        return Err(AppError::Internal(anyhow::anyhow!(
+            "embeddings alias {embeddings_alias} returned {status}"
+        )));
+    }
+    let embedding = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|value| value.pointer("/data/0/embedding").cloned())
+        .and_then(|embedding| serde_json::from_value::<Vec<f32>>(embedding).ok())
+        .ok_or_else(|| {
+            AppError::Internal(anyhow::anyhow!(
+                "embeddings alias {embeddings_alias} returned no embedding"
+            ))
+        })?;
+
+    Ok(Some(SemanticQuery {
+        context_hash: request.context_hash,
+        embedding,
+        threshold,
+    }))
+}
+
+/// Most similar cached request within `ttl`, if it clears the threshold.
+pub async fn find_match(
+    pool: &PgPool,
+    query: &SemanticQuery,
+    ttl: Duration,
+) -> AppResult<Option<SemanticMatch>> {
+    let not_before = OffsetDateTime::now_utc() - ttl;
+    let candidates = cache_embeddings::list_candidates(
+        pool,
+        &query.context_hash,
+        not_before,
+        SEMANTIC_CACHE_MAX_CANDIDATES,
+    )
+    .await?;
+
+    Ok(candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let similarity = cosine_similarity(&query.embedding, &candidate.embedding)?;
+            (similarity >= query.threshold).then_some(SemanticMatch {
+                request_body_hash: candidate.request_body_hash,
+                similarity,
+            })
+        })
+        .max_by(|a, b| a.similarity.total_cmp(&b.similarity)))
+}
+
+/// Remembers the embedding of a request that missed, keyed by its exact
+/// cache key, so later rephrasings can find its response.
+pub async fn record(
+    pool: &PgPool,
+    query: &SemanticQuery,
+    request_body_hash: &str,
+) -> AppResult<()> {
+    cache_embeddings::upsert_embedding(
+        pool,
+        request_body_hash,
+        &query.context_hash,
+        &query.embedding,
+    )
+    .await
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.is_empty() || a.len() != b.len() {
+        return None;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0_f32, 0.0_f32, 0.0_f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return None;
+    }
+    Some(dot / (norm_a.sqrt() * norm_b.sqrt()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rephrased_last_message_shares_context_and_compares_by_cosine() {
+        let body = |text: &str| {
+            serde_json::to_vec(&json!({
+                "model": "gpt-4o",
+                "messages": [
+                    { "role": "system", "content": "Be brief." },
+                    { "role": "user", "content": [{ "type": "text", "text": text }] },
+                ],
+            }))
+            .unwrap()
+        };
+        let scope = Some(Uuid::nil());
+
+        let first = semantic_request(
+            ApiType::OpenAiChatCompletions,
+            &body("What is Rust?"),
+            scope,
+        )
+        .unwrap();
+        let second =
+            semantic_request(ApiType::OpenAiChatCompletions, &body("what's rust"), scope).unwrap();
+        assert_eq!(first.text, "What is Rust?");
+        assert_eq!(first.context_hash, second.context_hash);
+        let shared = semantic_request(ApiType::OpenAiChatCompletions, &body("what's rust"), None);
+        assert_ne!(shared.unwrap().context_hash, first.context_hash);
+
+        let image = json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": [{ "type": "image_url" }] }],
+        });
+        let image = serde_json::to_vec(&image).unwrap();
+        assert!(semantic_request(ApiType::OpenAiChatCompletions, &image, scope).is_none());
+        assert!(semantic_request(ApiType::OpenAiEmbeddings, &body("x"), scope).is_none());
+
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), Some(1.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), Some(0.0));
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), None);
+    }
+}
@@ -1,24 +1,81 @@
+use serde::Deserialize;
 use sqlx::PgPool;
 use time::OffsetDateTime;
 
-use crate::db::stats::{self, CacheHitRateStats, CategoryCount, TimeSeriesPoint};
-use crate::error::AppResult;
+use crate::constants::MAX_STATS_BUCKETS;
+use crate::db::stats::{
+    self, CacheHitRateStats, CategoryCount, PerformancePoint, TimeSeriesPoint, TokenUsagePoint,
+    UsageBreakdown, UsageDimension,
+};
+use crate::error::{AppError, AppResult};
 
-pub async fn get_requests_over_time(
-    pool: &PgPool,
+/// Width of the buckets of a time series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsGranularity {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl StatsGranularity {
+    fn seconds(self) -> i64 {
+        match self {
+            Self::Minute => 60,
+            Self::Hour => 60 * 60,
+            Self::Day => 24 * 60 * 60,
+        }
+    }
+}
+
+/// Bucket width in seconds. Without an explicit granularity, ranges up to a
+/// day use half-hour buckets and longer ones six-hour buckets.
+pub fn bucket_seconds(
     start: OffsetDateTime,
     end: OffsetDateTime,
-) -> AppResult<Vec<TimeSeriesPoint>> {
+    granularity: Option<StatsGranularity>,
+) -> AppResult<i64> {
+    if end < start {
+        return Err(AppError::BadRequest(
+            "start must not be after end".to_string(),
+        ));
+    }
     let duration = end - start;
-    let granularity_seconds = if duration <= time::Duration::days(1) {
-        1800
-    } else {
-        21600
+    let Some(granularity) = granularity else {
+        return Ok(if duration <= time::Duration::days(1) {
+            1800
+        } else {
+            21600
+        });
     };
 
+    let seconds = granularity.seconds();
+    if duration.whole_seconds() / seconds >= MAX_STATS_BUCKETS {
+        return Err(AppError::BadRequest(format!(
+            "range too long for {granularity:?} granularity; at most {MAX_STATS_BUCKETS} buckets"
+        )));
+    }
+    Ok(seconds)
+}
+
+pub async fn get_requests_over_time(
+    pool: &PgPool,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    granularity_seconds: i64,
+) -> AppResult<Vec<TimeSeriesPoint>> {
     stats::requests_over_time(pool, start, end, granularity_seconds).await
 }
 
+pub async fn get_tokens_over_time(
+    pool: &PgPool,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    granularity_seconds: i64,
+) -> AppResult<Vec<TokenUsagePoint>> {
+    stats::tokens_over_time(pool, start, end, granularity_seconds).await
+}
+
 pub async fn get_requests_by_provider(
     pool: &PgPool,
     start: OffsetDateTime,
@@ -27,6 +84,26 @@ pub async fn get_requests_by_provider(
     stats::requests_by_provider(pool, start, end).await
 }
 
+pub async fn get_usage_breakdown(
+    pool: &PgPool,
+    dimension: UsageDimension,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    limit: i64,
+) -> AppResult<Vec<UsageBreakdown>> {
+    stats::usage_breakdown(pool, dimension, start, end, limit).await
+}
+
+pub async fn get_performance_over_time(
+    pool: &PgPool,
+    dimension: UsageDimension,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    granularity_seconds: i64,
+) -> AppResult<Vec<PerformancePoint>> {
+    stats::performance_over_time(pool, dimension, start, end, granularity_seconds).await
+}
+
 pub async fn get_cache_hit_rate(
     pool: &PgPool,
     start: OffsetDateTime,
@@ -34,3 +111,36 @@ pub async fn get_cache_hit_rate(
 ) -> AppResult<CacheHitRateStats> {
     stats::cache_hit_rate(pool, start, end).await
 }
+
+pub async fn get_total_cost(
+    pool: &PgPool,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> AppResult<f64> {
+    stats::total_cost(pool, start, end).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_seconds_follows_granularity_within_bucket_cap() {
+        let end = OffsetDateTime::UNIX_EPOCH + time::Duration::days(30);
+        let day_ago = end - time::Duration::days(1);
+        let month_ago = OffsetDateTime::UNIX_EPOCH;
+
+        assert_eq!(bucket_seconds(day_ago, end, None).ok(), Some(1800));
+        assert_eq!(bucket_seconds(month_ago, end, None).ok(), Some(21600));
+        assert_eq!(
+            bucket_seconds(day_ago, end, Some(StatsGranularity::Minute)).ok(),
+            Some(60)
+        );
+        assert_eq!(
+            bucket_seconds(month_ago, end, Some(StatsGranularity::Day)).ok(),
+            Some(86400)
+        );
+        assert!(bucket_seconds(month_ago, end, Some(StatsGranularity::Minute)).is_err());
+        assert!(bucket_seconds(end, day_ago, None).is_err());
+    }
+}
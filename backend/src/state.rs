@@ -1,5 +1,6 @@
 use crate::services::{
-    auth::LoginProtection, background::BackgroundTasks, openai::OpenAiService,
+    auth::LoginProtection, background::BackgroundTasks, budgets::BudgetSpend,
+    circuit_breaker::CircuitBreakers, metrics::Metrics, openai::OpenAiService,
     rate_limit::RateLimiter, response_cache::ResponseCache,
 };
 
@@ -11,5 +12,8 @@ pub struct AppState {
     pub rate_limiter: RateLimiter,
     pub login_protection: LoginProtection,
     pub response_cache: ResponseCache,
+    pub circuit_breakers: CircuitBreakers,
+    pub budget_spend: BudgetSpend,
     pub background_tasks: BackgroundTasks,
+    pub metrics: Metrics,
 }
@@ -3,6 +3,7 @@ use serde_json::Value;
 use crate::error::AppError;
 
 pub mod request_body_hash;
+pub mod sse;
 
 pub fn extract_model_from_payload(payload: &Value) -> Result<String, AppError> {
     payload
@@ -11,3 +12,24 @@ pub fn extract_model_from_payload(payload: &Value) -> Result<String, AppError> {
         .map(str::to_string)
         .ok_or_else(|| AppError::BadRequest("model is required".to_string()))
 }
+
+/// Splits a Gemini `{model}:{method}` path segment into the model and whether
+/// the method streams.
+pub fn gemini_target(target: &str) -> Result<(&str, bool), AppError> {
+    let (model, method) = target
+        .rsplit_once(':')
+        .ok_or_else(|| AppError::BadRequest("expected {model}:{method} in path".to_string()))?;
+    let stream = match method {
+        "generateContent" => false,
+        "streamGenerateContent" => true,
+        _ => {
+            return Err(AppError::BadRequest(format!(
+                "unsupported Gemini method: {method}"
+            )));
+        }
+    };
+    if model.is_empty() {
+        return Err(AppError::BadRequest("model is required".to_string()));
+    }
+    Ok((model, stream))
+}
@@ -1,11 +1,16 @@
+use uuid::Uuid;
+
 pub type RequestBodyHash = [u8; 16];
 
-pub fn hash_request_body(request_body: &[u8]) -> RequestBodyHash {
-    if let Some(bytes) = canonical_json_bytes(request_body) {
-        md5::compute(bytes).0
-    } else {
-        md5::compute(request_body).0
+/// Hash of a request body within a response cache scope. The shared scope
+/// (`None`) hashes the body alone. The stream flag is left out so streamed
+/// and buffered requests share cache entries.
+pub fn hash_scoped_request_body(request_body: &[u8], scope: Option<Uuid>) -> RequestBodyHash {
+    let mut bytes = canonical_json_bytes(request_body).unwrap_or_else(|| request_body.to_vec());
+    if let Some(scope) = scope {
+        bytes.extend_from_slice(scope.as_bytes());
     }
+    md5::compute(bytes).0
 }
 
 pub fn request_body_hash_hex(hash: RequestBodyHash) -> String {
@@ -18,11 +23,32 @@ pub fn request_body_hash_hex(hash: RequestBodyHash) -> String {
     out
 }
 
-pub fn hash_request_body_hex(request_body: &[u8]) -> String {
-    request_body_hash_hex(hash_request_body(request_body))
+/// Parses the 32-character hex form produced by [`request_body_hash_hex`].
+pub fn parse_request_body_hash_hex(hex: &str) -> Option<RequestBodyHash> {
+    if hex.len() != 32 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut hash = RequestBodyHash::default();
+    for (byte, pair) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(hash)
 }
 
+pub fn hash_scoped_request_body_hex(request_body: &[u8], scope: Option<Uuid>) -> String {
+    request_body_hash_hex(hash_scoped_request_body(request_body, scope))
+}
+
+/// Fields that only select how the response is delivered.
+const STREAM_FIELDS: [&str; 2] = ["stream", "stream_options"];
+
 fn canonical_json_bytes(request_body: &[u8]) -> Option<Vec<u8>> {
-    let value: serde_json::Value = serde_json::from_slice(request_body).ok()?;
+    let mut value: serde_json::Value = serde_json::from_slice(request_body).ok()?;
+    if let Some(object) = value.as_object_mut() {
+        for field in STREAM_FIELDS {
+            object.remove(field);
+        }
+    }
     serde_json::to_vec(&value).ok()
 }
@@ -0,0 +1,90 @@
+/// A single server-sent event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Incremental SSE parser; upstream chunks may split events anywhere.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer
+            .extend(chunk.iter().copied().filter(|byte| *byte != b'\r'));
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|window| window == b"\n\n") {
+            let block: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            if let Some(event) = parse_block(&block[..end]) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Flushes a trailing event that was not terminated by a blank line.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let block = std::mem::take(&mut self.buffer);
+        parse_block(&block)
+    }
+}
+
+fn parse_block(block: &[u8]) -> Option<SseEvent> {
+    let text = String::from_utf8_lossy(block);
+    let mut event = None;
+    let mut data: Option<String> = None;
+
+    for line in text.lines() {
+        if line.starts_with(':') {
+            continue;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event = Some(value.to_string()),
+            "data" => match &mut data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => data = Some(value.to_string()),
+            },
+            _ => {}
+        }
+    }
+
+    data.map(|data| SseEvent { event, data })
+}
+
+pub fn format_event(event: Option<&str>, data: &str) -> String {
+    match event {
+        Some(event) => format!("event: {event}\ndata: {data}\n\n"),
+        None => format!("data: {data}\n\n"),
+    }
+}
+
+/// Splits a captured SSE body into its raw events, each with its trailing
+/// blank line. Bytes after the last blank line form a final event.
+pub fn split_events(body: &[u8]) -> Vec<&[u8]> {
+    let mut events = Vec::new();
+    let mut start = 0;
+    for end in 0..body.len() {
+        let event = &body[start..=end];
+        if event.ends_with(b"\n\n") || event.ends_with(b"\r\n\r\n") {
+            events.push(event);
+            start = end + 1;
+        }
+    }
+    if start < body.len() {
+        events.push(&body[start..]);
+    }
+    events
+}
@@ -1,9 +1,22 @@
 import { requestJson } from "./client";
 
+export type LbStrategy =
+  | "round_robin"
+  | "weighted_random"
+  | "priority_with_fallback"
+  | "least_latency";
+
+export type CachePolicy = "disabled" | "per_key" | "shared";
+
 export interface Alias {
   id: string;
   name: string;
   enabled: boolean;
+  strategy: LbStrategy;
+  cache_ttl_secs: number | null;
+  cache_policy: CachePolicy | null;
+  cache_non_deterministic: boolean | null;
+  semantic_cache_threshold: number | null;
   created_at: string;
 }
 
@@ -14,6 +27,8 @@ export interface AliasTarget {
   model_id: string;
   extra_fields: Record<string, unknown>;
   usage_count: number;
+  weight: number;
+  priority: number;
   enabled: boolean;
   created_at: string;
 }
@@ -26,6 +41,7 @@ export interface AliasTargetDetail extends AliasTarget {
   provider_usage_count: number;
   provider_endpoint_id?: string;
   endpoint_url?: string;
+  strategy: LbStrategy;
   // api_type is no longer returned in detail list
 }
 
@@ -36,17 +52,29 @@ export interface ListAliasesParams {
 
 export interface CreateAliasRequest {
   name: string;
+  strategy?: LbStrategy;
+  cache_ttl_secs?: number | null;
+  cache_policy?: CachePolicy | null;
+  cache_non_deterministic?: boolean | null;
+  semantic_cache_threshold?: number | null;
 }
 
 export interface UpdateAliasRequest {
   name?: string;
   enabled?: boolean;
+  strategy?: LbStrategy;
+  cache_ttl_secs?: number | null;
+  cache_policy?: CachePolicy | null;
+  cache_non_deterministic?: boolean | null;
+  semantic_cache_threshold?: number | null;
 }
 
 export interface CreateAliasTargetRequest {
   provider_id: string;
   model_id: string;
   extra_fields?: Record<string, unknown>;
+  weight?: number;
+  priority?: number;
 }
 
 export interface UpdateAliasTargetRequest {
@@ -54,6 +82,8 @@ export interface UpdateAliasTargetRequest {
   model_id?: string;
   enabled?: boolean;
   extra_fields?: Record<string, unknown>;
+  weight?: number;
+  priority?: number;
 }
 
 // Alias API
@@ -1,3 +1,4 @@
+import type { CachePolicy } from "./aliases";
 import { requestJson } from "./client";
 
 export interface GatewayKey {
@@ -7,10 +8,39 @@ export interface GatewayKey {
   enabled: boolean;
   rate_limit_rps: number | null;
   rate_limit_rpm: number | null;
+  rate_limit_tpm: number | null;
+  rate_limit_tpd: number | null;
+  max_concurrent_requests: number | null;
+  cache_ttl_secs: number | null;
+  cache_policy: CachePolicy | null;
+  cache_non_deterministic: boolean | null;
   allowed_models: string[];
   created_at: string;
 }
 
+export type BudgetPeriod = "daily" | "weekly" | "monthly";
+
+export interface ModelPrice {
+  prompt_per_million: number;
+  completion_per_million: number;
+}
+
+export interface GatewayKeyBudget {
+  hard_limit: number | null;
+  soft_limit: number | null;
+  period: BudgetPeriod;
+  model_prices: Record<string, ModelPrice>;
+}
+
+export interface GatewayKeyBudgetStatus extends GatewayKeyBudget {
+  spend: number;
+  period_start: string;
+}
+
+export interface GatewayKeyDetails extends GatewayKey {
+  budget: GatewayKeyBudgetStatus | null;
+}
+
 export interface ListGatewayKeysParams {
   page?: number;
   page_size?: number;
@@ -20,6 +50,12 @@ export interface CreateGatewayKeyRequest {
   name?: string | null;
   rate_limit_rps?: number | null;
   rate_limit_rpm?: number | null;
+  rate_limit_tpm?: number | null;
+  rate_limit_tpd?: number | null;
+  max_concurrent_requests?: number | null;
+  cache_ttl_secs?: number | null;
+  cache_policy?: CachePolicy | null;
+  cache_non_deterministic?: boolean | null;
   allowed_models?: string[];
 }
 
@@ -28,6 +64,12 @@ export interface UpdateGatewayKeyRequest {
   enabled?: boolean;
   rate_limit_rps: number | null;
   rate_limit_rpm: number | null;
+  rate_limit_tpm: number | null;
+  rate_limit_tpd: number | null;
+  max_concurrent_requests: number | null;
+  cache_ttl_secs: number | null;
+  cache_policy: CachePolicy | null;
+  cache_non_deterministic: boolean | null;
   allowed_models?: string[];
 }
 
@@ -47,8 +89,8 @@ export async function createGatewayKey(payload: CreateGatewayKeyRequest): Promis
   });
 }
 
-export async function getGatewayKey(id: string): Promise<GatewayKey> {
-  return requestJson<GatewayKey>(`/gateway-keys/${id}`);
+export async function getGatewayKey(id: string): Promise<GatewayKeyDetails> {
+  return requestJson<GatewayKeyDetails>(`/gateway-keys/${id}`);
 }
 
 export async function updateGatewayKey(
@@ -66,3 +108,19 @@ export async function deleteGatewayKey(id: string): Promise<void> {
     method: "DELETE",
   });
 }
+
+export async function setGatewayKeyBudget(
+  id: string,
+  payload: GatewayKeyBudget,
+): Promise<GatewayKeyBudgetStatus> {
+  return requestJson<GatewayKeyBudgetStatus>(`/gateway-keys/${id}/budget`, {
+    method: "PUT",
+    body: payload,
+  });
+}
+
+export async function deleteGatewayKeyBudget(id: string): Promise<void> {
+  await requestJson<void>(`/gateway-keys/${id}/budget`, {
+    method: "DELETE",
+  });
+}
@@ -0,0 +1,45 @@
+import { requestJson } from "./client";
+
+export type ModelPricing = {
+  id: string;
+  provider_id: string;
+  provider_name: string;
+  model_id: string;
+  input_per_million: number;
+  output_per_million: number;
+  cached_input_per_million: number | null;
+  created_at: string;
+  updated_at: string;
+};
+
+export type ModelPricingRequest = {
+  provider_id: string;
+  model_id: string;
+  input_per_million: number;
+  output_per_million: number;
+  cached_input_per_million: number | null;
+};
+
+export function listPricing() {
+  return requestJson<ModelPricing[]>("/pricing");
+}
+
+export function createPricing(payload: ModelPricingRequest) {
+  return requestJson<ModelPricing>("/pricing", {
+    method: "POST",
+    body: payload,
+  });
+}
+
+export function updatePricing(id: string, payload: ModelPricingRequest) {
+  return requestJson<ModelPricing>(`/pricing/${id}`, {
+    method: "PUT",
+    body: payload,
+  });
+}
+
+export function deletePricing(id: string) {
+  return requestJson<void>(`/pricing/${id}`, {
+    method: "DELETE",
+  });
+}
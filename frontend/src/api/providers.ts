@@ -1,6 +1,6 @@
 import { requestJson } from "./client";
 
-export type ApiType = 'openai_chat_completions' | 'openai_embeddings' | 'openai_responses' | 'openai_models' | 'anthropic_messages';
+export type ApiType = 'openai_chat_completions' | 'openai_embeddings' | 'openai_responses' | 'openai_models' | 'anthropic_messages' | 'gemini_generate_content';
 
 export interface Provider {
   id: string;
@@ -27,6 +27,9 @@ export interface ProviderKey {
   key: string;
   usage_count: number;
   enabled: boolean;
+  cooldown_until: string | null;
+  disabled_reason: "manual" | "unauthorized" | null;
+  disabled_at: string | null;
   created_at: string;
 }
 
@@ -18,6 +18,9 @@ export interface RequestLogSummary {
   prompt_tokens: number | null;
   completion_tokens: number | null;
   total_tokens: number | null;
+  cached_tokens: number | null;
+  cost: number | null;
+  ttft_ms: number | null;
   created_at: string;
   cache_layer: string | null;
 }
@@ -41,6 +44,9 @@ export interface RequestLog {
   prompt_tokens: number | null;
   completion_tokens: number | null;
   total_tokens: number | null;
+  cached_tokens: number | null;
+  cost: number | null;
+  ttft_ms: number | null;
   created_at: string;
   cache_layer: string | null;
 }
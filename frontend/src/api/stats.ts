@@ -10,23 +10,90 @@ export interface CategoryCount {
   count: number;
 }
 
+export interface TokenUsagePoint {
+  time: string;
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_tokens: number;
+  cost: number;
+}
+
+export interface UsageBreakdown {
+  category: string | null;
+  requests: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_tokens: number;
+  cost: number;
+}
+
+export type StatsGranularity = "minute" | "hour" | "day";
+
 export interface DashboardStats {
+  granularity_seconds: number;
   requests_over_time: TimeSeriesPoint[];
+  tokens_over_time: TokenUsagePoint[];
   requests_by_provider: CategoryCount[];
+  top_gateway_keys: UsageBreakdown[];
+  top_aliases: UsageBreakdown[];
+  top_models: UsageBreakdown[];
+  top_api_types: UsageBreakdown[];
   cache_hit_requests: number;
   cache_total_requests: number;
   cache_hit_rate: number;
+  total_cost: number;
 }
 
 export interface StatsQuery {
   start?: string; // ISO string
   end?: string;   // ISO string
+  granularity?: StatsGranularity;
+  top?: number;
 }
 
 export async function getDashboardStats(query: StatsQuery = {}): Promise<DashboardStats> {
   const params = new URLSearchParams();
   if (query.start) params.append("start", query.start);
   if (query.end) params.append("end", query.end);
+  if (query.granularity) params.append("granularity", query.granularity);
+  if (query.top !== undefined) params.append("top", query.top.toString());
 
   return requestJson<DashboardStats>(`/stats?${params.toString()}`);
 }
+
+export type PerformanceGroupBy = "provider" | "alias";
+
+export interface PerformancePoint {
+  time: string;
+  category: string | null;
+  requests: number;
+  client_errors: number;
+  server_errors: number;
+  transport_errors: number;
+  error_rate: number;
+  latency_p50_ms: number | null;
+  latency_p90_ms: number | null;
+  latency_p99_ms: number | null;
+  ttft_p50_ms: number | null;
+  ttft_p90_ms: number | null;
+  ttft_p99_ms: number | null;
+}
+
+export interface PerformanceStats {
+  granularity_seconds: number;
+  series: PerformancePoint[];
+}
+
+export interface PerformanceQuery extends StatsQuery {
+  group_by?: PerformanceGroupBy;
+}
+
+export async function getPerformanceStats(query: PerformanceQuery = {}): Promise<PerformanceStats> {
+  const params = new URLSearchParams();
+  if (query.start) params.append("start", query.start);
+  if (query.end) params.append("end", query.end);
+  if (query.granularity) params.append("granularity", query.granularity);
+  if (query.group_by) params.append("group_by", query.group_by);
+
+  return requestJson<PerformanceStats>(`/stats/performance?${params.toString()}`);
+}
@@ -37,6 +37,11 @@ const router = createRouter({
           name: 'aliases',
           component: () => import('@/pages/AliasesPage.vue'),
         },
+        {
+          path: 'pricing',
+          name: 'pricing',
+          component: () => import('@/pages/PricingPage.vue'),
+        },
         {
           path: 'request-logs',
           name: 'request-logs',
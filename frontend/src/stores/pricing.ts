@@ -0,0 +1,103 @@
+import { defineStore } from "pinia";
+import { ref } from "vue";
+import {
+  listPricing as apiListPricing,
+  createPricing as apiCreatePricing,
+  updatePricing as apiUpdatePricing,
+  deletePricing as apiDeletePricing,
+  type ModelPricing,
+  type ModelPricingRequest,
+} from "@/api/pricing";
+import { ApiError } from "@/api/client";
+
+export const usePricingStore = defineStore("pricing", () => {
+  const pricing = ref<ModelPricing[]>([]);
+  const loading = ref(false);
+  const error = ref<string | null>(null);
+
+  async function fetchPricing() {
+    loading.value = true;
+    error.value = null;
+    try {
+      pricing.value = await apiListPricing();
+    } catch (e: unknown) {
+      if (e instanceof ApiError) {
+        error.value = e.message;
+      } else {
+        error.value = "Failed to fetch pricing";
+      }
+      throw e;
+    } finally {
+      loading.value = false;
+    }
+  }
+
+  async function createPricing(payload: ModelPricingRequest) {
+    loading.value = true;
+    error.value = null;
+    try {
+      const created = await apiCreatePricing(payload);
+      pricing.value.push(created);
+      return created;
+    } catch (e: unknown) {
+      if (e instanceof ApiError) {
+        error.value = e.message;
+      } else {
+        error.value = "Failed to create price";
+      }
+      throw e;
+    } finally {
+      loading.value = false;
+    }
+  }
+
+  async function updatePricing(id: string, payload: ModelPricingRequest) {
+    loading.value = true;
+    error.value = null;
+    try {
+      const updated = await apiUpdatePricing(id, payload);
+      const index = pricing.value.findIndex((p) => p.id === id);
+      if (index !== -1) {
+        pricing.value[index] = updated;
+      }
+      return updated;
+    } catch (e: unknown) {
+      if (e instanceof ApiError) {
+        error.value = e.message;
+      } else {
+        error.value = "Failed to update price";
+      }
+      throw e;
+    } finally {
+      loading.value = false;
+    }
+  }
+
+  async function deletePricing(id: string) {
+    loading.value = true;
+    error.value = null;
+    try {
+      await apiDeletePricing(id);
+      pricing.value = pricing.value.filter((p) => p.id !== id);
+    } catch (e: unknown) {
+      if (e instanceof ApiError) {
+        error.value = e.message;
+      } else {
+        error.value = "Failed to delete price";
+      }
+      throw e;
+    } finally {
+      loading.value = false;
+    }
+  }
+
+  return {
+    pricing,
+    loading,
+    error,
+    fetchPricing,
+    createPricing,
+    updatePricing,
+    deletePricing,
+  };
+});